@@ -0,0 +1,72 @@
+//! Compares relaying messages with `tell` and with `forward` through a wrapped `ActorRef`
+use acto::{
+    actor,
+    tokio::{TokioMailbox, TokioSpawner},
+    ActorRef, Result,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tokio::{runtime::Runtime, sync::oneshot};
+
+const MESSAGES: u64 = 10_000;
+
+enum Msg {
+    Item(u64),
+    Done(oneshot::Sender<u64>),
+}
+
+// spawns a consumer and returns a reference wrapped the way relaying code typically gets it
+fn consumer() -> ActorRef<Msg> {
+    let (aref, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+        let mut sum = 0;
+        while let Ok(msg) = ctx.receive().await {
+            match msg {
+                Msg::Item(n) => sum += n,
+                Msg::Done(tx) => {
+                    let _ = tx.send(sum);
+                    sum = 0;
+                }
+            }
+        }
+        Ok(())
+    });
+    aref.labeled("consumer").labeled("relay")
+}
+
+fn relay(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let _guard = rt.enter();
+    let aref = consumer();
+
+    let mut group = c.benchmark_group("relay");
+    group.throughput(Throughput::Elements(MESSAGES));
+    group.bench_function("tell", |b| {
+        b.iter_batched(
+            oneshot::channel,
+            |(tx, rx)| {
+                for n in 0..MESSAGES {
+                    aref.tell(Msg::Item(n));
+                }
+                aref.tell(Msg::Done(tx));
+                rt.block_on(rx).unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("forward", |b| {
+        b.iter_batched(
+            oneshot::channel,
+            |(tx, rx)| {
+                for n in 0..MESSAGES {
+                    let _ = aref.forward(Msg::Item(n));
+                }
+                let _ = aref.forward(Msg::Done(tx));
+                rt.block_on(rx).unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, relay);
+criterion_main!(benches);
@@ -0,0 +1,104 @@
+//! Many small actors: one tokio task each versus multiplexed onto a `SharedRuntime`
+use acto::{
+    actor,
+    tokio::{TokioMailbox, TokioSpawner},
+    ActorRef, Result, SharedRuntime, Spawner,
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicIsize, Ordering},
+};
+use tokio::{runtime::Runtime, sync::oneshot};
+
+const ACTORS: usize = 10_000;
+const DRIVERS: usize = 4;
+
+// tracks the live heap size, for comparing the memory held by idle actors
+struct Counting;
+
+static ALLOCATED: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size() as isize, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size() as isize, Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+type Msg = (u64, Option<oneshot::Sender<u64>>);
+
+// each actor adds up what it receives and replies with the total when asked
+fn spawn_counters<S: Spawner>(spawner: impl Fn() -> S) -> Vec<ActorRef<Msg>> {
+    (0..ACTORS)
+        .map(|_| {
+            let (aref, _handle) = actor!(TokioMailbox, spawner(), |ctx| {
+                let mut sum = 0;
+                while let Ok::<Msg, _>((n, reply)) = ctx.receive().await {
+                    sum += n;
+                    if let Some(reply) = reply {
+                        let _ = reply.send(sum);
+                    }
+                }
+                Ok(())
+            });
+            aref
+        })
+        .collect()
+}
+
+fn round(rt: &Runtime, actors: &[ActorRef<Msg>]) -> u64 {
+    let replies = actors
+        .iter()
+        .map(|aref| {
+            let (tx, rx) = oneshot::channel();
+            aref.tell((1, Some(tx)));
+            rx
+        })
+        .collect::<Vec<_>>();
+    rt.block_on(async {
+        let mut total = 0;
+        for rx in replies {
+            total += rx.await.unwrap();
+        }
+        total
+    })
+}
+
+fn bench<S: Spawner>(c: &mut Criterion, name: &str, rt: &Runtime, spawner: impl Fn() -> S) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let actors = spawn_counters(spawner);
+    // let every actor reach its first receive
+    round(rt, &actors);
+    let bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+    println!("{}: {} bytes per idle actor", name, bytes / ACTORS as isize);
+
+    let mut group = c.benchmark_group("many_actors");
+    group.throughput(Throughput::Elements(ACTORS as u64));
+    group.bench_function(name, |b| b.iter(|| round(rt, &actors)));
+    group.finish();
+}
+
+fn task_per_actor(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let _guard = rt.enter();
+    bench(c, "task_per_actor", &rt, || TokioSpawner);
+}
+
+fn shared_runtime(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let _guard = rt.enter();
+    let shared = SharedRuntime::new(TokioSpawner, DRIVERS);
+    bench(c, "shared_runtime", &rt, || shared.clone());
+}
+
+criterion_group!(benches, task_per_actor, shared_runtime);
+criterion_main!(benches);
@@ -0,0 +1,98 @@
+//! Message throughput of `TokioMailbox`, with one and with several producers
+use acto::{
+    actor,
+    tokio::{TokioMailbox, TokioSpawner},
+    ActorRef, Result,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use std::thread;
+use tokio::{runtime::Runtime, sync::oneshot};
+
+const MESSAGES: u64 = 100_000;
+const PRODUCERS: u64 = 4;
+
+enum Msg {
+    Item(u64),
+    // reply once this many items have been received
+    Expect(u64, oneshot::Sender<u64>),
+}
+
+fn consumer() -> ActorRef<Msg> {
+    let (aref, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+        let mut sum = 0;
+        let mut seen = 0;
+        let mut expected = None;
+        while let Ok(msg) = ctx.receive().await {
+            match msg {
+                Msg::Item(n) => {
+                    sum += n;
+                    seen += 1;
+                }
+                Msg::Expect(count, tx) => expected = Some((count, tx)),
+            }
+            if expected.as_ref().is_some_and(|(count, _)| *count == seen) {
+                let (_, tx) = expected.take().unwrap();
+                let _ = tx.send(sum);
+                sum = 0;
+                seen = 0;
+            }
+        }
+        Ok(())
+    });
+    aref
+}
+
+fn spsc(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let _guard = rt.enter();
+    let aref = consumer();
+
+    let mut group = c.benchmark_group("tokio_mailbox");
+    group.throughput(Throughput::Elements(MESSAGES));
+    group.bench_function("spsc", |b| {
+        b.iter_batched(
+            oneshot::channel,
+            |(tx, rx)| {
+                aref.tell(Msg::Expect(MESSAGES, tx));
+                for n in 0..MESSAGES {
+                    aref.tell(Msg::Item(n));
+                }
+                rt.block_on(rx).unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn mpsc(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let _guard = rt.enter();
+    let aref = consumer();
+
+    let mut group = c.benchmark_group("tokio_mailbox");
+    group.throughput(Throughput::Elements(MESSAGES));
+    group.bench_function("mpsc", |b| {
+        b.iter_batched(
+            oneshot::channel,
+            |(tx, rx)| {
+                aref.tell(Msg::Expect(MESSAGES, tx));
+                thread::scope(|s| {
+                    for _ in 0..PRODUCERS {
+                        s.spawn(|| {
+                            for n in 0..MESSAGES / PRODUCERS {
+                                aref.tell(Msg::Item(n));
+                            }
+                        });
+                    }
+                });
+                rt.block_on(rx).unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, spsc, mpsc);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+use acto::{
+    actor,
+    tokio::{TokioMailbox, TokioSpawner},
+    Context, Result, WithSpan,
+};
+use tracing::{info, info_span};
+
+// the logs from processing each message show the sender’s span
+async fn greeter(mut ctx: Context<WithSpan<String>>) -> Result<()> {
+    loop {
+        ctx.receive()
+            .await?
+            .in_span(|name| info!("Hello {}!", name));
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt().init();
+
+    let (aref, join_handle) = actor!(TokioMailbox, TokioSpawner, fn greeter(ctx));
+    let aref = aref.with_current_span();
+    info_span!("request", id = 1).in_scope(|| aref.tell("Fred".to_owned()));
+    info_span!("request", id = 2).in_scope(|| aref.tell("Barney".to_owned()));
+    drop(aref);
+    let _ = join_handle.await;
+}
@@ -0,0 +1,108 @@
+use crate::{ActorRef, Reply, Spawner};
+use anyhow::Result;
+use derive_more::{Display, Error};
+use std::{
+    fmt::{self, Debug},
+    future::{poll_fn, Future},
+    pin::pin,
+    task::Poll,
+    time::Duration,
+};
+
+/// A message together with the means of acknowledging it, see [`deliver_reliably`](fn.deliver_reliably.html)
+#[derive(Debug)]
+pub struct Envelope<M> {
+    pub msg: M,
+    pub ack: Ack,
+}
+
+/// Token for telling the sender whether a message has been processed
+///
+/// Dropping it without calling [`complete`](#method.complete) or [`nack`](#method.nack) — e.g.
+/// because the worker stopped — counts as [`AckStatus::Dropped`](enum.AckStatus.html).
+pub struct Ack(Reply<bool>);
+
+/// What happened to an acknowledged message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckStatus {
+    /// The message has been processed
+    Completed,
+    /// The worker declined to process the message
+    Nacked,
+    /// The [`Ack`](struct.Ack.html) was dropped without an answer
+    Dropped,
+}
+
+impl Ack {
+    /// Create a token and the future yielding its outcome
+    pub fn new() -> (Self, impl Future<Output = AckStatus> + Send + 'static) {
+        let (reply, answer) = Reply::new();
+        let status = async move {
+            match answer.await {
+                Ok(true) => AckStatus::Completed,
+                Ok(false) => AckStatus::Nacked,
+                Err(_) => AckStatus::Dropped,
+            }
+        };
+        (Self(reply), status)
+    }
+
+    pub fn complete(self) {
+        let _ = self.0.send(true);
+    }
+
+    pub fn nack(self) {
+        let _ = self.0.send(false);
+    }
+}
+
+impl Debug for Ack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Ack")
+    }
+}
+
+/// Error returned from [`deliver_reliably`](fn.deliver_reliably.html) when no worker completed the message
+#[derive(Debug, Display, Error)]
+#[display(fmt = "no worker acknowledged the message")]
+pub struct NotAcknowledged;
+
+/// Hand the message to one of the workers, trying the next one until it is acknowledged
+///
+/// The workers are tried in order, each getting a clone of the message in an
+/// [`Envelope`](struct.Envelope.html). A worker that nacks the message, drops its `Ack` — for
+/// example because it stopped — or does not answer within `timeout` is skipped. Returns the index
+/// of the worker that completed the message. The timers are obtained from the given `Spawner`.
+///
+/// Since a worker that times out may still process its copy, this gives at-least-once delivery.
+pub async fn deliver_reliably<M, S>(
+    spawner: &S,
+    workers: &[ActorRef<Envelope<M>>],
+    msg: M,
+    timeout: Duration,
+) -> Result<usize>
+where
+    M: Clone + Send + 'static,
+    S: Spawner + ?Sized,
+{
+    for (idx, worker) in workers.iter().enumerate() {
+        let (ack, status) = Ack::new();
+        worker.tell(Envelope {
+            msg: msg.clone(),
+            ack,
+        });
+        let mut status = pin!(status);
+        let mut delay = spawner.delay(timeout);
+        let status = poll_fn(|cx| {
+            if let Poll::Ready(status) = status.as_mut().poll(cx) {
+                return Poll::Ready(Some(status));
+            }
+            delay.as_mut().poll(cx).map(|_| None)
+        })
+        .await;
+        if status == Some(AckStatus::Completed) {
+            return Ok(idx);
+        }
+    }
+    Err(NotAcknowledged.into())
+}
@@ -1,26 +1,82 @@
-use crate::{FutureBox, FutureResultBox};
+use crate::{
+    handle::Control,
+    shutdown::ShutdownListener,
+    supervision::{spawn_supervised_child, Escalation, SupervisorFn},
+    watchdog::Watchdog,
+    ActorHandle, DeadLetter, DeadLetterReason, DelayBox, Directive, Either, FutureBox,
+    FutureResultBox, LocalFutureBox, LocalFutureResultBox, Permit, ReserveBox, Shutdown,
+    ShutdownToken, TaskHandle,
+};
 use anyhow::Result;
 use derive_more::{Display, Error};
 use parking_lot::Mutex;
 use std::{
-    future::Future,
-    pin::Pin,
+    collections::VecDeque,
+    fmt::{self, Debug},
+    future::{poll_fn, Future},
+    hash::{Hash, Hasher},
+    pin::{pin, Pin},
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Weak,
     },
     task::{Poll, Waker},
+    time::{Duration, Instant},
 };
 
 /// An ActorRef is the sending side of the actor’s mailbox, it can be freely cloned.
 ///
+/// All clones share the same mailbox and each of them keeps the actor alive: the number of
+/// `ActorRef`s is counted, including those obtained via [`Context::me`](struct.Context.html#method.me),
+/// [`contramap`](#method.contramap), or [`WeakActorRef::upgrade`](struct.WeakActorRef.html#method.upgrade).
+/// Dropping one of several clones therefore has no effect on the actor.
+///
 /// When the last `ActorRef` for an actor has been dropped, only the actor itself can
 /// fabricate a new one. And if the actor is waiting for new messages from its mailbox
 /// before a new `ActorRef` is created, the actor would be stuck in this state forever.
-/// Therefore, the actor’s `receive()` function returns an error in this case, which
-/// will usually lead to the actor shutting down.
+/// Therefore, the actor’s `receive()` function returns [`NoActorRef`](struct.NoActorRef.html)
+/// in this case — but only after all messages sent before have been received — which will
+/// usually lead to the actor shutting down.
+///
+/// Each mailbox gets a unique [`ActorId`](struct.ActorId.html) when it is created, which is shared
+/// by all clones of its `ActorRef`s; equality and hashing of `ActorRef`s are based on this id.
 pub struct ActorRef<M>(Arc<ActorRefInner<M>>);
 
+/// Unique identity of an actor’s mailbox, see [`ActorRef::id`](struct.ActorRef.html#method.id)
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display(fmt = "#{}", _0)]
+pub struct ActorId(u64);
+
+impl ActorId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl<M> PartialEq for ActorRef<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
+}
+
+impl<M> Eq for ActorRef<M> {}
+
+impl<M> Hash for ActorRef<M> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.id.hash(state)
+    }
+}
+
+impl<M> Debug for ActorRef<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.label {
+            Some(label) => write!(f, "ActorRef({}, {})", self.0.id, label),
+            None => write!(f, "ActorRef({})", self.0.id),
+        }
+    }
+}
+
 impl<M> Clone for ActorRef<M> {
     fn clone(&self) -> Self {
         self.0.count.fetch_add(1, Ordering::SeqCst);
@@ -41,135 +97,2083 @@ impl<M> Drop for ActorRef<M> {
 }
 
 impl<M> ActorRef<M> {
-    pub fn new(tell: Box<dyn Fn(M) + Send + Sync + 'static>) -> Self {
+    /// Create the sending side of a mailbox
+    ///
+    /// The [`Sender`](trait.Sender.html) may also be a plain closure that hands back the message
+    /// if it could not be enqueued, e.g. because the receiving side has been dropped.
+    pub fn new(sender: Box<dyn Sender<M>>) -> Self {
+        Self::with_id(sender, ActorId::next(), None)
+    }
+
+    // an ActorRef for the same actor, but sending via the given Sender
+    pub(crate) fn with_id(
+        sender: Box<dyn Sender<M>>,
+        id: ActorId,
+        label: Option<Arc<str>>,
+    ) -> Self {
+        Self::with_sender(MailboxSender::Boxed(sender), id, label)
+    }
+
+    // an ActorRef for a tokio mailbox, without the indirection through a boxed Sender
+    #[cfg(feature = "with_tokio")]
+    pub(crate) fn from_tokio(sender: crate::tokio::TokioSender<M>) -> Self {
+        Self::with_sender(MailboxSender::Tokio(sender), ActorId::next(), None)
+    }
+
+    fn with_sender(sender: MailboxSender<M>, id: ActorId, label: Option<Arc<str>>) -> Self {
         Self(Arc::new(ActorRefInner {
-            count: AtomicUsize::new(0),
+            id,
+            label,
+            count: AtomicUsize::new(1),
+            waker: Mutex::new(None),
+            closed: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            sender,
+            dead_letters: None,
+            direct: None,
+        }))
+    }
+
+    /// The identity of the actor’s mailbox
+    pub fn id(&self) -> ActorId {
+        self.0.id
+    }
+
+    /// The label given with [`labeled`](#method.labeled), if any
+    pub fn label(&self) -> Option<&str> {
+        self.0.label.as_deref()
+    }
+
+    /// Create an `ActorRef` that tags send failures with the given label
+    ///
+    /// The label is included in the errors from [`try_tell_labeled`](#method.try_tell_labeled) and
+    /// in the [`DeadLetter`](struct.DeadLetter.html)s it produces, which makes it
+    /// easier to tell which actor rejected a message. It is kept by clones and by the references
+    /// derived with [`contramap`](#method.contramap) and [`inspect`](#method.inspect). The returned
+    /// `ActorRef` has the same [`id`](#method.id) and dead-letter sink as `self`.
+    pub fn labeled(self, label: impl Into<Arc<str>>) -> ActorRef<M>
+    where
+        M: Send + 'static,
+    {
+        let dead_letters = self.0.dead_letters.clone();
+        let direct = Some(self.direct());
+        ActorRef(Arc::new(ActorRefInner {
+            id: self.id(),
+            label: Some(label.into()),
+            count: AtomicUsize::new(1),
             waker: Mutex::new(None),
-            tell,
+            closed: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            sender: MailboxSender::Boxed(Box::new(Forward(self))),
+            dead_letters,
+            direct,
         }))
     }
 
+    /// Create a reference that does not keep the actor alive
+    pub fn downgrade(&self) -> WeakActorRef<M> {
+        WeakActorRef(Arc::downgrade(&self.0))
+    }
+
+    // the shared mailbox state, for holding on to it without keeping the actor alive
+    pub(crate) fn inner(&self) -> Arc<ActorRefInner<M>> {
+        self.0.clone()
+    }
+
+    /// Send a message to the actor, ignoring delivery failures
+    ///
+    /// Undelivered messages are passed to the dead-letter sink if one has been configured with
+    /// [`with_dead_letters`](#method.with_dead_letters).
     pub fn tell(&self, msg: M) {
-        (self.0.tell)(msg);
+        if let Err(msg) = self.deliver(msg) {
+            if let Some(dead_letters) = &self.0.dead_letters {
+                dead_letters(msg, self.is_closed(), self.0.label.clone());
+            }
+        }
     }
-}
 
-struct ActorRefInner<M> {
-    count: AtomicUsize,
-    waker: Mutex<Option<Waker>>,
-    tell: Box<dyn Fn(M) + Send + Sync + 'static>, // TODO get rid of the box (requires unsafe)
-}
+    /// Send several messages to the actor, in order
+    ///
+    /// The messages are enqueued one after the other without yielding in between, which keeps
+    /// the window for other senders small, but it does not lock them out: messages from other
+    /// `ActorRef`s may still be interleaved with the batch. The messages of the batch are received
+    /// in iteration order, like consecutive calls to [`tell`](#method.tell) — including the
+    /// handling of undelivered messages.
+    pub fn tell_all(&self, msgs: impl IntoIterator<Item = M>) {
+        for msg in msgs {
+            self.tell(msg);
+        }
+    }
 
-/// The context in which an actor is running
-///
-/// This context allows the actor to
-///
-/// - receive messages
-/// - get its own address (i.e. [`ActorRef`](struct.ActorRef.html))
-/// - spawn new actors
-///
-/// The definition of child actors is best donw using the [`actor`](macro.actor.html) macro.
-pub struct Context<M> {
-    recv: Box<dyn Receiver<M>>,
-    aref: ActorRef<M>,
-    spawner: Arc<dyn Spawner>,
-}
+    // enqueue without dead-letter handling
+    fn deliver(&self, msg: M) -> std::result::Result<(), M> {
+        if self.0.closed.load(Ordering::SeqCst) {
+            return Err(msg);
+        }
+        self.0.sender.tell(msg)
+    }
 
-impl<M: Send + 'static> Context<M> {
-    pub fn new(mailbox: impl Mailbox, spawner: Arc<dyn Spawner>) -> Self {
-        let (aref, recv) = mailbox.make_mailbox();
-        Self {
-            recv,
-            aref,
-            spawner,
+    /// Send a message to the actor, handing it back if it could not be enqueued
+    ///
+    /// A failure usually means that the actor has stopped, but bounded mailboxes may also
+    /// reject messages when full.
+    pub fn try_tell(&self, msg: M) -> std::result::Result<(), SendError<M>> {
+        self.deliver(msg).map_err(SendError)
+    }
+
+    /// Like [`try_tell`](#method.try_tell), but the error also carries this reference’s
+    /// [label](#method.labeled), for attributing the failure further up the call chain
+    pub fn try_tell_labeled(&self, msg: M) -> std::result::Result<(), LabeledSendError<M>> {
+        self.try_tell(msg).map_err(|error| LabeledSendError {
+            error,
+            label: self.0.label.clone(),
+        })
+    }
+
+    /// Send a message straight to the actor’s mailbox, handing it back if it could not be enqueued
+    ///
+    /// This behaves like [`try_tell`](#method.try_tell), but references derived with
+    /// [`labeled`](#method.labeled) or [`with_dead_letters`](#method.with_dead_letters) do not
+    /// pass the message through each layer of wrapping: it is handed to the mailbox’s
+    /// [`Sender`](trait.Sender.html) directly, which is cheaper when relaying many messages.
+    /// References that transform or observe messages ([`contramap`](#method.contramap),
+    /// [`inspect`](#method.inspect)) still apply their function. Like with `try_tell`, the message
+    /// is handed back if any of the references in between has been [closed](#method.close).
+    pub fn forward(&self, msg: M) -> std::result::Result<(), SendError<M>> {
+        let result = match &self.0.direct {
+            // is_closed asks each wrapper’s Forward in turn, which is cheaper than sending through
+            Some(direct) if !self.is_closed() => direct.deliver(msg),
+            Some(_) => Err(msg),
+            None => self.deliver(msg),
+        };
+        result.map_err(SendError)
+    }
+
+    // the reference whose sender is the mailbox’s, skipping Forward wrappers
+    fn direct(&self) -> ActorRef<M> {
+        self.0.direct.clone().unwrap_or_else(|| self.clone())
+    }
+
+    /// Send a message to the actor, waiting for room in a bounded mailbox
+    ///
+    /// For unbounded mailboxes this completes immediately, like [`try_tell`](#method.try_tell).
+    /// For bounded ones, the message is enqueued as soon as there is space, bypassing the
+    /// mailbox’s policy for full queues. Dropping the returned future before it completes drops
+    /// the message without sending it. Fails if the actor has stopped, with an error that carries
+    /// this reference’s [label](#method.labeled) like [`try_tell_labeled`](#method.try_tell_labeled).
+    pub fn send(&self, msg: M) -> impl Future<Output = std::result::Result<(), LabeledSendError<M>>>
+    where
+        M: Send + 'static,
+    {
+        // either the result of sending right away, or what is needed for waiting
+        let state = match self.reserve() {
+            Some(reserve) => Err((reserve, msg)),
+            None => Ok(self.try_tell_labeled(msg)),
+        };
+        let label = self.0.label.clone();
+        async move {
+            let (reserve, msg) = match state {
+                Ok(sent) => return sent,
+                Err(waiting) => waiting,
+            };
+            match reserve.await {
+                Some(permit) => {
+                    permit(msg);
+                    Ok(())
+                }
+                None => Err(LabeledSendError {
+                    error: SendError(msg),
+                    label,
+                }),
+            }
         }
     }
 
-    pub fn inherit<N: Send + 'static, MB: Mailbox>(&self, mailbox: MB) -> Context<N> {
-        let (aref, recv) = mailbox.make_mailbox();
-        Context {
-            recv,
-            aref,
-            spawner: self.spawner.clone(),
+    /// The number of messages currently waiting in the actor’s mailbox
+    ///
+    /// This is only a snapshot, which may already be outdated when this method returns.
+    /// Mailboxes that cannot track their length always report zero, which for a `TokioMailbox`
+    /// includes those whose [`tokio_sender`](#method.tokio_sender) has been used.
+    pub fn len(&self) -> usize {
+        self.0.sender.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The maximum number of messages the actor’s mailbox can hold, `None` if unbounded
+    pub fn capacity(&self) -> Option<usize> {
+        self.0.sender.capacity()
+    }
+
+    /// The tokio channel behind a [`TokioMailbox`](tokio/struct.TokioMailbox.html), `None` for other mailboxes
+    ///
+    /// This is an escape hatch for handing the actor’s mailbox to code that expects a tokio
+    /// `UnboundedSender`, it is only available with the `with_tokio` feature. Messages sent through
+    /// it go to the same queue as those sent via the `ActorRef`, in the order they were sent, but
+    /// they bypass everything else the `ActorRef` does: rejected messages do not become dead
+    /// letters, and the sender does not keep the actor alive, i.e. it still stops with
+    /// [`NoActorRef`](struct.NoActorRef.html) once the last `ActorRef` is gone.
+    ///
+    /// Since receiving cannot tell the messages sent through it apart, calling this switches off
+    /// the length tracking of the mailbox: [`len`](#method.len) reports zero from then on.
+    #[cfg(feature = "with_tokio")]
+    pub fn tokio_sender(&self) -> Option<tokio::sync::mpsc::UnboundedSender<M>> {
+        match &self.direct().0.sender {
+            MailboxSender::Tokio(sender) => Some(sender.raw()),
+            MailboxSender::Boxed(_) => None,
         }
     }
 
-    /// Receive the next message from the mailbox, possibly waiting for it
+    // whether the mailbox is known to be gone
+    pub(crate) fn is_closed(&self) -> bool {
+        self.0.closed.load(Ordering::SeqCst) || self.0.sender.is_closed()
+    }
+
+    /// Whether the actor may still receive messages sent via this `ActorRef`
     ///
-    /// This method’s return value should always be immediately `.await`ed, it has no other use.
-    pub fn receive(&mut self) -> ReceiveFuture<'_, M> {
-        ReceiveFuture {
-            aref: &self.aref.0,
-            fut: self.recv.receive(),
+    /// This becomes `false` once the actor has stopped (more precisely: its mailbox has been
+    /// dropped) or the `ActorRef` was [`close`](#method.close)d. It is only a cheap pre-check, e.g.
+    /// for skipping expensive work to build a message: the actor may stop right after this
+    /// returned `true`, so use [`try_tell`](#method.try_tell) to reliably detect delivery
+    /// failures. Mailboxes that cannot detect the actor stopping always report `true` until closed.
+    pub fn is_alive(&self) -> bool {
+        !self.is_closed()
+    }
+
+    /// Stop accepting messages, while letting the actor process those already queued
+    ///
+    /// Afterwards all sending methods fail, for all clones of this `ActorRef` and those derived
+    /// from it, and the actor’s `receive()` returns [`NoActorRef`](struct.NoActorRef.html) once
+    /// the mailbox has been drained — just as if the last `ActorRef` had been dropped, but without
+    /// having to track down all of them. Closing an `ActorRef` obtained via
+    /// [`contramap`](#method.contramap) or similar closes the actor it sends to.
+    pub fn close(&self) {
+        self.0.closed.store(true, Ordering::SeqCst);
+        self.0.sender.close();
+        if let Some(waker) = self.0.waker.lock().take() {
+            waker.wake();
         }
     }
 
-    /// The actors own address a.k.a. ActorRef
+    /// Tell the actor to stop, even if its mailbox is full
     ///
-    /// The actor can put this `ActorRef` into messages to send it to other actors so that they
-    /// can contact this actor back later.
-    pub fn me(&self) -> ActorRef<M> {
-        self.aref.clone()
+    /// This bypasses the mailbox: the actor’s next `receive()` fails with
+    /// [`Shutdown`](struct.Shutdown.html) before any queued message is looked at, as does every
+    /// further one. Unlike [`close`](#method.close), the queued messages are therefore not
+    /// processed, and unlike dropping all `ActorRef`s this does not depend on the mailbox having
+    /// room for a dedicated stop message. Signalling an `ActorRef` obtained via
+    /// [`contramap`](#method.contramap) or similar stops the actor it sends to.
+    pub fn stop_signal(&self) {
+        self.0.stopped.store(true, Ordering::SeqCst);
+        self.0.sender.stop_signal();
+        if let Some(waker) = self.0.waker.lock().take() {
+            waker.wake();
+        }
     }
 
-    /// Spawn a future for the purpose of running a child actor
+    /// Turn this `ActorRef` into a [`Sink`](https://docs.rs/futures/0.3/futures/sink/trait.Sink.html)
     ///
-    /// This method is best used via the [`actor`](macro.actor.html) macro.
-    pub fn spawn<F>(&self, fut: F) -> impl Future<Output = Result<F::Output>> + Send + 'static
+    /// See [`ActorSink`](struct.ActorSink.html) for details.
+    pub fn into_sink(self) -> crate::ActorSink<M> {
+        crate::ActorSink::new(self)
+    }
+
+    // used by ActorSink
+    pub(crate) fn reserve(&self) -> Option<ReserveBox<M>> {
+        if self.0.closed.load(Ordering::SeqCst) {
+            // the caller falls back to sending right away, which fails
+            return None;
+        }
+        self.0.sender.reserve()
+    }
+
+    /// Create an `ActorRef` that transforms each message using `f` before sending it to this actor
+    ///
+    /// The returned `ActorRef` keeps this actor alive like a clone of `self` would. Delivery failure
+    /// is detected before applying `f` if the actor is known to have stopped, so that `try_tell`
+    /// can hand back the original message; if the transformed message is rejected nonetheless
+    /// (e.g. due to a race with the actor stopping, or a full bounded mailbox), it is dropped.
+    ///
+    /// The returned `ActorRef` has the same [`id`](#method.id) as this one.
+    pub fn contramap<N, F>(self, f: F) -> ActorRef<N>
     where
-        F: Future + Send + 'static,
-        F::Output: Send + 'static,
+        M: Send + 'static,
+        N: 'static,
+        F: Fn(N) -> M + Send + Sync + 'static,
     {
-        crate::spawn(&*self.spawner, fut)
+        let id = self.id();
+        let label = self.0.label.clone();
+        let f = Arc::new(f);
+        ActorRef::with_id(Box::new(Contramap { target: self, f }), id, label)
+    }
+
+    /// Create an `ActorRef` that calls `f` with each message before sending it to this actor
+    ///
+    /// This is meant for instrumentation like logging or counting messages, see also
+    /// [`Traced`](struct.Traced.html). The returned `ActorRef` keeps this actor alive like a
+    /// clone of `self` would and has the same [`id`](#method.id).
+    pub fn inspect(self, f: impl Fn(&M) + Send + Sync + 'static) -> ActorRef<M>
+    where
+        M: Send + 'static,
+    {
+        let id = self.id();
+        let label = self.0.label.clone();
+        ActorRef::with_id(
+            Box::new(Inspect {
+                target: self,
+                f: Arc::new(f),
+            }),
+            id,
+            label,
+        )
+    }
+
+    /// Create an `ActorRef` that forwards messages it cannot deliver to the given sink
+    ///
+    /// This applies to messages sent with [`tell`](#method.tell), while `try_tell` still hands
+    /// them back to the caller. The returned `ActorRef` keeps this actor alive like a clone of
+    /// `self` would and has the same [`id`](#method.id), other `ActorRef`s for this actor are
+    /// unaffected.
+    pub fn with_dead_letters(self, sink: ActorRef<DeadLetter>) -> ActorRef<M>
+    where
+        M: Send + 'static,
+    {
+        let direct = Some(self.direct());
+        let mut inner = ActorRefInner {
+            id: self.id(),
+            label: self.0.label.clone(),
+            count: AtomicUsize::new(1),
+            waker: Mutex::new(None),
+            closed: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            sender: MailboxSender::Boxed(Box::new(Forward(self))),
+            dead_letters: None,
+            direct,
+        };
+        inner.dead_letters = Some(Arc::new(move |msg, closed, label| {
+            let reason = if closed {
+                DeadLetterReason::Closed
+            } else {
+                DeadLetterReason::Rejected
+            };
+            sink.tell(DeadLetter::new(msg, reason, label));
+        }));
+        ActorRef(Arc::new(inner))
+    }
+
+    /// Send a message to the actor after the given delay
+    ///
+    /// The timer runs as a task spawned using the given `Spawner`. Dropping the returned
+    /// [`CancelHandle`](struct.CancelHandle.html) cancels the delivery if it has not yet happened.
+    pub fn tell_after_with<S: Spawner + ?Sized>(
+        &self,
+        spawner: &S,
+        msg: M,
+        delay: Duration,
+    ) -> CancelHandle
+    where
+        M: Send + 'static,
+    {
+        let aref = self.clone();
+        CancelHandle::schedule(spawner, delay, move || aref.tell(msg))
+    }
+
+    /// Send a message to the actor at the given point in time
+    ///
+    /// See [`tell_after_with`](#method.tell_after_with), a deadline in the past leads to
+    /// immediate delivery.
+    pub fn tell_at_with<S: Spawner + ?Sized>(
+        &self,
+        spawner: &S,
+        msg: M,
+        deadline: Instant,
+    ) -> CancelHandle
+    where
+        M: Send + 'static,
+    {
+        let delay = deadline.saturating_duration_since(spawner.now());
+        self.tell_after_with(spawner, msg, delay)
+    }
+
+    /// Send a request to this actor and wait for its reply
+    ///
+    /// A temporary mailbox is created using the given `Mailbox`, its `ActorRef` is passed to
+    /// `make_msg` for embedding it in the request, and the returned future resolves with the
+    /// first message delivered to it. If the recipient drops the reply `ActorRef` without
+    /// answering, the future fails with [`NoActorRef`](struct.NoActorRef.html).
+    ///
+    /// The request is sent immediately, the temporary mailbox is cleaned up when the future is
+    /// dropped.
+    pub fn ask_with<R, MB>(
+        &self,
+        mailbox: MB,
+        make_msg: impl FnOnce(ActorRef<R>) -> M,
+    ) -> impl Future<Output = Result<R>> + Send + 'static
+    where
+        R: Send + 'static,
+        MB: MailboxFor<R>,
+    {
+        let (aref, mut recv) = mailbox.make_mailbox_for();
+        let inner = aref.0.clone();
+        self.tell(make_msg(aref));
+        async move {
+            ReceiveFuture {
+                aref: &inner,
+                unstashed: &mut VecDeque::new(),
+                shutdown: None,
+                escalation: None,
+                on_no_refs: NoRefsPolicy::DrainThenStop,
+                watchdog: None,
+                takes_turns: false,
+                fut: recv.receive(),
+            }
+            .await
+        }
+    }
+
+    /// Wait until the actor has processed all messages sent so far via this `ActorRef`
+    ///
+    /// The marker created by `make_marker` is enqueued behind those messages, the returned future
+    /// resolves once the actor answers the embedded [`Reply`](struct.Reply.html), as in
+    /// `Msg::Flush(reply) => { let _ = reply.send(()); }`. Unlike watching [`len`](#method.len),
+    /// this also covers the message the actor is currently processing. It fails right away with a
+    /// `SendError<()>` if the marker cannot be sent (the marker itself is dropped), and with [`NoActorRef`](struct.NoActorRef.html) if the `Reply` is
+    /// dropped without an answer — e.g. because the actor stopped before reaching the marker, in
+    /// which case nothing can be said about the earlier messages.
+    ///
+    /// Mailboxes are FIFO per sender only: messages sent concurrently from other threads or
+    /// actors may still be queued after the marker.
+    pub fn flush(
+        &self,
+        make_marker: impl FnOnce(Reply<()>) -> M,
+    ) -> impl Future<Output = Result<()>> + Send + 'static {
+        let (reply, reached) = Reply::new();
+        let sent = self.try_tell(make_marker(reply)).map_err(|_| SendError(()));
+        async move {
+            sent?;
+            reached.await
+        }
+    }
+
+    /// Like [`ask_with`](#method.ask_with), but giving up after the given duration
+    ///
+    /// Fails with [`AskTimeout`](struct.AskTimeout.html) if no reply arrived in time, or with
+    /// [`NoActorRef`](struct.NoActorRef.html) if the recipient dropped the reply `ActorRef`. The
+    /// timer is obtained from the given `Spawner`; the temporary mailbox and the timer are dropped
+    /// as soon as the returned future completes.
+    pub fn ask_timeout_with<R, MB, S>(
+        &self,
+        mailbox: MB,
+        spawner: &S,
+        make_msg: impl FnOnce(ActorRef<R>) -> M,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<R>> + Send + 'static
+    where
+        R: Send + 'static,
+        MB: MailboxFor<R>,
+        S: Spawner + ?Sized,
+    {
+        let reply = self.ask_with(mailbox, make_msg);
+        let mut delay = spawner.delay(timeout);
+        async move {
+            let mut reply = pin!(reply);
+            poll_fn(|cx| {
+                if let Poll::Ready(result) = reply.as_mut().poll(cx) {
+                    return Poll::Ready(result);
+                }
+                delay.as_mut().poll(cx).map(|_| Err(AskTimeout.into()))
+            })
+            .await
+        }
     }
 }
 
-pub struct ReceiveFuture<'a, M: Send + 'static> {
-    aref: &'a ActorRefInner<M>,
-    fut: &'a mut (dyn Future<Output = Result<M>> + Send + Unpin + 'a),
+/// The means of answering a request exactly once
+///
+/// Embed it in request messages, as in `enum Msg { GetCount(Reply<u64>) }`, and await the future
+/// returned together with it from [`new`](#method.new). This future fails with
+/// [`NoActorRef`](struct.NoActorRef.html) if the `Reply` is dropped without sending a value.
+pub struct Reply<R>(ActorRef<R>);
+
+impl<R: Send + 'static> Reply<R> {
+    pub fn new() -> (Self, impl Future<Output = Result<R>> + Send + 'static) {
+        let (aref, mut recv) = ReplySlot.make_mailbox();
+        let inner = aref.0.clone();
+        let reply = async move {
+            ReceiveFuture {
+                aref: &inner,
+                unstashed: &mut VecDeque::new(),
+                shutdown: None,
+                escalation: None,
+                on_no_refs: NoRefsPolicy::DrainThenStop,
+                watchdog: None,
+                takes_turns: false,
+                fut: recv.receive(),
+            }
+            .await
+        };
+        (Self(aref), reply)
+    }
+
+    /// Answer the request, handing back the value if nobody is waiting for it anymore
+    pub fn send(self, value: R) -> std::result::Result<(), SendError<R>> {
+        self.0.try_tell(value)
+    }
 }
 
-impl<'a, M: Send + 'static> Future for ReceiveFuture<'a, M> {
-    type Output = Result<M>;
+impl<R> Debug for Reply<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Reply({})", self.0 .0.id)
+    }
+}
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
-        match Pin::new(&mut *self.fut).poll(cx) {
-            Poll::Ready(x) => Poll::Ready(x),
-            Poll::Pending => {
-                if self.aref.count.load(Ordering::SeqCst) == 0 {
-                    // observing no external address means that there cannot be one created, either:
-                    // we hold an exclusive reference on the Context (via self.fut)
-                    Poll::Ready(Err(NoActorRef.into()))
+/// An [`ActorRef`](struct.ActorRef.html) that does not keep the actor alive, see [`ActorRef::downgrade`](struct.ActorRef.html#method.downgrade)
+///
+/// The actor’s `receive()` fails with [`NoActorRef`](struct.NoActorRef.html) once all strong
+/// `ActorRef`s are gone, regardless of any `WeakActorRef`s. From then on, `upgrade` fails as well.
+pub struct WeakActorRef<M>(Weak<ActorRefInner<M>>);
+
+impl<M> Clone for WeakActorRef<M> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<M> WeakActorRef<M> {
+    /// Obtain an `ActorRef` if there still is one
+    pub fn upgrade(&self) -> Option<ActorRef<M>> {
+        let inner = self.0.upgrade()?;
+        // once the count has dropped to zero the actor may have observed it, so it must not be revived
+        inner
+            .count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                if count == 0 {
+                    None
                 } else {
-                    *self.aref.waker.lock() = Some(cx.waker().clone());
-                    // in case the last ActorRef was dropped between the check and installing the waker,
-                    // we must now re-check
-                    if self.aref.count.load(Ordering::SeqCst) == 0 {
-                        Poll::Ready(Err(NoActorRef.into()))
-                    } else {
-                        Poll::Pending
-                    }
+                    Some(count + 1)
                 }
-            }
+            })
+            .ok()?;
+        Some(ActorRef(inner))
+    }
+}
+
+pub(crate) struct ActorRefInner<M> {
+    id: ActorId,
+    label: Option<Arc<str>>,
+    count: AtomicUsize,
+    waker: Mutex<Option<Waker>>,
+    // set by ActorRef::close
+    closed: AtomicBool,
+    // set by ActorRef::stop_signal
+    stopped: AtomicBool,
+    sender: MailboxSender<M>,
+    // called with undelivered messages, whether the mailbox was closed, and the sender’s label
+    #[allow(clippy::type_complexity)]
+    dead_letters: Option<Arc<dyn Fn(M, bool, Option<Arc<str>>) + Send + Sync>>,
+    // the reference owning the mailbox sender, for references that merely wrap it with Forward
+    direct: Option<ActorRef<M>>,
+}
+
+// the sending side, with tokio’s unbounded channel stored inline since it is the common case;
+// this is Send + Sync if M: Send, like tokio’s UnboundedSender
+pub(crate) enum MailboxSender<M> {
+    #[cfg(feature = "with_tokio")]
+    Tokio(crate::tokio::TokioSender<M>),
+    Boxed(Box<dyn Sender<M> + Send + Sync>),
+}
+
+impl<M> MailboxSender<M> {
+    #[inline]
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        match self {
+            #[cfg(feature = "with_tokio")]
+            MailboxSender::Tokio(sender) => sender.tell(msg),
+            MailboxSender::Boxed(sender) => sender.tell(msg),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            #[cfg(feature = "with_tokio")]
+            MailboxSender::Tokio(sender) => sender.len(),
+            MailboxSender::Boxed(sender) => sender.len(),
+        }
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        match self {
+            #[cfg(feature = "with_tokio")]
+            MailboxSender::Tokio(_) => None,
+            MailboxSender::Boxed(sender) => sender.capacity(),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        match self {
+            #[cfg(feature = "with_tokio")]
+            MailboxSender::Tokio(sender) => sender.is_closed(),
+            MailboxSender::Boxed(sender) => sender.is_closed(),
+        }
+    }
+
+    fn close(&self) {
+        match self {
+            #[cfg(feature = "with_tokio")]
+            MailboxSender::Tokio(_) => {}
+            MailboxSender::Boxed(sender) => sender.close(),
+        }
+    }
+
+    fn stop_signal(&self) {
+        match self {
+            #[cfg(feature = "with_tokio")]
+            MailboxSender::Tokio(_) => {}
+            MailboxSender::Boxed(sender) => sender.stop_signal(),
+        }
+    }
+
+    fn reserve(&self) -> Option<ReserveBox<M>> {
+        match self {
+            #[cfg(feature = "with_tokio")]
+            MailboxSender::Tokio(_) => None,
+            MailboxSender::Boxed(sender) => sender.reserve(),
         }
     }
 }
 
-#[derive(Debug, Display, Error)]
-#[display(fmt = "cannot receive: no external ActorRef for this actor")]
-pub struct NoActorRef;
+impl<M> ActorRefInner<M> {
+    // no more messages can arrive once the mailbox has been drained
+    fn is_orphaned(&self) -> bool {
+        self.count.load(Ordering::SeqCst) == 0 || self.closed.load(Ordering::SeqCst)
+    }
+}
 
-pub trait Receiver<M: Send + 'static>: Send {
-    // this trait is only necessary because FnMut doesn’t make its argument’s self reference lifetime available
-    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_);
+/// Handle for a scheduled message delivery, dropping it cancels the delivery
+#[must_use = "dropping the CancelHandle cancels the scheduled delivery"]
+pub struct CancelHandle(Arc<Mutex<CancelState>>);
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: bool,
+    waker: Option<Waker>,
 }
 
-/// Factory for mailboxes, which usually are MPSC queues under the hood
-pub trait Mailbox {
-    fn make_mailbox<M: Send + 'static>(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>);
+impl CancelHandle {
+    // run `f` after the delay unless cancelled
+    fn schedule<S: Spawner + ?Sized>(
+        spawner: &S,
+        delay: Duration,
+        f: impl FnOnce() + Send + 'static,
+    ) -> Self {
+        let handle = CancelHandle(Arc::new(Mutex::new(CancelState::default())));
+        let state = handle.0.clone();
+        let mut timer = spawner.delay(delay);
+        drop(crate::spawn(spawner, async move {
+            let fired = poll_fn(|cx| {
+                let mut state = state.lock();
+                if state.cancelled {
+                    return Poll::Ready(false);
+                }
+                state.waker = Some(cx.waker().clone());
+                timer.as_mut().poll(cx).map(|_| true)
+            })
+            .await;
+            if fired {
+                f();
+            }
+        }));
+        handle
+    }
+
+    /// Cancel the scheduled delivery, same as dropping the handle
+    pub fn cancel(self) {}
 }
 
-/// Facility for spawning a particular kind of Future that is used to run actors
-pub trait Spawner: Send + Sync + 'static {
-    fn spawn(&self, fut: FutureBox) -> FutureResultBox;
+impl Drop for CancelHandle {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.cancelled = true;
+        // wake up the timer task so that it releases its ActorRef right away
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Contramap<M, F> {
+    target: ActorRef<M>,
+    // shared with the permits handed out by reserve
+    f: Arc<F>,
+}
+
+impl<M: Send + 'static, N: 'static, F> Sender<N> for Contramap<M, F>
+where
+    F: Fn(N) -> M + Send + Sync + 'static,
+{
+    fn tell(&self, msg: N) -> std::result::Result<(), N> {
+        if self.target.is_closed() {
+            return Err(msg);
+        }
+        self.target.tell((self.f)(msg));
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.target.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.target.capacity()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.target.is_closed()
+    }
+
+    fn close(&self) {
+        self.target.close()
+    }
+
+    fn stop_signal(&self) {
+        self.target.stop_signal()
+    }
+
+    fn reserve(&self) -> Option<ReserveBox<N>> {
+        let reserve = self.target.reserve()?;
+        let f = self.f.clone();
+        Some(Box::pin(async move {
+            let permit = reserve.await?;
+            Some(Box::new(move |msg: N| permit(f(msg))) as Permit<N>)
+        }))
+    }
+}
+
+// another ActorRef’s mailbox, without its dead-letter handling
+struct Forward<M>(ActorRef<M>);
+
+impl<M: Send> Sender<M> for Forward<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        self.0.deliver(msg)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.0.capacity()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    fn close(&self) {
+        self.0.close()
+    }
+
+    fn stop_signal(&self) {
+        self.0.stop_signal()
+    }
+
+    fn reserve(&self) -> Option<ReserveBox<M>> {
+        self.0.reserve()
+    }
+}
+
+struct Inspect<M> {
+    target: ActorRef<M>,
+    f: Arc<dyn Fn(&M) + Send + Sync>,
+}
+
+impl<M: Send + 'static> Sender<M> for Inspect<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        (self.f)(&msg);
+        self.target.deliver(msg)
+    }
+
+    fn len(&self) -> usize {
+        self.target.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.target.capacity()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.target.is_closed()
+    }
+
+    fn close(&self) {
+        self.target.close()
+    }
+
+    fn stop_signal(&self) {
+        self.target.stop_signal()
+    }
+
+    fn reserve(&self) -> Option<ReserveBox<M>> {
+        let reserve = self.target.reserve()?;
+        let f = self.f.clone();
+        Some(Box::pin(async move {
+            let permit = reserve.await?;
+            Some(Box::new(move |msg: M| {
+                f(&msg);
+                permit(msg)
+            }) as Permit<M>)
+        }))
+    }
+}
+
+/// The context in which an actor is running
+///
+/// This context allows the actor to
+///
+/// - receive messages
+/// - get its own address (i.e. [`ActorRef`](struct.ActorRef.html))
+/// - spawn new actors
+///
+/// The definition of child actors is best donw using the [`actor`](macro.actor.html) macro.
+pub struct Context<M> {
+    recv: Box<dyn Receiver<M>>,
+    // not counted as an ActorRef, otherwise the actor would always keep itself alive
+    aref: Arc<ActorRefInner<M>>,
+    spawner: Arc<dyn Spawner>,
+    // see Spawner::takes_turns, looked up once instead of with every message
+    takes_turns: bool,
+    ticker: Option<Ticker>,
+    stash: VecDeque<M>,
+    // messages to be received before those in the mailbox
+    unstashed: VecDeque<M>,
+    shutdown: Option<ShutdownListener>,
+    config: ActorConfig,
+    // stop signals of the actors started with spawn_child
+    children: Vec<ShutdownToken>,
+    supervisor: Option<SupervisorFn>,
+    escalation: Option<Escalation>,
+    // the top one handles the next message, it is taken out (None) while being called
+    behaviors: Vec<Option<Behavior<M>>>,
+    watchdog: Option<Watchdog>,
+    // tasks from spawn_task, aborted when the Context is dropped
+    tasks: Mutex<Vec<Arc<Control>>>,
+}
+
+type Behavior<M> =
+    Box<dyn FnMut(M, &mut Context<M>) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send>;
+
+/// What an actor does with queued messages once its last [`ActorRef`](struct.ActorRef.html) is gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoRefsPolicy {
+    /// Keep receiving until the mailbox is empty, then fail with [`NoActorRef`](struct.NoActorRef.html)
+    #[default]
+    DrainThenStop,
+    /// Fail with [`NoActorRef`](struct.NoActorRef.html) right away, abandoning queued messages
+    StopImmediately,
+}
+
+/// Per-actor settings, see [`Context::set_config`](struct.Context.html#method.set_config)
+#[derive(Debug, Clone, Default)]
+pub struct ActorConfig {
+    pub on_no_refs: NoRefsPolicy,
+    /// Log how the actor ended before its task finishes, for actors spawned with [`actor!`](macro.actor.html)
+    /// or an [`ActorBuilder`](struct.ActorBuilder.html)
+    ///
+    /// Errors and panics are logged at error level, stopping due to
+    /// [`NoActorRef`](struct.NoActorRef.html) or [`Shutdown`](struct.Shutdown.html) at debug level,
+    /// together with the actor’s id; named actors run within a span that records the name.
+    ///
+    /// Without the `with_tracing` feature this is a no-op: nothing is logged and no error is
+    /// reported for enabling it, so check the feature when the logs stay empty.
+    pub log_errors: bool,
+}
+
+// logs the outcome of an actor spawned with the actor! macro, see ActorConfig::log_errors
+#[doc(hidden)]
+pub struct ErrorLog {
+    #[allow(dead_code)]
+    id: Option<ActorId>,
+}
+
+impl ErrorLog {
+    pub fn wrap<F, T>(self, fut: F) -> impl Future<Output = Result<T>>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        #[cfg(feature = "with_tracing")]
+        {
+            async move {
+                let mut fut = pin!(fut);
+                let id = match self.id {
+                    Some(id) => id,
+                    None => return fut.await,
+                };
+                let polled = poll_fn(|cx| {
+                    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        fut.as_mut().poll(cx)
+                    })) {
+                        Ok(poll) => poll.map(Ok),
+                        Err(payload) => Poll::Ready(Err(payload)),
+                    }
+                })
+                .await;
+                let result = match polled {
+                    Ok(result) => result,
+                    Err(payload) => {
+                        tracing::error!(actor = %id, "actor panicked: {}", crate::panic_message(&*payload));
+                        std::panic::resume_unwind(payload)
+                    }
+                };
+                match &result {
+                    Ok(_) => {}
+                    Err(err) if err.is::<NoActorRef>() || err.is::<Shutdown>() => {
+                        tracing::debug!(actor = %id, "actor stopped: {}", err)
+                    }
+                    Err(err) => tracing::error!(actor = %id, "actor failed: {:#}", err),
+                }
+                result
+            }
+        }
+        #[cfg(not(feature = "with_tracing"))]
+        fut
+    }
+}
+
+struct Ticker {
+    period: Duration,
+    next: Instant,
+    delay: DelayBox,
+}
+
+/// Result of [`Context::receive_or_tick`](struct.Context.html#method.receive_or_tick)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<M> {
+    Message(M),
+    Tick,
+}
+
+/// Result of [`Context::recv`](struct.Context.html#method.recv)
+#[derive(Debug)]
+pub enum RecvResult<M> {
+    Message(M),
+    /// No external `ActorRef` is left or the mailbox was closed, see [`NoActorRef`](struct.NoActorRef.html)
+    Closed,
+    /// Any other reason why no message can be received, e.g. [`Shutdown`](struct.Shutdown.html)
+    Failed(anyhow::Error),
+}
+
+impl<M: Send + 'static> Context<M> {
+    pub fn new(mailbox: impl MailboxFor<M>, spawner: Arc<dyn Spawner>) -> Self {
+        let (aref, recv) = mailbox.make_mailbox_for();
+        Self::from_parts(aref.0.clone(), recv, spawner)
+    }
+
+    pub fn inherit<N: Send + 'static, MB: MailboxFor<N>>(&self, mailbox: MB) -> Context<N> {
+        let (aref, recv) = mailbox.make_mailbox_for();
+        let mut ctx = Context::from_parts(aref.0.clone(), recv, self.spawner.clone());
+        ctx.shutdown = self.shutdown.as_ref().map(|s| s.token().listener());
+        ctx
+    }
+
+    pub(crate) fn from_parts(
+        aref: Arc<ActorRefInner<M>>,
+        recv: Box<dyn Receiver<M>>,
+        spawner: Arc<dyn Spawner>,
+    ) -> Self {
+        Self {
+            recv,
+            aref,
+            takes_turns: spawner.takes_turns(),
+            spawner,
+            ticker: None,
+            stash: VecDeque::new(),
+            unstashed: VecDeque::new(),
+            shutdown: None,
+            config: ActorConfig::default(),
+            children: Vec::new(),
+            supervisor: None,
+            escalation: None,
+            behaviors: Vec::new(),
+            watchdog: None,
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Receive the next message from the mailbox, possibly waiting for it
+    ///
+    /// This method’s return value should always be immediately `.await`ed, it has no other use.
+    pub fn receive(&mut self) -> ReceiveFuture<'_, M> {
+        self.done();
+        ReceiveFuture {
+            aref: &self.aref,
+            unstashed: &mut self.unstashed,
+            shutdown: self.shutdown.as_ref(),
+            escalation: self.escalation.as_ref(),
+            on_no_refs: self.config.on_no_refs,
+            watchdog: self.watchdog.as_ref(),
+            takes_turns: self.takes_turns,
+            fut: self.recv.receive(),
+        }
+    }
+
+    /// Receive the next message like [`receive`](#method.receive), but report a closed mailbox as a value
+    ///
+    /// `receive` fails with [`NoActorRef`](struct.NoActorRef.html) once no message can arrive
+    /// anymore, which `?` turns into the end of the actor. `recv` returns
+    /// [`RecvResult::Closed`](enum.RecvResult.html#variant.Closed) instead, so the actor can
+    /// decide whether to stop, e.g. after finishing some cleanup or if it still has work that
+    /// does not depend on messages. Since the condition is permanent, further calls return
+    /// `Closed` again right away. Other errors, like [`Shutdown`](struct.Shutdown.html), are
+    /// returned as `RecvResult::Failed`.
+    pub async fn recv(&mut self) -> RecvResult<M> {
+        match self.receive().await {
+            Ok(msg) => RecvResult::Message(msg),
+            Err(err) if err.is::<NoActorRef>() => RecvResult::Closed,
+            Err(err) => RecvResult::Failed(err),
+        }
+    }
+
+    /// Receive all immediately available messages, up to `max`, waiting for at least one
+    ///
+    /// Errors are only returned when no message has been received, so if the mailbox is closed
+    /// in the middle of a batch, the remaining messages are returned and the next call fails.
+    pub async fn receive_many(&mut self, max: usize) -> Result<Vec<M>> {
+        if max == 0 {
+            return Ok(Vec::new());
+        }
+        let mut batch = Vec::new();
+        let mut recv = self.receive();
+        poll_fn(|cx| {
+            while batch.len() < max {
+                match Pin::new(&mut recv).poll(cx) {
+                    Poll::Ready(Ok(msg)) => batch.push(msg),
+                    Poll::Ready(Err(err)) if batch.is_empty() => return Poll::Ready(Err(err)),
+                    Poll::Ready(Err(_)) | Poll::Pending => break,
+                }
+            }
+            if batch.is_empty() {
+                Poll::Pending
+            } else {
+                Poll::Ready(Ok(std::mem::take(&mut batch)))
+            }
+        })
+        .await
+    }
+
+    /// Receive the next message from the mailbox, giving up after the given duration
+    ///
+    /// Returns `Ok(None)` if no message arrived in time. The timer is obtained from this actor’s
+    /// [`Spawner`](trait.Spawner.html) and dropped as soon as a message is received.
+    pub async fn receive_timeout(&mut self, timeout: Duration) -> Result<Option<M>> {
+        let mut delay = self.spawner.delay(timeout);
+        let mut recv = self.receive();
+        poll_fn(|cx| {
+            if let Poll::Ready(result) = Pin::new(&mut recv).poll(cx) {
+                return Poll::Ready(result.map(Some));
+            }
+            delay.as_mut().poll(cx).map(|_| Ok(None))
+        })
+        .await
+    }
+
+    /// Receive the next message or the output of `other`, whichever comes first
+    ///
+    /// Messages are not lost when `other` wins, they stay in the mailbox for the next call. The
+    /// opposite is up to `other`: if a message arrives first, `other` is dropped, so pass a
+    /// cancel-safe future — e.g. `&mut fut` for a pinned future that is polled again in the next
+    /// iteration, or something like `watch::Receiver::changed` that does not consume anything
+    /// until it completes. Mailbox errors like [`NoActorRef`](struct.NoActorRef.html) are
+    /// returned as usual.
+    pub async fn receive_or<F: Future>(&mut self, other: F) -> Result<Either<M, F::Output>> {
+        let mut other = pin!(other);
+        let mut recv = self.receive();
+        poll_fn(|cx| {
+            if let Poll::Ready(result) = Pin::new(&mut recv).poll(cx) {
+                return Poll::Ready(result.map(Either::Left));
+            }
+            other.as_mut().poll(cx).map(|out| Ok(Either::Right(out)))
+        })
+        .await
+    }
+
+    /// Receive the next message or a periodic tick, whichever comes first
+    ///
+    /// The first call with a given `period` starts a timer (using this actor’s
+    /// [`Spawner`](trait.Spawner.html)) that ticks every `period` from then on; calling this method
+    /// with a different `period` restarts it. Ticks do not pile up while the actor is busy: when
+    /// several ticks were missed, only one is delivered and the next one is due at the following
+    /// multiple of `period`. A due tick is delivered before waiting messages, which cannot lead to
+    /// starvation since there is at most one tick per period. The timer is stopped when the
+    /// `Context` is dropped.
+    ///
+    /// Panics if `period` is zero.
+    pub async fn receive_or_tick(&mut self, period: Duration) -> Result<Event<M>> {
+        assert!(!period.is_zero(), "tick period must not be zero");
+        if self.ticker.as_ref().map(|t| t.period) != Some(period) {
+            self.ticker = Some(Ticker {
+                period,
+                next: self.spawner.now() + period,
+                delay: self.spawner.delay(period),
+            });
+        }
+        self.done();
+        let ticker = self.ticker.as_mut().unwrap();
+        let spawner = &*self.spawner;
+        let mut recv = ReceiveFuture {
+            aref: &self.aref,
+            unstashed: &mut self.unstashed,
+            shutdown: self.shutdown.as_ref(),
+            escalation: self.escalation.as_ref(),
+            on_no_refs: self.config.on_no_refs,
+            watchdog: self.watchdog.as_ref(),
+            takes_turns: self.takes_turns,
+            fut: self.recv.receive(),
+        };
+        poll_fn(|cx| {
+            if ticker.delay.as_mut().poll(cx).is_ready() {
+                let now = spawner.now();
+                let mut next = ticker.next + period;
+                while next <= now {
+                    next += period;
+                }
+                ticker.next = next;
+                ticker.delay = spawner.delay(next - now);
+                return Poll::Ready(Ok(Event::Tick));
+            }
+            Pin::new(&mut recv).poll(cx).map(|r| r.map(Event::Message))
+        })
+        .await
+    }
+
+    /// Put a message aside for handling it later, see [`unstash_all`](#method.unstash_all)
+    pub fn stash(&mut self, msg: M) {
+        self.stash.push_back(msg);
+    }
+
+    /// Make all stashed messages available for receiving again
+    ///
+    /// The stashed messages will be received in the order in which they were stashed, and before
+    /// any other message — including those that were unstashed earlier but not yet received.
+    pub fn unstash_all(&mut self) {
+        while let Some(msg) = self.stash.pop_back() {
+            self.unstashed.push_front(msg);
+        }
+    }
+
+    /// Look at the next message without receiving it, if one is available right away
+    ///
+    /// A message taken from the mailbox for this purpose is buffered in the `Context` — there is
+    /// at most one such message, since `peek` returns it again until it is received. It is the
+    /// next message returned from [`receive`](#method.receive) or [`consume`](#method.consume),
+    /// only messages made available with [`unstash_all`](#method.unstash_all) afterwards are
+    /// received before it; if messages were unstashed earlier, `peek` shows the first of those
+    /// instead. To set a peeked message aside, `consume` it and [`stash`](#method.stash) it.
+    ///
+    /// This method does not wait: it returns `None` if the mailbox is empty or failing, or if
+    /// `receive` would fail anyway, e.g. after shutdown; the next `receive` then reports the error.
+    pub fn peek(&mut self) -> Option<&M> {
+        if self.must_stop() {
+            return None;
+        }
+        if self.unstashed.is_empty() {
+            let mut cx = std::task::Context::from_waker(Waker::noop());
+            if let Poll::Ready(Ok(msg)) = Pin::new(self.recv.receive()).poll(&mut cx) {
+                self.unstashed.push_back(msg);
+            }
+        }
+        self.unstashed.front()
+    }
+
+    // whether receive fails before looking for messages, like ReceiveFuture::poll but without
+    // taking the escalated error
+    fn must_stop(&self) -> bool {
+        self.shutdown
+            .as_ref()
+            .is_some_and(|s| s.token().is_triggered())
+            || self.aref.stopped.load(Ordering::SeqCst)
+            || self.escalation.as_ref().is_some_and(|e| e.is_pending())
+            || self.config.on_no_refs == NoRefsPolicy::StopImmediately
+                && self.aref.count.load(Ordering::SeqCst) == 0
+    }
+
+    /// Take the message shown by [`peek`](#method.peek), if one is available right away
+    pub fn consume(&mut self) -> Option<M> {
+        self.peek()?;
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.received();
+        }
+        self.unstashed.pop_front()
+    }
+
+    /// Replace the current behavior, i.e. the handler that [`run_behavior`](#method.run_behavior) calls for the next messages
+    ///
+    /// The handler gets each message together with the `Context`, which it can use to change the
+    /// behavior again before returning the future that processes the message. If there is no
+    /// current behavior, this one becomes the initial one.
+    pub fn r#become<F, Fut>(&mut self, handler: F)
+    where
+        F: FnMut(M, &mut Context<M>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.behaviors.pop();
+        self.become_stacked(handler);
+    }
+
+    /// Like [`become`](#method.become), but keep the current behavior for returning to it with [`unbecome`](#method.unbecome)
+    pub fn become_stacked<F, Fut>(&mut self, mut handler: F)
+    where
+        F: FnMut(M, &mut Context<M>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.behaviors
+            .push(Some(Box::new(move |msg, ctx| Box::pin(handler(msg, ctx)))));
+    }
+
+    /// Drop the current behavior, returning to the one it was [stacked](#method.become_stacked) upon
+    ///
+    /// Once the last behavior is gone, [`run_behavior`](#method.run_behavior) returns.
+    pub fn unbecome(&mut self) {
+        self.behaviors.pop();
+    }
+
+    /// Handle messages with the current behavior until it has been [`unbecome`](#method.unbecome)d completely
+    ///
+    /// Returns `Ok(())` also when the actor can no longer receive because it has no external
+    /// `ActorRef` left ([`NoActorRef`](struct.NoActorRef.html)) or is asked to
+    /// [`Shutdown`](struct.Shutdown.html); other errors from receiving or from a handler are returned.
+    ///
+    /// ```
+    /// use acto::{actor, Context, Reply, Result};
+    /// use acto::tokio::{TokioMailbox, TokioSpawner};
+    ///
+    /// fn locked(msg: Reply<bool>, ctx: &mut Context<Reply<bool>>) -> std::future::Ready<Result<()>> {
+    ///     ctx.r#become(unlocked);
+    ///     let _ = msg.send(false);
+    ///     std::future::ready(Ok(()))
+    /// }
+    ///
+    /// fn unlocked(msg: Reply<bool>, ctx: &mut Context<Reply<bool>>) -> std::future::Ready<Result<()>> {
+    ///     ctx.r#become(locked);
+    ///     let _ = msg.send(true);
+    ///     std::future::ready(Ok(()))
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let (turnstile, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+    ///         ctx.r#become(locked);
+    ///         ctx.run_behavior().await
+    ///     });
+    ///     for expected in [false, true, false] {
+    ///         let (reply, passed) = Reply::new();
+    ///         turnstile.tell(reply);
+    ///         assert_eq!(passed.await?, expected);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn run_behavior(&mut self) -> Result<()> {
+        while !self.behaviors.is_empty() {
+            let msg = match self.receive().await {
+                Ok(msg) => msg,
+                Err(err) if err.is::<NoActorRef>() || err.is::<Shutdown>() => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            let depth = self.behaviors.len();
+            let mut handler = match self.behaviors[depth - 1].take() {
+                Some(handler) => handler,
+                None => unreachable!("behavior is only taken while being called"),
+            };
+            let fut = handler(msg, self);
+            // put it back unless it has been replaced or dropped in the meantime
+            if let Some(slot @ None) = self.behaviors.get_mut(depth - 1) {
+                *slot = Some(handler);
+            }
+            fut.await?;
+        }
+        Ok(())
+    }
+
+    /// Report messages whose processing takes longer than `limit`
+    ///
+    /// Processing a message starts when it is received and ends with the next call to
+    /// [`receive`](#method.receive) (or one of its variants) or [`done`](#method.done). If that
+    /// takes longer than `limit`, `on_stuck` is called once for that message with the actor’s id
+    /// and the time spent so far — e.g. for logging a warning about an accidentally blocking or
+    /// looping handler. The check runs in a separate task from this actor’s `Spawner`, waking up
+    /// at least every `limit`, so reports may be up to `limit` late; a handler blocking the thread
+    /// will only be noticed if the runtime has other threads. Calling this again replaces the
+    /// previous watchdog, dropping the `Context` stops it.
+    pub fn set_watchdog(
+        &mut self,
+        limit: Duration,
+        on_stuck: impl FnMut(ActorId, Duration) + Send + 'static,
+    ) {
+        self.watchdog = Some(Watchdog::start(
+            self.aref.id,
+            self.spawner.clone(),
+            limit,
+            on_stuck,
+        ));
+    }
+
+    /// Mark the processing of the last received message as finished, see [`set_watchdog`](#method.set_watchdog)
+    pub fn done(&mut self) {
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.done();
+        }
+    }
+
+    /// Make this actor (and its children spawned afterwards) react to the given shutdown signal
+    ///
+    /// Once the token is triggered, `receive()` fails with [`Shutdown`](struct.Shutdown.html).
+    pub fn set_shutdown_token(&mut self, token: ShutdownToken) {
+        self.shutdown = Some(token.listener());
+    }
+
+    /// Apply the given settings to this actor, they are not inherited by its children
+    pub fn set_config(&mut self, config: ActorConfig) {
+        self.config = config;
+    }
+
+    // used by the actor! macro
+    #[doc(hidden)]
+    pub fn set_name(&mut self, name: &str) {
+        self.recv.set_name(name);
+    }
+
+    // used by the actor! macro
+    #[doc(hidden)]
+    pub fn error_log(&self) -> ErrorLog {
+        ErrorLog {
+            id: self.config.log_errors.then(|| self.aref.id),
+        }
+    }
+
+    /// Whether this actor’s [`ShutdownToken`](struct.ShutdownToken.html) has been triggered
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown
+            .as_ref()
+            .is_some_and(|s| s.token().is_triggered())
+    }
+
+    /// Wait until this actor’s [`ShutdownToken`](struct.ShutdownToken.html) is triggered
+    ///
+    /// Never completes if no token was set.
+    pub async fn shutdown(&self) {
+        match &self.shutdown {
+            Some(shutdown) => poll_fn(|cx| shutdown.poll(cx)).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// The actors own address a.k.a. ActorRef
+    ///
+    /// The actor can put this `ActorRef` into messages to send it to other actors so that they
+    /// can contact this actor back later, or use it to send messages to itself.
+    ///
+    /// The returned `ActorRef` counts like any external one: as long as it exists, `receive()`
+    /// will never fail with [`NoActorRef`](struct.NoActorRef.html), so an actor holding on to its
+    /// own address needs to decide by itself when to stop.
+    pub fn me(&self) -> ActorRef<M> {
+        self.aref.count.fetch_add(1, Ordering::SeqCst);
+        ActorRef(self.aref.clone())
+    }
+
+    /// Send a message to this actor after the given delay
+    ///
+    /// Like [`ActorRef::tell_after_with`](struct.ActorRef.html#method.tell_after_with) with this
+    /// actor’s own address and `Spawner`, except that the pending message does not keep the actor
+    /// alive: it is discarded if the actor has stopped or lost all its `ActorRef`s by the time the
+    /// timer fires. Dropping the returned handle cancels the delivery, which makes it easy to
+    /// debounce or restart timeouts by replacing the handle.
+    pub fn schedule(&self, msg: M, delay: Duration) -> CancelHandle {
+        let me = WeakActorRef(Arc::downgrade(&self.aref));
+        CancelHandle::schedule(&*self.spawner, delay, move || {
+            if let Some(aref) = me.upgrade() {
+                aref.tell(msg);
+            }
+        })
+    }
+
+    /// Send a request to another actor and wait for its reply before continuing
+    ///
+    /// Like [`ActorRef::ask_with`](struct.ActorRef.html#method.ask_with), `make_msg` is given the
+    /// `ActorRef` to reply to, but no mailbox factory is needed since there is only one reply.
+    /// While waiting, messages sent to this actor are buffered in its mailbox as usual and will be
+    /// received afterwards; a bounded mailbox may fill up and apply its policy for full queues in
+    /// the meantime. If `target` asks this actor in turn, both will wait forever — unless the
+    /// `with_deadlock_detection` feature is enabled, which makes the ask that closes such a cycle
+    /// fail with `Deadlock` right away. Only asks made through a `Context` are tracked.
+    ///
+    /// Fails with [`NoActorRef`](struct.NoActorRef.html) if `target` dropped the reply `ActorRef`
+    /// without answering, e.g. because it has stopped, and with [`Shutdown`](struct.Shutdown.html)
+    /// if this actor is asked to shut down while waiting.
+    pub async fn ask<N, R>(
+        &mut self,
+        target: &ActorRef<N>,
+        make_msg: impl FnOnce(ActorRef<R>) -> N,
+    ) -> Result<R>
+    where
+        R: Send + 'static,
+    {
+        #[cfg(feature = "with_deadlock_detection")]
+        let _waiting = crate::deadlock::Waiting::enter(self.aref.id, target.id())?;
+        let (aref, mut recv) = ReplySlot.make_mailbox();
+        let inner = aref.0.clone();
+        target.tell(make_msg(aref));
+        ReceiveFuture {
+            aref: &inner,
+            unstashed: &mut VecDeque::new(),
+            shutdown: self.shutdown.as_ref(),
+            escalation: None,
+            on_no_refs: NoRefsPolicy::DrainThenStop,
+            watchdog: None,
+            takes_turns: false,
+            fut: recv.receive(),
+        }
+        .await
+    }
+
+    /// Spawn a future for the purpose of running a child actor
+    ///
+    /// This method is best used via the [`actor`](macro.actor.html) macro.
+    pub fn spawn<F>(&self, fut: F) -> ActorHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        crate::spawn(&*self.spawner, fut)
+    }
+
+    /// Like [`spawn`](#method.spawn), but giving the task a name
+    pub fn spawn_named<F>(&self, name: &str, fut: F) -> ActorHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        crate::spawn_named(&*self.spawner, name, fut)
+    }
+
+    /// Spawn a helper task that is aborted when this actor’s future ends
+    ///
+    /// This is meant for background work on behalf of the actor, e.g. fetching something. The
+    /// task’s result can be awaited via the returned handle, or the task can send it to the actor
+    /// itself using an [`ActorRef`](struct.ActorRef.html) obtained from [`me`](#method.me) —
+    /// which keeps the actor alive while the task runs.
+    ///
+    /// Like with [`ActorHandle::abort`](struct.ActorHandle.html#method.abort), an aborted task is
+    /// dropped the next time it is scheduled.
+    pub fn spawn_task<F>(&self, fut: F) -> TaskHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let handle = crate::spawn(&*self.spawner, fut);
+        let mut tasks = self.tasks.lock();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(handle.control());
+        TaskHandle::new(handle)
+    }
+
+    /// Spawn an actor that is owned by this one and stopped together with it
+    ///
+    /// The child gets its own [`ShutdownToken`](struct.ShutdownToken.html), which is triggered
+    /// when this `Context` is dropped — i.e. when this actor’s future ends — or when
+    /// [`stop_children`](#method.stop_children) is called, so the child’s `receive()` then fails
+    /// with [`Shutdown`](struct.Shutdown.html). Its own children are stopped in turn once its
+    /// future ends. With the `hooks = <expr>` argument to the [`actor`](macro.actor.html) macro,
+    /// the children are signalled before the parent’s [`stopped`](trait.Lifecycle.html#method.stopped)
+    /// hook runs; the parent does not wait for them to finish, though.
+    pub fn spawn_child<C, MB, S, F, Fut, T>(
+        &mut self,
+        mailbox: MB,
+        spawner: S,
+        body: F,
+    ) -> ActorRef<C>
+    where
+        C: Send + 'static,
+        MB: MailboxFor<C>,
+        S: Spawner,
+        F: FnOnce(Context<C>) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let token = ShutdownToken::new();
+        let mut ctx = Context::new(mailbox, Arc::new(spawner));
+        ctx.set_shutdown_token(token.clone());
+        let aref = ctx.me();
+        let spawner = ctx.spawner.clone();
+        drop(crate::spawn(&*spawner, body(ctx)));
+        self.children.push(token);
+        aref
+    }
+
+    /// Decide what happens when a child started with [`spawn_supervised`](#method.spawn_supervised) fails
+    ///
+    /// The function is called with the child’s error and applies to the children spawned
+    /// afterwards. It runs outside of this actor, so it should be quick; keeping count of
+    /// restarts in its captured state allows limiting them.
+    pub fn set_supervisor(
+        &mut self,
+        supervisor: impl FnMut(&anyhow::Error) -> Directive + Send + 'static,
+    ) {
+        self.supervisor = Some(Arc::new(Mutex::new(Box::new(supervisor))));
+    }
+
+    /// Like [`spawn_child`](#method.spawn_child), but restarting the child on failure if the
+    /// supervisor says so
+    ///
+    /// A child fails when its future panics or returns an error other than
+    /// [`NoActorRef`](struct.NoActorRef.html) or [`Shutdown`](struct.Shutdown.html), in which case
+    /// the function given to [`set_supervisor`](#method.set_supervisor) picks the
+    /// [`Directive`](enum.Directive.html); without one, failures are escalated. Each restart
+    /// calls `factory` again with a new `Context` for the same mailbox, so the returned `ActorRef`
+    /// stays valid. An escalated error is returned from this actor’s next `receive()`.
+    pub fn spawn_supervised<C, MB, S, F, Fut, T>(
+        &mut self,
+        mailbox: MB,
+        spawner: S,
+        factory: F,
+    ) -> ActorRef<C>
+    where
+        C: Send + 'static,
+        MB: MailboxFor<C>,
+        S: Spawner,
+        F: Fn(Context<C>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let token = ShutdownToken::new();
+        let escalation = self
+            .escalation
+            .get_or_insert_with(Escalation::default)
+            .clone();
+        let aref = spawn_supervised_child(
+            mailbox,
+            Arc::new(spawner),
+            token.clone(),
+            self.supervisor.clone(),
+            escalation,
+            factory,
+        );
+        self.children.push(token);
+        aref
+    }
+
+    /// Signal all actors started with [`spawn_child`](#method.spawn_child) to stop
+    pub fn stop_children(&mut self) {
+        for child in self.children.drain(..) {
+            child.trigger();
+        }
+    }
+}
+
+impl<M> Drop for Context<M> {
+    fn drop(&mut self) {
+        for child in self.children.drain(..) {
+            child.trigger();
+        }
+        for task in self.tasks.get_mut().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+pub struct ReceiveFuture<'a, M: Send + 'static> {
+    aref: &'a ActorRefInner<M>,
+    unstashed: &'a mut VecDeque<M>,
+    shutdown: Option<&'a ShutdownListener>,
+    escalation: Option<&'a Escalation>,
+    on_no_refs: NoRefsPolicy,
+    watchdog: Option<&'a Watchdog>,
+    takes_turns: bool,
+    fut: &'a mut (dyn Future<Output = Result<M>> + Send + Unpin + 'a),
+}
+
+impl<'a, M: Send + 'static> ReceiveFuture<'a, M> {
+    fn received(&self, msg: M) -> M {
+        if let Some(watchdog) = self.watchdog {
+            watchdog.received();
+        }
+        msg
+    }
+
+    // the mailbox was Pending before the last ActorRef was seen gone, but messages sent before
+    // that may have arrived in between; also, Pending may only mean that the runtime wants this
+    // task to yield (tokio’s coop budget), so look once more without the budget
+    fn poll_orphaned(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<M>> {
+        #[cfg(feature = "with_tokio")]
+        let polled = Pin::new(&mut tokio::task::unconstrained(&mut *self.fut)).poll(cx);
+        #[cfg(not(feature = "with_tokio"))]
+        let polled = Pin::new(&mut *self.fut).poll(cx);
+        match polled {
+            Poll::Ready(Ok(msg)) => Poll::Ready(Ok(self.received(msg))),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Ready(Err(NoActorRef.into())),
+        }
+    }
+}
+
+impl<'a, M: Send + 'static> Future for ReceiveFuture<'a, M> {
+    type Output = Result<M>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        if let Some(shutdown) = self.shutdown {
+            if shutdown.poll(cx).is_ready() {
+                return Poll::Ready(Err(Shutdown.into()));
+            }
+        }
+        if self.aref.stopped.load(Ordering::SeqCst) {
+            return Poll::Ready(Err(Shutdown.into()));
+        }
+        if let Some(escalation) = self.escalation {
+            if let Poll::Ready(err) = escalation.poll(cx) {
+                return Poll::Ready(Err(err));
+            }
+        }
+        if self.on_no_refs == NoRefsPolicy::StopImmediately
+            && self.aref.count.load(Ordering::SeqCst) == 0
+        {
+            return Poll::Ready(Err(NoActorRef.into()));
+        }
+        // actors on a SharedRuntime take turns after each message
+        if !self.unstashed.is_empty() {
+            if self.takes_turns && crate::shared::poll_budget(cx).is_pending() {
+                return Poll::Pending;
+            }
+            let msg = self.unstashed.pop_front().unwrap();
+            return Poll::Ready(Ok(self.received(msg)));
+        }
+        match Pin::new(&mut *self.fut).poll(cx) {
+            Poll::Ready(Ok(msg)) => {
+                if self.takes_turns && crate::shared::poll_budget(cx).is_pending() {
+                    // it is the next message in any case
+                    self.unstashed.push_back(msg);
+                    return Poll::Pending;
+                }
+                Poll::Ready(Ok(self.received(msg)))
+            }
+            Poll::Ready(x) => Poll::Ready(x),
+            Poll::Pending => {
+                if self.aref.is_orphaned() {
+                    // observing no external address means that there cannot be one created, either:
+                    // we hold an exclusive reference on the Context (via self.fut); a closed
+                    // mailbox stays closed
+                    self.poll_orphaned(cx)
+                } else {
+                    *self.aref.waker.lock() = Some(cx.waker().clone());
+                    // in case the last ActorRef was dropped or the actor was stopped between the
+                    // checks and installing the waker, we must now re-check
+                    if self.aref.stopped.load(Ordering::SeqCst) {
+                        return Poll::Ready(Err(Shutdown.into()));
+                    }
+                    if let Some(escalation) = self.escalation {
+                        if let Poll::Ready(err) = escalation.poll(cx) {
+                            return Poll::Ready(Err(err));
+                        }
+                    }
+                    if self.aref.is_orphaned() {
+                        self.poll_orphaned(cx)
+                    } else {
+                        Poll::Pending
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Display, Error)]
+#[display(fmt = "cannot receive: no external ActorRef for this actor")]
+pub struct NoActorRef;
+
+/// Error returned from [`ActorRef::ask_timeout_with`](struct.ActorRef.html#method.ask_timeout_with) when no reply arrived in time
+#[derive(Debug, Display, Error)]
+#[display(fmt = "no reply received within the timeout")]
+pub struct AskTimeout;
+
+/// Reason why a spawned actor task did not produce a result
+///
+/// [`Spawner`](trait.Spawner.html) implementations return this error (wrapped in an
+/// `anyhow::Error`) from the future obtained by `spawn`, so that it can be recovered using `downcast`.
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum SpawnError {
+    /// The actor panicked, with the given message
+    #[display(fmt = "actor task panicked: {}", _0)]
+    Panicked(String),
+    /// The actor task was cancelled, e.g. because its runtime was shut down
+    #[display(fmt = "actor task was cancelled")]
+    Cancelled,
+}
+
+impl std::error::Error for SpawnError {}
+
+/// Error returned from [`ActorRef::try_tell`](struct.ActorRef.html#method.try_tell), containing the undelivered message
+pub struct SendError<M>(pub M);
+
+impl<M> fmt::Display for SendError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cannot send: message was not delivered to the actor")
+    }
+}
+
+impl<M> Debug for SendError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+impl<M> std::error::Error for SendError<M> {}
+
+/// Error returned from [`ActorRef::try_tell_labeled`](struct.ActorRef.html#method.try_tell_labeled)
+/// and [`ActorRef::send`](struct.ActorRef.html#method.send), a [`SendError`](struct.SendError.html) together with the label of the reference
+pub struct LabeledSendError<M> {
+    error: SendError<M>,
+    label: Option<Arc<str>>,
+}
+
+impl<M> LabeledSendError<M> {
+    /// The [label](struct.ActorRef.html#method.labeled) of the `ActorRef` the message was sent to
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// The undelivered message
+    pub fn into_inner(self) -> M {
+        self.error.0
+    }
+}
+
+impl<M> From<LabeledSendError<M>> for SendError<M> {
+    fn from(err: LabeledSendError<M>) -> Self {
+        err.error
+    }
+}
+
+impl<M> fmt::Display for LabeledSendError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(
+                f,
+                "cannot send: message was not delivered to actor `{}`",
+                label
+            ),
+            None => fmt::Display::fmt(&self.error, f),
+        }
+    }
+}
+
+impl<M> Debug for LabeledSendError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "LabeledSendError({}, ..)", label),
+            None => Debug::fmt(&self.error, f),
+        }
+    }
+}
+
+impl<M> std::error::Error for LabeledSendError<M> {}
+
+/// The sending side of a mailbox, wrapped by an [`ActorRef`](struct.ActorRef.html)
+pub trait Sender<M>: Send + Sync {
+    /// Enqueue the message, handing it back if that is not possible
+    fn tell(&self, msg: M) -> std::result::Result<(), M>;
+
+    /// The number of messages currently in the queue, zero if this cannot be determined
+    fn len(&self) -> usize {
+        0
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The maximum number of messages in the queue, `None` if unbounded
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether the receiving side is known to be gone, so that `tell` will fail
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    /// Stop accepting messages, see [`ActorRef::close`](struct.ActorRef.html#method.close)
+    ///
+    /// Mailboxes don’t need to do anything here, the `ActorRef` takes care of rejecting further
+    /// messages; senders that forward to other `ActorRef`s should close those.
+    fn close(&self) {}
+
+    /// Stop the actor, see [`ActorRef::stop_signal`](struct.ActorRef.html#method.stop_signal)
+    ///
+    /// Like for `close`, only senders that forward to other `ActorRef`s need to pass this on.
+    fn stop_signal(&self) {}
+
+    /// Wait for room in a bounded queue, `None` if the queue is unbounded
+    ///
+    /// The returned future must be cancel-safe, i.e. dropping it shall release the reserved slot.
+    fn reserve(&self) -> Option<ReserveBox<M>> {
+        None
+    }
+}
+
+impl<M, F> Sender<M> for F
+where
+    F: Fn(M) -> std::result::Result<(), M> + Send + Sync,
+{
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        self(msg)
+    }
+}
+
+/// The receiving end of an actor’s mailbox
+///
+/// When the mailbox cannot yield any more messages because its sending side is gone, the
+/// future shall fail with [`NoActorRef`](struct.NoActorRef.html), so that actors can tell this
+/// apart from other errors.
+pub trait Receiver<M: Send + 'static>: Send {
+    // this trait is only necessary because FnMut doesn’t make its argument’s self reference lifetime available
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_);
+
+    /// Called with the actor’s name if it is spawned with one, e.g. for labelling metrics
+    ///
+    /// The default implementation ignores the name; receivers that wrap another one should pass it on.
+    fn set_name(&mut self, name: &str) {
+        let _ = name;
+    }
+}
+
+/// Factory for mailboxes, which usually are MPSC queues under the hood
+///
+/// Being generic over the message type, this trait cannot be used as a trait object; see
+/// [`BoxedMailbox`](struct.BoxedMailbox.html) for choosing a mailbox at runtime.
+pub trait Mailbox {
+    fn make_mailbox<M: Send + 'static>(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>);
+}
+
+// mailbox for a single message, used for replies
+struct ReplySlot;
+
+struct SlotState<M> {
+    msg: Option<M>,
+    waker: Option<Waker>,
+    // a message has been sent, or the receiving side is gone
+    done: bool,
+}
+
+struct Slot<M>(Arc<Mutex<SlotState<M>>>);
+
+struct SlotReceiver<M>(Arc<Mutex<SlotState<M>>>);
+
+impl Mailbox for ReplySlot {
+    fn make_mailbox<M: Send + 'static>(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let state = Arc::new(Mutex::new(SlotState {
+            msg: None,
+            waker: None,
+            done: false,
+        }));
+        (
+            ActorRef::new(Box::new(Slot(state.clone()))),
+            Box::new(SlotReceiver(state)),
+        )
+    }
+}
+
+impl<M: Send> Sender<M> for Slot<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        let mut state = self.0.lock();
+        if state.done {
+            return Err(msg);
+        }
+        state.done = true;
+        state.msg = Some(msg);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.lock().done
+    }
+}
+
+impl<M: Send + 'static> Receiver<M> for SlotReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M> Future for SlotReceiver<M> {
+    type Output = Result<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.0.lock();
+        match state.msg.take() {
+            Some(msg) => Poll::Ready(Ok(msg)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<M> Drop for SlotReceiver<M> {
+    fn drop(&mut self) {
+        self.0.lock().done = true;
+    }
+}
+
+/// Factory for mailboxes of one particular message type
+///
+/// This is what actors are created with, every [`Mailbox`](trait.Mailbox.html) is one for all
+/// message types. Implement this trait directly for mailboxes that need to know something about
+/// the messages, e.g. how to prioritise them.
+pub trait MailboxFor<M: Send + 'static> {
+    fn make_mailbox_for(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>);
+}
+
+impl<M: Send + 'static, MB: Mailbox + ?Sized> MailboxFor<M> for MB {
+    fn make_mailbox_for(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        self.make_mailbox()
+    }
+}
+
+/// A mailbox factory for one message type that is chosen at runtime
+///
+/// [`Mailbox`](trait.Mailbox.html) cannot be a trait object since `make_mailbox` is generic over
+/// the message type, but [`MailboxFor`](trait.MailboxFor.html) can: box any mailbox once the
+/// message type is known, e.g. `BoxedMailbox::new(TokioMailbox)`.
+pub struct BoxedMailbox<M>(Box<dyn MailboxFor<M> + Send + Sync>);
+
+impl<M: Send + 'static> BoxedMailbox<M> {
+    pub fn new(mailbox: impl MailboxFor<M> + Send + Sync + 'static) -> Self {
+        Self(Box::new(mailbox))
+    }
+}
+
+impl<M: Send + 'static> MailboxFor<M> for BoxedMailbox<M> {
+    fn make_mailbox_for(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        self.0.make_mailbox_for()
+    }
+}
+
+/// A spawner that is chosen at runtime, e.g. from configuration
+pub type BoxedSpawner = Box<dyn Spawner>;
+
+impl<S: Spawner + ?Sized> Spawner for Box<S> {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        (**self).spawn(fut)
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        (**self).delay(dur)
+    }
+
+    fn spawn_named(&self, name: &str, fut: FutureBox) -> FutureResultBox {
+        (**self).spawn_named(name, fut)
+    }
+
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn takes_turns(&self) -> bool {
+        (**self).takes_turns()
+    }
+}
+
+impl<S: Spawner + ?Sized> Spawner for Arc<S> {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        (**self).spawn(fut)
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        (**self).delay(dur)
+    }
+
+    fn spawn_named(&self, name: &str, fut: FutureBox) -> FutureResultBox {
+        (**self).spawn_named(name, fut)
+    }
+
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn takes_turns(&self) -> bool {
+        (**self).takes_turns()
+    }
+}
+
+/// Facility for spawning a particular kind of Future that is used to run actors
+pub trait Spawner: Send + Sync + 'static {
+    /// Run the future, returning a handle for its result
+    ///
+    /// If the future does not complete, the handle should fail with a
+    /// [`SpawnError`](enum.SpawnError.html) describing why.
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox;
+
+    /// Create a future that completes after the given duration
+    ///
+    /// The default implementation uses a dedicated thread for each timer, which keeps running
+    /// until the duration has elapsed even if the future is dropped earlier. Spawners for
+    /// runtimes with a native timer should override this.
+    fn delay(&self, dur: Duration) -> DelayBox {
+        thread_delay(dur)
+    }
+
+    /// Like [`spawn`](#tymethod.spawn), but with a name for the task, e.g. for debugging tools
+    ///
+    /// The default implementation ignores the name.
+    fn spawn_named(&self, name: &str, fut: FutureBox) -> FutureResultBox {
+        let _ = name;
+        self.spawn(fut)
+    }
+
+    /// The current time as seen by the timers obtained from [`delay`](#method.delay)
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Whether the actors spawned here share a task and take turns after each message, like
+    /// with the [`SharedRuntime`](struct.SharedRuntime.html)
+    ///
+    /// Spawners that wrap another one should pass this on.
+    fn takes_turns(&self) -> bool {
+        false
+    }
+}
+
+/// Facility for spawning actors whose futures are not `Send`, e.g. because they hold `Rc` state
+///
+/// Such actors stay on the thread they were spawned on. Their messages still need to be `Send`
+/// since their `ActorRef` may be passed to other threads; only the actor’s own state is exempt.
+pub trait SpawnLocal: Spawner {
+    fn spawn_local(&self, fut: LocalFutureBox) -> LocalFutureResultBox;
+}
+
+pub(crate) fn thread_delay(dur: Duration) -> DelayBox {
+    let state = Arc::new(Mutex::new((false, None::<Waker>)));
+    let timer = state.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(dur);
+        let mut timer = timer.lock();
+        timer.0 = true;
+        if let Some(waker) = timer.1.take() {
+            waker.wake();
+        }
+    });
+    Box::pin(poll_fn(move |cx| {
+        let mut state = state.lock();
+        if state.0 {
+            Poll::Ready(())
+        } else {
+            state.1 = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }))
 }
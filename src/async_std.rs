@@ -1,14 +1,30 @@
-use crate::{FutureBox, FutureResultBox, Mailbox, Receiver, Spawner};
+use crate::{
+    panic_message, DelayBox, FutureBox, FutureResultBox, Mailbox, NoActorRef, Receiver, Sender,
+    SpawnError, Spawner,
+};
 use async_std::{channel, task};
-use futures::StreamExt;
-use std::{future::Future, pin::Pin, task::Poll};
+use futures::{FutureExt, StreamExt};
+use std::{future::Future, panic::AssertUnwindSafe, pin::Pin, task::Poll, time::Duration};
 
+/// Spawner that uses the global async-std executor
+///
+/// A panic in the actor is caught and surfaced as an error from the join handle, like the
+/// `JoinError` returned by the tokio spawners.
 pub struct AsyncStdSpawner;
 
 impl Spawner for AsyncStdSpawner {
     fn spawn(&self, fut: FutureBox) -> FutureResultBox {
-        let fut = task::spawn(fut);
-        Box::pin(async move { Ok(fut.await) })
+        let fut = task::spawn(AssertUnwindSafe(fut).catch_unwind());
+        Box::pin(async move {
+            match fut.await {
+                Ok(result) => Ok(result),
+                Err(payload) => Err(SpawnError::Panicked(panic_message(&*payload)).into()),
+            }
+        })
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        Box::pin(task::sleep(dur))
     }
 }
 
@@ -17,13 +33,27 @@ pub struct AsyncStdMailbox;
 impl Mailbox for AsyncStdMailbox {
     fn make_mailbox<M: Send + 'static>(&self) -> (super::ActorRef<M>, Box<dyn Receiver<M>>) {
         let (tx, rx) = channel::unbounded::<M>();
-        let aref = super::ActorRef::new(Box::new(move |msg| {
-            let _ = tx.try_send(msg);
-        }));
+        let aref = super::ActorRef::new(Box::new(AsyncStdSender(tx)));
         (aref, Box::new(AsyncStdReceiver(rx)))
     }
 }
 
+struct AsyncStdSender<M>(channel::Sender<M>);
+
+impl<M: Send> Sender<M> for AsyncStdSender<M> {
+    fn tell(&self, msg: M) -> Result<(), M> {
+        self.0.try_send(msg).map_err(|e| e.into_inner())
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
 pub struct AsyncStdReceiver<M>(channel::Receiver<M>);
 
 impl<M: Send + 'static> super::Receiver<M> for AsyncStdReceiver<M> {
@@ -41,7 +71,7 @@ impl<M: Send + 'static> Future for AsyncStdReceiver<M> {
     ) -> std::task::Poll<Self::Output> {
         match self.as_mut().0.poll_next_unpin(cx) {
             Poll::Ready(Some(msg)) => Poll::Ready(Ok(msg)),
-            Poll::Ready(None) => Poll::Ready(Err(anyhow::anyhow!("channel closed"))),
+            Poll::Ready(None) => Poll::Ready(Err(NoActorRef.into())),
             Poll::Pending => Poll::Pending,
         }
     }
@@ -125,4 +155,18 @@ mod tests {
         err.downcast::<NoActorRef>()
             .unwrap_or_else(|e| panic!("unexpected error type: {}", e));
     }
+
+    #[async_std::test]
+    async fn panicked() {
+        let (aref, handle) = actor!(AsyncStdMailbox, AsyncStdSpawner, |ctx| {
+            let msg: u32 = ctx.receive().await?;
+            if msg == 0 {
+                panic!("boom");
+            }
+            Ok(msg)
+        });
+        aref.tell(0);
+        let err = handle.await.unwrap_err();
+        assert_eq!(err.to_string(), "actor task panicked: boom");
+    }
 }
@@ -0,0 +1,154 @@
+use crate::{ActorRef, MailboxFor, Spawner};
+use anyhow::Result;
+use derive_more::{Display, Error};
+use parking_lot::Mutex;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// State of a [`CircuitBreaker`](struct.CircuitBreaker.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Requests are passed on to the target
+    Closed,
+    /// Requests fail right away with [`BreakerOpen`](struct.BreakerOpen.html)
+    Open,
+    /// The cooldown has passed, the next request decides whether to close or open again
+    HalfOpen,
+}
+
+/// Configuration for [`CircuitBreaker`](struct.CircuitBreaker.html)
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerConfig {
+    /// The number of consecutive failed requests after which the breaker opens
+    pub failure_threshold: usize,
+    /// How long the breaker stays open before letting a request through again
+    pub cooldown: Duration,
+    /// How long to wait for each reply
+    pub timeout: Duration,
+}
+
+/// Error returned from [`CircuitBreaker::ask`](struct.CircuitBreaker.html#method.ask) while the breaker is open
+#[derive(Debug, Display, Error)]
+#[display(fmt = "circuit breaker is open")]
+pub struct BreakerOpen;
+
+/// Wrapper around an `ActorRef` that stops asking a target which keeps failing
+///
+/// A request fails when no reply arrives within the configured timeout or when the target drops
+/// the reply `ActorRef`. After `failure_threshold` consecutive failures the breaker opens and
+/// further requests fail with [`BreakerOpen`](struct.BreakerOpen.html) without reaching the
+/// target. Once the cooldown has passed, the breaker is half-open: a single request is let
+/// through, closing the breaker if it succeeds and opening it again if it fails.
+///
+/// Clones share their state, so the breaker can be used from several actors.
+pub struct CircuitBreaker<M> {
+    target: ActorRef<M>,
+    config: BreakerConfig,
+    state: Arc<Mutex<Breaker>>,
+}
+
+struct Breaker {
+    state: BreakerState,
+    failures: usize,
+    // taken from the spawner’s clock, see `ask`; None until the breaker first opens
+    opened_at: Option<Instant>,
+    // a half-open trial request is in flight
+    probing: bool,
+}
+
+impl<M> Clone for CircuitBreaker<M> {
+    fn clone(&self) -> Self {
+        Self {
+            target: self.target.clone(),
+            config: self.config,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<M: Send + 'static> CircuitBreaker<M> {
+    pub fn wrap(target: ActorRef<M>, config: BreakerConfig) -> Self {
+        Self {
+            target,
+            config,
+            state: Arc::new(Mutex::new(Breaker {
+                state: BreakerState::Closed,
+                failures: 0,
+                opened_at: None,
+                probing: false,
+            })),
+        }
+    }
+
+    /// The current state, an open breaker only becomes half-open with the next request
+    pub fn state(&self) -> BreakerState {
+        self.state.lock().state
+    }
+
+    /// Like [`ActorRef::ask_timeout_with`](struct.ActorRef.html#method.ask_timeout_with), but
+    /// failing fast while the breaker is open
+    ///
+    /// The timer and the clock for the cooldown are obtained from the given `Spawner`.
+    pub async fn ask<R, MB, S>(
+        &self,
+        mailbox: MB,
+        spawner: &S,
+        make_msg: impl FnOnce(ActorRef<R>) -> M,
+    ) -> Result<R>
+    where
+        R: Send + 'static,
+        MB: MailboxFor<R>,
+        S: Spawner + ?Sized,
+    {
+        {
+            let mut breaker = self.state.lock();
+            let cooled_down = breaker
+                .opened_at
+                .is_some_and(|at| spawner.now() >= at + self.config.cooldown);
+            if breaker.state == BreakerState::Open && cooled_down {
+                breaker.state = BreakerState::HalfOpen;
+            }
+            match breaker.state {
+                BreakerState::Closed => {}
+                BreakerState::Open => return Err(BreakerOpen.into()),
+                BreakerState::HalfOpen if breaker.probing => return Err(BreakerOpen.into()),
+                BreakerState::HalfOpen => breaker.probing = true,
+            }
+        }
+        let guard = ProbeGuard(&self.state);
+        let result = self
+            .target
+            .ask_timeout_with(mailbox, spawner, make_msg, self.config.timeout)
+            .await;
+        drop(guard);
+
+        let mut breaker = self.state.lock();
+        match &result {
+            Ok(_) => {
+                breaker.state = BreakerState::Closed;
+                breaker.failures = 0;
+            }
+            Err(_) => {
+                breaker.failures += 1;
+                if breaker.state == BreakerState::HalfOpen
+                    || breaker.failures >= self.config.failure_threshold
+                {
+                    breaker.state = BreakerState::Open;
+                    breaker.opened_at = Some(spawner.now());
+                }
+            }
+        }
+        result
+    }
+}
+
+// lets the next request probe a half-open breaker if this one is dropped before completing
+struct ProbeGuard<'a>(&'a Mutex<Breaker>);
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        self.0.lock().probing = false;
+    }
+}
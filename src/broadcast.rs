@@ -0,0 +1,51 @@
+use crate::ActorRef;
+use parking_lot::Mutex;
+
+/// Sends each message to all subscribed actors
+///
+/// Subscribers whose actor has stopped are removed when the next message is sent to them.
+/// A subscriber that is alive but rejects a message (e.g. due to a full bounded mailbox) misses
+/// that message but stays subscribed.
+pub struct Broadcast<M> {
+    subscribers: Mutex<Vec<ActorRef<M>>>,
+}
+
+impl<M> Default for Broadcast<M> {
+    fn default() -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<M: Clone> Broadcast<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&mut self, aref: ActorRef<M>) {
+        self.subscribers.get_mut().push(aref);
+    }
+
+    /// Remove all subscriptions for the same actor as `aref`
+    pub fn unsubscribe(&mut self, aref: &ActorRef<M>) {
+        self.subscribers.get_mut().retain(|sub| sub != aref);
+    }
+
+    /// The number of current subscribers
+    pub fn len(&self) -> usize {
+        self.subscribers.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Send a clone of the message to each subscriber
+    pub fn tell(&self, msg: M) {
+        self.subscribers.lock().retain(|sub| {
+            // closed is checked after sending since it may only be noticed then
+            sub.try_tell(msg.clone()).is_ok() || !sub.is_closed()
+        });
+    }
+}
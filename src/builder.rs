@@ -0,0 +1,193 @@
+use crate::{
+    spawn, spawn_named, ActorConfig, ActorHandle, ActorRef, Context, Lifecycle, MailboxFor,
+    ShutdownToken, Spawner,
+};
+use anyhow::Result;
+use std::{future::Future, sync::Arc};
+
+/// Step-by-step definition of an actor, as an alternative to the [`actor`](macro.actor.html) macro
+///
+/// A mailbox and a spawner must be given before the actor can be started with
+/// [`run`](#method.run), all other settings are optional:
+///
+/// ```
+/// use acto::{ActorBuilder, ActorConfig, Context, Lifecycle, NoRefsPolicy, Result, ShutdownToken};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// struct Logging;
+///
+/// impl Lifecycle<String> for Logging {
+///     async fn stopped(&mut self) {
+///         println!("stopped");
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (aref, join_handle) = ActorBuilder::new()
+///         .mailbox(TokioMailbox)
+///         .spawner(TokioSpawner)
+///         .name("greeter")
+///         .config(ActorConfig {
+///             on_no_refs: NoRefsPolicy::DrainThenStop,
+///             ..ActorConfig::default()
+///         })
+///         .shutdown_token(ShutdownToken::new())
+///         .hooks(Logging)
+///         .on_start(|ctx| {
+///             println!("starting {:?}", ctx.me());
+///             Ok(())
+///         })
+///         .run(|mut ctx: Context<String>| async move {
+///             let msg = ctx.receive().await?;
+///             println!("got msg: {}", msg);
+///             Ok(msg.len())
+///         });
+///     aref.tell("Bamm-Bamm".to_owned());
+///     assert_eq!(join_handle.await.unwrap().unwrap(), 9);
+/// }
+/// ```
+pub struct ActorBuilder<M, MB = (), S = (), H = ()> {
+    mailbox: MB,
+    spawner: S,
+    hooks: H,
+    name: Option<String>,
+    config: ActorConfig,
+    shutdown: Option<ShutdownToken>,
+    #[allow(clippy::type_complexity)]
+    on_start: Option<Box<dyn FnOnce(&mut Context<M>) -> Result<()> + Send>>,
+}
+
+impl<M> ActorBuilder<M> {
+    pub fn new() -> Self {
+        Self {
+            mailbox: (),
+            spawner: (),
+            hooks: (),
+            name: None,
+            config: ActorConfig::default(),
+            shutdown: None,
+            on_start: None,
+        }
+    }
+}
+
+impl<M> Default for ActorBuilder<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M, MB, S, H> ActorBuilder<M, MB, S, H> {
+    /// The mailbox to create for the actor
+    pub fn mailbox<MB2>(self, mailbox: MB2) -> ActorBuilder<M, MB2, S, H> {
+        ActorBuilder {
+            mailbox,
+            spawner: self.spawner,
+            hooks: self.hooks,
+            name: self.name,
+            config: self.config,
+            shutdown: self.shutdown,
+            on_start: self.on_start,
+        }
+    }
+
+    /// The spawner to run the actor with, which its `Context` passes on to child actors
+    pub fn spawner<S2>(self, spawner: S2) -> ActorBuilder<M, MB, S2, H> {
+        ActorBuilder {
+            mailbox: self.mailbox,
+            spawner,
+            hooks: self.hooks,
+            name: self.name,
+            config: self.config,
+            shutdown: self.shutdown,
+            on_start: self.on_start,
+        }
+    }
+
+    /// Setup and teardown code to run around the actor’s body, like `hooks = <expr>` in the macro
+    pub fn hooks<H2>(self, hooks: H2) -> ActorBuilder<M, MB, S, H2> {
+        ActorBuilder {
+            mailbox: self.mailbox,
+            spawner: self.spawner,
+            hooks,
+            name: self.name,
+            config: self.config,
+            shutdown: self.shutdown,
+            on_start: self.on_start,
+        }
+    }
+
+    /// Give the actor’s task a name, see [`spawn_named`](fn.spawn_named.html)
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// See [`Context::set_config`](struct.Context.html#method.set_config)
+    pub fn config(mut self, config: ActorConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// See [`Context::set_shutdown_token`](struct.Context.html#method.set_shutdown_token)
+    pub fn shutdown_token(mut self, token: ShutdownToken) -> Self {
+        self.shutdown = Some(token);
+        self
+    }
+
+    /// Run the given function on the actor’s `Context` before the hooks and the body
+    ///
+    /// An error stops the actor before the body runs.
+    pub fn on_start(
+        mut self,
+        f: impl FnOnce(&mut Context<M>) -> Result<()> + Send + 'static,
+    ) -> Self {
+        self.on_start = Some(Box::new(f));
+        self
+    }
+}
+
+impl<M, MB, S, H> ActorBuilder<M, MB, S, H>
+where
+    M: Send + 'static,
+    MB: MailboxFor<M>,
+    S: Spawner,
+    H: Lifecycle<M>,
+{
+    /// Spawn the actor, returning its address and join handle like the [`actor`](macro.actor.html) macro
+    pub fn run<F, Fut, T>(self, body: F) -> (ActorRef<M>, ActorHandle<Result<T>>)
+    where
+        F: FnOnce(Context<M>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let spawner = Arc::new(self.spawner);
+        let mut ctx = Context::new(self.mailbox, spawner.clone());
+        ctx.set_config(self.config);
+        if let Some(name) = &self.name {
+            ctx.set_name(name);
+        }
+        if let Some(token) = self.shutdown {
+            ctx.set_shutdown_token(token);
+        }
+        let aref = ctx.me();
+        let log = ctx.error_log();
+        let mut hooks = self.hooks;
+        let on_start = self.on_start;
+        let fut = async move {
+            if let Some(on_start) = on_start {
+                on_start(&mut ctx)?;
+            }
+            hooks.started(&mut ctx).await?;
+            let result = body(ctx).await;
+            hooks.stopped().await;
+            result
+        };
+        let handle = match &self.name {
+            Some(name) => spawn_named(&*spawner, name, log.wrap(fut)),
+            None => spawn(&*spawner, log.wrap(fut)),
+        };
+        (aref, handle)
+    }
+}
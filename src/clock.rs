@@ -0,0 +1,40 @@
+use crate::{DelayBox, FutureBox, FutureResultBox, Spawner};
+use std::time::{Duration, Instant};
+
+/// Source of time for the timers used by actors
+///
+/// The timer-based APIs like [`Context::receive_timeout`](struct.Context.html#method.receive_timeout)
+/// obtain their timers from the actor’s [`Spawner`](trait.Spawner.html); wrapping it in
+/// [`WithClock`](struct.WithClock.html) makes them use a different clock, e.g. one that is
+/// controlled by a test.
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> Instant;
+
+    /// Create a future that completes once `dur` has passed according to this clock
+    fn delay(&self, dur: Duration) -> DelayBox;
+}
+
+/// Spawner decorator that takes timers from the given [`Clock`](trait.Clock.html)
+pub struct WithClock<S, C>(pub S, pub C);
+
+impl<S: Spawner, C: Clock> Spawner for WithClock<S, C> {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        self.0.spawn(fut)
+    }
+
+    fn spawn_named(&self, name: &str, fut: FutureBox) -> FutureResultBox {
+        self.0.spawn_named(name, fut)
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        self.1.delay(dur)
+    }
+
+    fn now(&self) -> Instant {
+        self.1.now()
+    }
+
+    fn takes_turns(&self) -> bool {
+        self.0.takes_turns()
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{ActorRef, MailboxFor, Receiver, Sender};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    hash::Hash,
+    pin::Pin,
+    sync::Arc,
+    task::{Poll, Waker},
+};
+
+/// Where a replacing message ends up in a [`CoalescingMailbox`](struct.CoalescingMailbox.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoalesceOrder {
+    /// Take the place of the message it replaces
+    KeepPosition,
+    /// Go to the back of the queue, like a new message
+    MoveToBack,
+}
+
+/// Mailbox that keeps only the latest queued message per key
+///
+/// When a message arrives while another one with the same key (as computed by `key`) is still
+/// queued, the queued message is discarded and the new one is delivered in its stead, either at
+/// the old position or at the back of the queue depending on the [`CoalesceOrder`](enum.CoalesceOrder.html).
+/// Messages that have already been received are not affected, so an actor sees every key at most
+/// once per batch of waiting messages. This suits actors that synchronise state, where only the
+/// latest update for each item matters.
+pub struct CoalescingMailbox<M, K> {
+    pub key: fn(&M) -> K,
+    pub order: CoalesceOrder,
+}
+
+impl<M, K> CoalescingMailbox<M, K> {
+    /// A mailbox that keeps the position of replaced messages
+    pub fn new(key: fn(&M) -> K) -> Self {
+        Self {
+            key,
+            order: CoalesceOrder::KeepPosition,
+        }
+    }
+
+    pub fn with_order(self, order: CoalesceOrder) -> Self {
+        Self { order, ..self }
+    }
+}
+
+impl<M, K> MailboxFor<M> for CoalescingMailbox<M, K>
+where
+    M: Send + 'static,
+    K: Eq + Hash + Clone + Send + 'static,
+{
+    fn make_mailbox_for(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let queue = Arc::new(Mutex::new(Queue {
+            order: BTreeMap::new(),
+            latest: HashMap::new(),
+            next_gen: 0,
+            waker: None,
+            closed: false,
+        }));
+        let sender = CoalescingSender {
+            queue: queue.clone(),
+            key: self.key,
+            order: self.order,
+        };
+        (
+            ActorRef::new(Box::new(sender)),
+            Box::new(CoalescingReceiver(queue)),
+        )
+    }
+}
+
+struct Queue<M, K> {
+    // keys in delivery order, by the generation of the message they were queued for; there is
+    // exactly one entry per message in `latest`
+    order: BTreeMap<u64, K>,
+    latest: HashMap<K, (u64, M)>,
+    next_gen: u64,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+struct CoalescingSender<M, K> {
+    queue: Arc<Mutex<Queue<M, K>>>,
+    key: fn(&M) -> K,
+    order: CoalesceOrder,
+}
+
+impl<M: Send, K: Eq + Hash + Clone + Send> Sender<M> for CoalescingSender<M, K> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        let key = (self.key)(&msg);
+        let mut queue = self.queue.lock();
+        if queue.closed {
+            return Err(msg);
+        }
+        let queue = &mut *queue;
+        match (queue.latest.get_mut(&key), self.order) {
+            (Some(entry), CoalesceOrder::KeepPosition) => entry.1 = msg,
+            (entry, _) => {
+                if let Some((old, _)) = entry {
+                    queue.order.remove(old);
+                }
+                let gen = queue.next_gen;
+                queue.next_gen += 1;
+                queue.order.insert(gen, key.clone());
+                queue.latest.insert(key, (gen, msg));
+            }
+        }
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.queue.lock().latest.len()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.queue.lock().closed
+    }
+}
+
+struct CoalescingReceiver<M, K>(Arc<Mutex<Queue<M, K>>>);
+
+impl<M, K> Drop for CoalescingReceiver<M, K> {
+    fn drop(&mut self) {
+        self.0.lock().closed = true;
+    }
+}
+
+impl<M: Send + 'static, K: Eq + Hash + Send + 'static> Receiver<M> for CoalescingReceiver<M, K> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M, K: Eq + Hash> Future for CoalescingReceiver<M, K> {
+    type Output = Result<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut queue = self.0.lock();
+        if let Some((_, key)) = queue.order.pop_first() {
+            return Poll::Ready(Ok(queue.latest.remove(&key).unwrap().1));
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_to_back_keeps_one_entry_per_key() {
+        let mailbox =
+            CoalescingMailbox::new(|(k, _): &(u32, u32)| *k).with_order(CoalesceOrder::MoveToBack);
+        let queue = Arc::new(Mutex::new(Queue {
+            order: BTreeMap::new(),
+            latest: HashMap::new(),
+            next_gen: 0,
+            waker: None,
+            closed: false,
+        }));
+        let sender = CoalescingSender {
+            queue: queue.clone(),
+            key: mailbox.key,
+            order: mailbox.order,
+        };
+        for n in 0..1000 {
+            sender.tell((n % 2, n)).unwrap();
+        }
+        let queue = queue.lock();
+        assert_eq!(queue.latest.len(), 2);
+        assert_eq!(queue.order.len(), 2);
+        assert_eq!(queue.order.values().collect::<Vec<_>>(), vec![&0, &1]);
+    }
+}
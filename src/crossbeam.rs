@@ -0,0 +1,174 @@
+//! Mailbox that can be fed from plain threads without any async runtime
+//!
+//! Messages travel through a `crossbeam_channel`, each `tell` wakes the actor if it is waiting
+//! for its next message.
+
+use crate::{ActorRef, Mailbox, NoActorRef, Receiver, Sender};
+use crossbeam_channel::{unbounded, TryRecvError};
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Poll, Waker},
+};
+
+/// Mailbox based on an unbounded `crossbeam_channel`
+///
+/// Sending never blocks and needs no async context, so the resulting
+/// [`ActorRef`](../struct.ActorRef.html) can be used from any thread.
+pub struct CrossbeamMailbox;
+
+impl Mailbox for CrossbeamMailbox {
+    fn make_mailbox<M: Send + 'static>(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let (tx, rx) = unbounded::<M>();
+        let notifier = Arc::new(Notifier::default());
+        let sender = CrossbeamSender {
+            tx: Some(tx),
+            notifier: notifier.clone(),
+        };
+        (
+            ActorRef::new(Box::new(sender)),
+            Box::new(CrossbeamReceiver { rx, notifier }),
+        )
+    }
+}
+
+// bridges the blocking channel to the receiving task
+#[derive(Default)]
+struct Notifier {
+    waker: Mutex<Option<Waker>>,
+    closed: AtomicBool,
+}
+
+impl Notifier {
+    fn notify(&self) {
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+struct CrossbeamSender<M> {
+    // only taken when dropped, so that the receiver sees the disconnect before being woken
+    tx: Option<crossbeam_channel::Sender<M>>,
+    notifier: Arc<Notifier>,
+}
+
+impl<M: Send> Sender<M> for CrossbeamSender<M> {
+    fn tell(&self, msg: M) -> Result<(), M> {
+        match &self.tx {
+            Some(tx) => tx.send(msg).map_err(|e| e.into_inner())?,
+            None => return Err(msg),
+        }
+        self.notifier.notify();
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.tx.as_ref().map_or(0, |tx| tx.len())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.notifier.closed.load(Ordering::Acquire)
+    }
+}
+
+impl<M> Drop for CrossbeamSender<M> {
+    fn drop(&mut self) {
+        self.tx.take();
+        self.notifier.notify();
+    }
+}
+
+pub struct CrossbeamReceiver<M> {
+    rx: crossbeam_channel::Receiver<M>,
+    notifier: Arc<Notifier>,
+}
+
+impl<M: Send + 'static> Receiver<M> for CrossbeamReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = anyhow::Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M: Send + 'static> Future for CrossbeamReceiver<M> {
+    type Output = anyhow::Result<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        match self.rx.try_recv() {
+            Ok(msg) => return Poll::Ready(Ok(msg)),
+            Err(TryRecvError::Disconnected) => return Poll::Ready(Err(NoActorRef.into())),
+            Err(TryRecvError::Empty) => {}
+        }
+        *self.notifier.waker.lock() = Some(cx.waker().clone());
+        // a message sent before the waker was registered would otherwise go unnoticed
+        match self.rx.try_recv() {
+            Ok(msg) => Poll::Ready(Ok(msg)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(Err(NoActorRef.into())),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+}
+
+// no pinned state, the channel itself is behind a pointer
+impl<M> Unpin for CrossbeamReceiver<M> {}
+
+impl<M> Drop for CrossbeamReceiver<M> {
+    fn drop(&mut self) {
+        self.notifier.closed.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, feature = "with_tokio"))]
+mod tests {
+    use super::*;
+    use crate::tokio::TokioSpawner;
+    use anyhow::Result;
+    use std::thread;
+
+    #[tokio::test]
+    async fn from_threads() {
+        let (aref, handle) = actor!(CrossbeamMailbox, TokioSpawner, |ctx| {
+            let mut sum = 0u64;
+            while let Ok(n) = ctx.receive().await {
+                sum += n;
+            }
+            Ok(sum)
+        });
+        let threads = (0..4)
+            .map(|_| {
+                let aref = aref.clone();
+                thread::spawn(move || {
+                    for n in 1..=1000 {
+                        aref.tell(n);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(aref);
+        for t in threads {
+            t.join().unwrap();
+        }
+        assert_eq!(handle.await.unwrap().unwrap(), 4 * 500_500);
+    }
+
+    #[tokio::test]
+    async fn closed() {
+        let (aref, handle) = actor!(CrossbeamMailbox, TokioSpawner, |ctx| {
+            let msg: u32 = ctx.receive().await?;
+            Ok(msg)
+        });
+        thread::spawn({
+            let aref = aref.clone();
+            move || aref.tell(1)
+        })
+        .join()
+        .unwrap();
+        assert_eq!(handle.await.unwrap().unwrap(), 1);
+        assert!(aref.is_closed());
+    }
+}
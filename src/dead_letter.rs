@@ -0,0 +1,69 @@
+use std::{
+    any::Any,
+    fmt::{self, Debug},
+    sync::Arc,
+};
+
+/// A message that could not be delivered, see [`ActorRef::with_dead_letters`](struct.ActorRef.html#method.with_dead_letters)
+///
+/// The message is type-erased so that a single diagnostics actor can collect dead letters from
+/// actors of all kinds; it can be recovered with `downcast` if the type is known.
+pub struct DeadLetter {
+    pub message: Box<dyn Any + Send>,
+    /// The name of the message’s type, for logging
+    pub type_name: &'static str,
+    pub reason: DeadLetterReason,
+    /// The [label](struct.ActorRef.html#method.labeled) of the `ActorRef` the message was sent to
+    pub label: Option<Arc<str>>,
+}
+
+/// Why a [`DeadLetter`](struct.DeadLetter.html) was not delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// The actor’s mailbox has been closed, usually because the actor stopped
+    Closed,
+    /// The mailbox is still open but did not accept the message, e.g. because it was full
+    Rejected,
+    /// The actor received the message but has no handler for its type, see [`Dispatcher`](struct.Dispatcher.html)
+    Unhandled,
+}
+
+impl DeadLetter {
+    pub(crate) fn new<M: Send + 'static>(
+        message: M,
+        reason: DeadLetterReason,
+        label: Option<Arc<str>>,
+    ) -> Self {
+        Self {
+            message: Box::new(message),
+            type_name: std::any::type_name::<M>(),
+            reason,
+            label,
+        }
+    }
+
+    // for messages whose type is only known by name
+    pub(crate) fn erased(
+        message: Box<dyn Any + Send>,
+        type_name: &'static str,
+        reason: DeadLetterReason,
+        label: Option<Arc<str>>,
+    ) -> Self {
+        Self {
+            message,
+            type_name,
+            reason,
+            label,
+        }
+    }
+}
+
+impl Debug for DeadLetter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeadLetter")
+            .field("type_name", &self.type_name)
+            .field("reason", &self.reason)
+            .field("label", &self.label)
+            .finish()
+    }
+}
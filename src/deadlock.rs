@@ -0,0 +1,71 @@
+use crate::ActorId;
+use parking_lot::Mutex;
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
+/// Error returned from [`Context::ask`](struct.Context.html#method.ask) when the request would
+/// complete a cycle of actors waiting for each other’s replies
+///
+/// The cycle starts and ends with the asking actor, e.g. `[#1, #2, #1]` when actor #1 asks #2
+/// while #2 is waiting for a reply from #1. Only the ask that closes the cycle fails, the other
+/// actors keep waiting until they receive a reply or the reply `ActorRef` is dropped — which
+/// typically happens when the failed actor stops.
+///
+/// This needs the `with_deadlock_detection` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadlock {
+    pub cycle: Vec<ActorId>,
+}
+
+impl fmt::Display for Deadlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ask would deadlock:")?;
+        for (idx, id) in self.cycle.iter().enumerate() {
+            let sep = if idx == 0 { " " } else { " -> " };
+            write!(f, "{}{}", sep, id)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Deadlock {}
+
+// which actor each actor with an outstanding Context::ask waits for
+fn wait_for() -> &'static Mutex<HashMap<ActorId, ActorId>> {
+    static GRAPH: OnceLock<Mutex<HashMap<ActorId, ActorId>>> = OnceLock::new();
+    GRAPH.get_or_init(Default::default)
+}
+
+/// Entry in the global wait-for graph, removed when dropped
+pub(crate) struct Waiting(ActorId);
+
+impl Waiting {
+    pub(crate) fn enter(me: ActorId, target: ActorId) -> Result<Self, Deadlock> {
+        let mut graph = wait_for().lock();
+        let mut cycle = vec![me, target];
+        let mut current = target;
+        while current != me {
+            match graph.get(&current) {
+                Some(next) => {
+                    // a cycle not involving this actor cannot grow, it is reported by one of its members
+                    if cycle[1..].contains(next) && *next != me {
+                        break;
+                    }
+                    cycle.push(*next);
+                    current = *next;
+                }
+                None => break,
+            }
+        }
+        if current == me {
+            return Err(Deadlock { cycle });
+        }
+        graph.insert(me, target);
+        Ok(Self(me))
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        wait_for().lock().remove(&self.0);
+    }
+}
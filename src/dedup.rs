@@ -0,0 +1,182 @@
+use crate::{ActorRef, Permit, ReserveBox, Sender, Spawner};
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// How long a message id is remembered by [`Dedup`](struct.Dedup.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupWindow {
+    /// Remember the given number of most recently seen ids, evicting the least recently seen
+    Count(usize),
+    /// Remember ids for the given duration after they were last seen
+    Time(Duration),
+}
+
+/// Sender that drops messages whose id has already been seen within a window
+///
+/// Use it in front of idempotent handlers when messages may be delivered more than once.
+/// Duplicates are dropped silently, they count as sent, and seeing an id again refreshes it like
+/// in an LRU cache. A message that the target does not accept is not remembered, so that it can be
+/// sent again. Time windows are measured with the given spawner’s clock.
+pub struct Dedup<M, K> {
+    target: ActorRef<M>,
+    id_fn: Arc<dyn Fn(&M) -> K + Send + Sync>,
+    window: DedupWindow,
+    spawner: Arc<dyn Spawner>,
+    // shared with the permits handed out by reserve
+    seen: Arc<Mutex<Seen<K>>>,
+}
+
+struct Seen<K> {
+    // by recency, for evicting the least recently seen id
+    order: BTreeMap<u64, K>,
+    // the key into `order` and when the id was last seen
+    ids: HashMap<K, (u64, Instant)>,
+    next: u64,
+}
+
+impl<M, K> Dedup<M, K>
+where
+    M: Send + 'static,
+    K: Clone + Eq + Hash + Send + 'static,
+{
+    /// Create an `ActorRef` for the same actor that drops duplicate messages
+    ///
+    /// The returned `ActorRef` has the same [`id`](struct.ActorRef.html#method.id) and
+    /// [`label`](struct.ActorRef.html#method.label) as `target`. Only messages sent through it or
+    /// its clones are deduplicated.
+    pub fn wrap(
+        target: ActorRef<M>,
+        id_fn: impl Fn(&M) -> K + Send + Sync + 'static,
+        window: DedupWindow,
+        spawner: Arc<dyn Spawner>,
+    ) -> ActorRef<M> {
+        let id = target.id();
+        let label = target.label().map(Arc::from);
+        let dedup = Dedup {
+            target,
+            id_fn: Arc::new(id_fn),
+            window,
+            spawner,
+            seen: Arc::new(Mutex::new(Seen {
+                order: BTreeMap::new(),
+                ids: HashMap::new(),
+                next: 0,
+            })),
+        };
+        ActorRef::with_id(Box::new(dedup), id, label)
+    }
+}
+
+impl<K: Clone + Eq + Hash> Seen<K> {
+    // forget the ids that dropped out of the window
+    fn expire(&mut self, now: Instant, window: DedupWindow) {
+        let within = match window {
+            DedupWindow::Time(within) => within,
+            DedupWindow::Count(_) => return,
+        };
+        while let Some((_, id)) = self.order.first_key_value() {
+            if now.saturating_duration_since(self.ids[id].1) <= within {
+                break;
+            }
+            let (_, id) = self.order.pop_first().unwrap();
+            self.ids.remove(&id);
+        }
+    }
+
+    // mark a known id as the most recently seen, returning false if it wasn’t known
+    fn refresh(&mut self, id: &K, now: Instant) -> bool {
+        let (seq, seen) = match self.ids.get_mut(id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let id = self.order.remove(seq).unwrap();
+        *seq = self.next;
+        *seen = now;
+        self.order.insert(self.next, id);
+        self.next += 1;
+        true
+    }
+
+    // whether the id is a duplicate, refreshing it if so
+    fn check(&mut self, id: &K, now: Instant, window: DedupWindow) -> bool {
+        self.expire(now, window);
+        self.refresh(id, now)
+    }
+
+    fn insert(&mut self, id: K, now: Instant, window: DedupWindow) {
+        self.order.insert(self.next, id.clone());
+        self.ids.insert(id, (self.next, now));
+        self.next += 1;
+        if let DedupWindow::Count(n) = window {
+            while self.order.len() > n {
+                let (_, id) = self.order.pop_first().unwrap();
+                self.ids.remove(&id);
+            }
+        }
+    }
+}
+
+impl<M, K> Sender<M> for Dedup<M, K>
+where
+    M: Send + 'static,
+    K: Clone + Eq + Hash + Send + 'static,
+{
+    fn tell(&self, msg: M) -> Result<(), M> {
+        let id = (self.id_fn)(&msg);
+        let now = self.spawner.now();
+        // held while sending, so that a duplicate cannot slip past a failing first delivery
+        let mut seen = self.seen.lock();
+        if seen.check(&id, now, self.window) {
+            return Ok(());
+        }
+        self.target.try_tell(msg).map_err(|err| err.0)?;
+        seen.insert(id, now, self.window);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.target.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.target.capacity()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.target.is_closed()
+    }
+
+    fn close(&self) {
+        self.target.close()
+    }
+
+    fn stop_signal(&self) {
+        self.target.stop_signal()
+    }
+
+    fn reserve(&self) -> Option<ReserveBox<M>> {
+        let reserve = self.target.reserve()?;
+        let id_fn = self.id_fn.clone();
+        let window = self.window;
+        let spawner = self.spawner.clone();
+        let seen = self.seen.clone();
+        Some(Box::pin(async move {
+            let permit = reserve.await?;
+            // a duplicate is dropped together with the permit, releasing the reserved slot
+            Some(Box::new(move |msg: M| {
+                let id = id_fn(&msg);
+                let now = spawner.now();
+                let mut seen = seen.lock();
+                if !seen.check(&id, now, window) {
+                    permit(msg);
+                    seen.insert(id, now, window);
+                }
+            }) as Permit<M>)
+        }))
+    }
+}
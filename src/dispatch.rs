@@ -0,0 +1,177 @@
+use crate::{ActorRef, Context, DeadLetter, DeadLetterReason, SendError};
+use anyhow::Result;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt::{self, Debug},
+};
+
+/// A message of any type, as received by actors using a [`Dispatcher`](struct.Dispatcher.html)
+pub struct AnyMessage {
+    value: Box<dyn Any + Send>,
+    type_name: &'static str,
+}
+
+impl AnyMessage {
+    pub fn new<T: Any + Send>(msg: T) -> Self {
+        Self {
+            value: Box::new(msg),
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// The name of the message’s type, for logging
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.is::<T>()
+    }
+
+    /// Recover the message, handing it back if it is of a different type
+    pub fn downcast<T: Any>(self) -> std::result::Result<T, Self> {
+        match self.value.downcast::<T>() {
+            Ok(msg) => Ok(*msg),
+            Err(value) => Err(Self {
+                value,
+                type_name: self.type_name,
+            }),
+        }
+    }
+}
+
+impl Debug for AnyMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AnyMessage({})", self.type_name)
+    }
+}
+
+/// Address of an actor that accepts messages of any type
+///
+/// This trades type safety for flexibility, e.g. for plugin-style actors: whether a message is
+/// understood is only found out by the recipient, see [`Dispatcher`](struct.Dispatcher.html).
+/// The mailbox is an ordinary one for [`AnyMessage`](struct.AnyMessage.html)s, created with any
+/// [`Mailbox`](trait.Mailbox.html).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnyMailbox(pub ActorRef<AnyMessage>);
+
+impl AnyMailbox {
+    /// Send a message to the actor, ignoring delivery failures like [`ActorRef::tell`](struct.ActorRef.html#method.tell)
+    pub fn tell(&self, msg: impl Any + Send) {
+        self.0.tell(AnyMessage::new(msg));
+    }
+
+    /// Send a message to the actor, handing it back if it could not be enqueued
+    pub fn try_tell<T: Any + Send>(&self, msg: T) -> std::result::Result<(), SendError<T>> {
+        self.0
+            .try_tell(AnyMessage::new(msg))
+            .map_err(|SendError(msg)| match msg.downcast() {
+                Ok(msg) => SendError(msg),
+                Err(_) => unreachable!("message changed its type"),
+            })
+    }
+
+    /// An `ActorRef` for sending messages of one particular type to this actor
+    pub fn typed<T: Any + Send>(&self) -> ActorRef<T> {
+        self.0.clone().contramap(AnyMessage::new)
+    }
+}
+
+impl From<ActorRef<AnyMessage>> for AnyMailbox {
+    fn from(aref: ActorRef<AnyMessage>) -> Self {
+        Self(aref)
+    }
+}
+
+type Handler = Box<dyn FnMut(AnyMessage) -> Result<()> + Send>;
+
+/// Message handling by type, for actors receiving [`AnyMessage`](struct.AnyMessage.html)s
+///
+/// Each message is passed to the handler registered for its type with [`on`](#method.on); the
+/// actor fails if a handler returns an error. Messages without a handler are sent to the
+/// [`dead_letters`](#method.dead_letters) sink with
+/// [`DeadLetterReason::Unhandled`](enum.DeadLetterReason.html#variant.Unhandled), or dropped if
+/// there is none.
+///
+/// ```
+/// use acto::{actor, AnyMailbox, Dispatcher, Result};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// # #[tokio::main] async fn main() {
+/// let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+///     Dispatcher::new()
+///         .on(|name: String| {
+///             println!("hello {}", name);
+///             Ok(())
+///         })
+///         .on(|n: u32| {
+///             anyhow::ensure!(n > 0, "zero");
+///             Ok(())
+///         })
+///         .run(ctx)
+///         .await
+/// });
+/// let plugin = AnyMailbox::from(aref);
+/// plugin.tell("Fred".to_owned());
+/// plugin.tell(42u32);
+/// drop(plugin);
+/// # let _ = handle.await;
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Dispatcher {
+    handlers: HashMap<TypeId, Handler>,
+    dead_letters: Option<ActorRef<DeadLetter>>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle messages of type `T` with `f`, replacing a previously registered handler
+    pub fn on<T: Any + Send>(
+        mut self,
+        mut f: impl FnMut(T) -> Result<()> + Send + 'static,
+    ) -> Self {
+        let handler: Handler = Box::new(move |msg: AnyMessage| match msg.downcast::<T>() {
+            Ok(msg) => f(msg),
+            Err(_) => unreachable!("handler called for the wrong type"),
+        });
+        self.handlers.insert(TypeId::of::<T>(), handler);
+        self
+    }
+
+    /// Send messages without a handler to the given sink
+    pub fn dead_letters(mut self, sink: ActorRef<DeadLetter>) -> Self {
+        self.dead_letters = Some(sink);
+        self
+    }
+
+    /// Pass the message to its handler
+    pub fn dispatch(&mut self, msg: AnyMessage) -> Result<()> {
+        match self.handlers.get_mut(&(*msg.value).type_id()) {
+            Some(handler) => handler(msg),
+            None => {
+                if let Some(sink) = &self.dead_letters {
+                    sink.tell(DeadLetter::erased(
+                        msg.value,
+                        msg.type_name,
+                        DeadLetterReason::Unhandled,
+                        None,
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Receive and dispatch messages until the actor stops, like the body of an actor
+    pub async fn run(mut self, mut ctx: Context<AnyMessage>) -> Result<()> {
+        loop {
+            let msg = ctx.receive().await?;
+            self.dispatch(msg)?;
+        }
+    }
+}
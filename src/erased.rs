@@ -0,0 +1,143 @@
+use crate::{ActorRef, Mailbox, Permit, Receiver, ReserveBox, Sender};
+use anyhow::Result;
+use std::{any::Any, future::Future, pin::Pin, sync::Arc, task::Poll};
+
+type Erased = Box<dyn Any + Send>;
+
+/// Object-safe form of [`Mailbox`](trait.Mailbox.html), implemented for all mailboxes
+///
+/// The mailbox is created for type-erased messages, which [`MailboxFactory`](struct.MailboxFactory.html)
+/// then turns back into typed ones.
+pub trait DynMailbox: Send + Sync + 'static {
+    fn make_erased(&self) -> (ActorRef<Erased>, Box<dyn Receiver<Erased>>);
+}
+
+impl<MB: Mailbox + Send + Sync + 'static> DynMailbox for MB {
+    fn make_erased(&self) -> (ActorRef<Erased>, Box<dyn Receiver<Erased>>) {
+        self.make_mailbox()
+    }
+}
+
+/// A mailbox strategy that is chosen at runtime, for all message types
+///
+/// Unlike [`BoxedMailbox`](struct.BoxedMailbox.html), this is not tied to one message type, so
+/// it can be stored e.g. in an [`ActorSystem`](struct.ActorSystem.html) that spawns all kinds of
+/// actors. The price is that each message is boxed on its way through the mailbox.
+///
+/// ```
+/// use acto::{ActorSystem, BoxedSpawner, Context, MailboxFactory};
+/// use acto::tokio::{OnFull, TokioBoundedMailbox, TokioMailbox, TokioSpawner};
+/// use std::time::Duration;
+///
+/// fn system(bounded: bool) -> ActorSystem {
+///     let mailbox = if bounded {
+///         MailboxFactory::new(TokioBoundedMailbox::new(100, OnFull::Block))
+///     } else {
+///         MailboxFactory::new(TokioMailbox)
+///     };
+///     let spawner: BoxedSpawner = Box::new(TokioSpawner);
+///     ActorSystem::new(mailbox, spawner, Duration::from_secs(1))
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let system = system(true);
+///     let aref = system.spawn(|mut ctx: Context<String>| async move {
+///         let msg = ctx.receive().await?;
+///         println!("got msg: {}", msg);
+///         Ok(())
+///     });
+///     aref.tell("Dino".to_owned());
+///     system.shutdown().await;
+/// }
+/// ```
+#[derive(Clone)]
+pub struct MailboxFactory(Arc<dyn DynMailbox>);
+
+impl MailboxFactory {
+    pub fn new(mailbox: impl DynMailbox) -> Self {
+        Self(Arc::new(mailbox))
+    }
+
+    /// A function creating mailboxes for messages of type `M`
+    #[allow(clippy::type_complexity)]
+    pub fn factory<M: Send + 'static>(
+        &self,
+    ) -> Box<dyn Fn() -> (ActorRef<M>, Box<dyn Receiver<M>>) + Send + Sync> {
+        let this = self.clone();
+        Box::new(move || this.make_mailbox())
+    }
+}
+
+impl Mailbox for MailboxFactory {
+    fn make_mailbox<M: Send + 'static>(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let (aref, recv) = self.0.make_erased();
+        let id = aref.id();
+        let aref = ActorRef::with_id(Box::new(Boxing(aref)), id, None);
+        (aref, Box::new(Typed::<M>(recv, Default::default())))
+    }
+}
+
+struct Boxing(ActorRef<Erased>);
+
+impl<M: Send + 'static> Sender<M> for Boxing {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        self.0
+            .try_tell(Box::new(msg))
+            .map_err(|err| *err.0.downcast::<M>().expect("message of the wrong type"))
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.0.capacity()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    fn close(&self) {
+        self.0.close()
+    }
+
+    fn stop_signal(&self) {
+        self.0.stop_signal()
+    }
+
+    fn reserve(&self) -> Option<ReserveBox<M>> {
+        let reserve = self.0.reserve()?;
+        Some(Box::pin(async move {
+            let permit = reserve.await?;
+            Some(Box::new(move |msg: M| permit(Box::new(msg))) as Permit<M>)
+        }))
+    }
+}
+
+// the messages are only ever sent as M, see make_mailbox
+struct Typed<M>(
+    Box<dyn Receiver<Erased>>,
+    std::marker::PhantomData<fn() -> M>,
+);
+
+impl<M: Send + 'static> Receiver<M> for Typed<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.0.set_name(name)
+    }
+}
+
+impl<M: Send + 'static> Future for Typed<M> {
+    type Output = Result<M>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        Pin::new(self.0.receive()).poll(cx).map(|result| {
+            result.map(|msg| *msg.downcast::<M>().expect("message of the wrong type"))
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{Context, NoActorRef, Shutdown};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{collections::HashMap, sync::Arc};
+
+/// Actor state that is only changed by applying events
+///
+/// Commands are turned into events by [`handle_command`](#tymethod.handle_command), which must not
+/// change the state itself; this way the state can be rebuilt by replaying the events. Commands
+/// that need an answer can carry an `ActorRef` to reply to.
+pub trait EventSourced: Send + 'static {
+    type Command: Send + 'static;
+    type Event: Send + 'static;
+
+    fn handle_command(&self, cmd: Self::Command) -> Vec<Self::Event>;
+    fn apply_event(&mut self, event: &Self::Event);
+}
+
+/// Durable log of events, keyed by the event-sourced actor’s name
+pub trait EventStore<E>: Send + Sync + 'static {
+    /// Add the events to the end of the log for `id`
+    fn append(&self, id: &str, events: &[E]) -> Result<()>;
+    /// All events that have been appended for `id`, oldest first
+    fn replay(&self, id: &str) -> Result<Vec<E>>;
+}
+
+/// Event store that keeps the logs in memory, shared by all clones
+pub struct MemoryEventStore<E>(Arc<Mutex<HashMap<String, Vec<E>>>>);
+
+impl<E> Clone for MemoryEventStore<E> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<E> Default for MemoryEventStore<E> {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(HashMap::new())))
+    }
+}
+
+impl<E> MemoryEventStore<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<E: Clone + Send + 'static> EventStore<E> for MemoryEventStore<E> {
+    fn append(&self, id: &str, events: &[E]) -> Result<()> {
+        self.0
+            .lock()
+            .entry(id.to_owned())
+            .or_default()
+            .extend_from_slice(events);
+        Ok(())
+    }
+
+    fn replay(&self, id: &str) -> Result<Vec<E>> {
+        Ok(self.0.lock().get(id).cloned().unwrap_or_default())
+    }
+}
+
+/// Actor body that drives an [`EventSourced`](trait.EventSourced.html) state
+///
+/// Before receiving the first command, all events stored under `id` are applied to `state`. Then
+/// each command’s events are appended to the store and afterwards applied, so the state never
+/// reflects events that have not been persisted. Use it with the `fn` form of the
+/// [`actor`](macro.actor.html) macro or as the factory of a [`Supervised`](struct.Supervised.html)
+/// actor.
+///
+/// When the actor stops because there is no external `ActorRef` anymore or because it was asked
+/// to [`Shutdown`](struct.Shutdown.html), the state is returned.
+pub async fn event_sourced<A: EventSourced>(
+    mut ctx: Context<A::Command>,
+    store: Arc<dyn EventStore<A::Event>>,
+    id: String,
+    mut state: A,
+) -> Result<A> {
+    for event in store.replay(&id)? {
+        state.apply_event(&event);
+    }
+    loop {
+        let cmd = match ctx.receive().await {
+            Ok(cmd) => cmd,
+            Err(err) if err.is::<NoActorRef>() || err.is::<Shutdown>() => return Ok(state),
+            Err(err) => return Err(err),
+        };
+        let events = state.handle_command(cmd);
+        if events.is_empty() {
+            continue;
+        }
+        store.append(&id, &events)?;
+        for event in &events {
+            state.apply_event(event);
+        }
+    }
+}
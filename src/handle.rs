@@ -0,0 +1,169 @@
+use crate::{FutureBox, FutureResultBox, SpawnError};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    future::{poll_fn, Future},
+    marker::PhantomData,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Poll, Waker},
+};
+
+/// Join handle of a spawned actor, which can also be used to stop it
+///
+/// Awaiting the handle yields the actor’s result like the future returned from
+/// [`Spawner::spawn`](trait.Spawner.html#tymethod.spawn); dropping it lets the actor keep running.
+/// After [`abort`](#method.abort) the actor’s future is dropped the next time its task is
+/// scheduled, without waiting for its mailbox to be drained — an actor that never yields cannot
+/// be stopped this way. The handle then yields [`SpawnError::Cancelled`](enum.SpawnError.html).
+pub struct ActorHandle<T> {
+    control: Arc<Control>,
+    result: FutureResultBox,
+    _type: PhantomData<fn() -> T>,
+}
+
+#[derive(Default)]
+pub(crate) struct Control {
+    aborted: AtomicBool,
+    finished: AtomicBool,
+    // the actor task’s waker, for noticing abort() while the actor is waiting
+    waker: Mutex<Option<Waker>>,
+}
+
+// the result of an aborted actor
+struct Aborted;
+
+// sets the finished flag however the actor future ends, including cancellation by the runtime
+struct Finished(Arc<Control>);
+
+impl Drop for Finished {
+    fn drop(&mut self) {
+        self.0.finished.store(true, Ordering::SeqCst);
+    }
+}
+
+// wrap the actor future so that it can be stopped via the returned Control
+pub(crate) fn abortable(mut fut: FutureBox) -> (FutureBox, Arc<Control>) {
+    let control = Arc::new(Control::default());
+    let finished = Finished(control.clone());
+    let wrapped = Box::pin(async move {
+        let control = &finished.0;
+        poll_fn(|cx| {
+            if control.aborted.load(Ordering::SeqCst) {
+                return Poll::Ready(Box::new(Aborted) as _);
+            }
+            {
+                let mut waker = control.waker.lock();
+                if !waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+                    *waker = Some(cx.waker().clone());
+                }
+            }
+            // in case abort() was called before the waker was installed
+            if control.aborted.load(Ordering::SeqCst) {
+                return Poll::Ready(Box::new(Aborted) as _);
+            }
+            fut.as_mut().poll(cx)
+        })
+        .await
+    });
+    (wrapped, control)
+}
+
+impl<T> ActorHandle<T> {
+    pub(crate) fn new(result: FutureResultBox, control: Arc<Control>) -> Self {
+        Self {
+            control,
+            result,
+            _type: PhantomData,
+        }
+    }
+
+    /// Stop the actor, see above
+    pub fn abort(&self) {
+        self.control.abort();
+    }
+
+    /// Whether the actor’s future has completed, was aborted, or was dropped by its runtime
+    pub fn is_finished(&self) -> bool {
+        self.control.is_finished()
+    }
+
+    pub(crate) fn control(&self) -> Arc<Control> {
+        self.control.clone()
+    }
+}
+
+impl Control {
+    pub(crate) fn abort(&self) {
+        self.aborted.store(true, Ordering::SeqCst);
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+}
+
+impl<T: 'static> ActorHandle<Result<T>> {
+    /// Wait for the actor’s value, treating task failures and actor errors alike
+    ///
+    /// Awaiting the handle directly yields `Result<Result<T>>`, where the outer error is about the
+    /// task (e.g. [`SpawnError`](enum.SpawnError.html)) and the inner one is returned by the actor;
+    /// this is convenient for actors that run as one-shot computations.
+    pub async fn join(self) -> Result<T> {
+        self.await?
+    }
+}
+
+impl<T: 'static> Future for ActorHandle<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let result = match self.result.as_mut().poll(cx) {
+            Poll::Ready(result) => result?,
+            Poll::Pending => return Poll::Pending,
+        };
+        Poll::Ready(match result.downcast::<T>() {
+            Ok(out) => Ok(*out),
+            Err(result) if result.is::<Aborted>() => Err(SpawnError::Cancelled.into()),
+            Err(_) => Err(anyhow::anyhow!("found wrong type in join handle")),
+        })
+    }
+}
+
+/// Join handle of a task spawned with [`Context::spawn_task`](struct.Context.html#method.spawn_task)
+///
+/// Like an [`ActorHandle`](struct.ActorHandle.html) it yields the task’s output, or
+/// [`SpawnError::Cancelled`](enum.SpawnError.html) once the task has been aborted — either via
+/// [`abort`](#method.abort) or because the actor that spawned it has stopped. Dropping the handle
+/// lets the task keep running until then.
+pub struct TaskHandle<T>(ActorHandle<T>);
+
+impl<T> TaskHandle<T> {
+    pub(crate) fn new(handle: ActorHandle<T>) -> Self {
+        Self(handle)
+    }
+
+    /// Stop the task the next time it is scheduled
+    pub fn abort(&self) {
+        self.0.abort();
+    }
+
+    /// Whether the task’s future has completed, was aborted, or was dropped by its runtime
+    pub fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+}
+
+impl<T: 'static> Future for TaskHandle<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
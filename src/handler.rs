@@ -0,0 +1,86 @@
+use crate::{spawn, ActorHandle, ActorRef, Context, MailboxFor, NoActorRef, Shutdown, Spawner};
+use anyhow::Result;
+use std::{future::Future, sync::Arc};
+
+/// An actor defined by its state and a method for handling each message
+///
+/// This is an alternative to writing the receive loop in a closure or function for the
+/// [`actor`](macro.actor.html) macro: [`spawn_actor`](fn.spawn_actor.html) runs the loop, calling
+/// [`handle`](#tymethod.handle) for every message.
+///
+/// ```
+/// use acto::{spawn_actor, Actor, Context, Reply, Result};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// enum Command {
+///     Add(u64),
+///     Get(Reply<u64>),
+/// }
+///
+/// struct Counter(u64);
+///
+/// impl Actor for Counter {
+///     type Message = Command;
+///
+///     async fn handle(&mut self, msg: Command, _ctx: &mut Context<Command>) -> Result<()> {
+///         match msg {
+///             Command::Add(n) => self.0 += n,
+///             Command::Get(reply) => drop(reply.send(self.0)),
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let (counter, join_handle) = spawn_actor(Counter(0), TokioMailbox, TokioSpawner);
+///     counter.tell(Command::Add(5));
+///     let (reply, answer) = Reply::new();
+///     counter.tell(Command::Get(reply));
+///     assert_eq!(answer.await?, 5);
+///     drop(counter);
+///     assert_eq!(join_handle.await??.0, 5);
+///     Ok(())
+/// }
+/// ```
+pub trait Actor: Send + Sized + 'static {
+    type Message: Send + 'static;
+
+    /// Process one message, an error stops the actor
+    fn handle(
+        &mut self,
+        msg: Self::Message,
+        ctx: &mut Context<Self::Message>,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Spawn an [`Actor`](trait.Actor.html), returning its address and join handle
+///
+/// When the actor stops because there is no external `ActorRef` anymore or because it was asked
+/// to [`Shutdown`](struct.Shutdown.html), the join handle yields its final state. Errors from
+/// `handle` are returned instead.
+pub fn spawn_actor<A, MB, S>(
+    actor: A,
+    mailbox: MB,
+    spawner: S,
+) -> (ActorRef<A::Message>, ActorHandle<Result<A>>)
+where
+    A: Actor,
+    MB: MailboxFor<A::Message>,
+    S: Spawner,
+{
+    let spawner = Arc::new(spawner);
+    let ctx = Context::new(mailbox, spawner.clone());
+    let aref = ctx.me();
+    (aref, spawn(&*spawner, run(actor, ctx)))
+}
+
+async fn run<A: Actor>(mut actor: A, mut ctx: Context<A::Message>) -> Result<A> {
+    loop {
+        match ctx.receive().await {
+            Ok(msg) => actor.handle(msg, &mut ctx).await?,
+            Err(err) if err.is::<NoActorRef>() || err.is::<Shutdown>() => return Ok(actor),
+            Err(err) => return Err(err),
+        }
+    }
+}
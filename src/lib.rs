@@ -60,57 +60,509 @@
 ///     }
 /// }
 /// ```
+///
+/// Actors whose state is not `Send` can be spawned with a [`SpawnLocal`](trait.SpawnLocal.html)
+/// spawner by prefixing the inline form’s body with `local`:
+///
+/// ```
+/// use acto::{actor, Result};
+/// use acto::tokio::{LocalSpawner, TokioMailbox};
+/// use std::rc::Rc;
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     tokio::task::LocalSet::new().run_until(async {
+///         let (aref, join_handle) = actor!(TokioMailbox, LocalSpawner, local |ctx| {
+///             let state = Rc::new(ctx.receive().await?);
+///             println!("got msg: {}", state);
+///             Ok(())
+///         });
+///         aref.tell("Wilma".to_owned());
+///         join_handle.await.unwrap().unwrap();
+///     }).await;
+/// }
+/// ```
+///
+/// The messages still need to be `Send` here; for messages that are not, see
+/// [`LocalMailbox`](tokio/struct.LocalMailbox.html).
+///
+/// Instead of capturing its state from the environment, an actor can be given it with
+/// `state = <expr>`, which is then available as a mutable binding in the body; for the `fn` form
+/// it is passed as the argument after the `Context`:
+///
+/// ```
+/// use acto::{actor, ActorRef, Result};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// fn counter(start: u32) -> ActorRef<u32> {
+///     let (aref, _join_handle) = actor!(TokioMailbox, TokioSpawner, state = start, |ctx, count| {
+///         while count < 100 {
+///             count += ctx.receive().await?;
+///             println!("count is now {}", count);
+///         }
+///         Ok(())
+///     });
+///     aref
+/// }
+/// ```
+///
+/// All forms except the `local` one can be prefixed with `name = <expr>` to give the actor’s task a
+/// name, see [`spawn_named`](fn.spawn_named.html). The name is also handed to the mailbox’s
+/// [`Receiver`](trait.Receiver.html#method.set_name), so that e.g. [`Metered`](struct.Metered.html)
+/// mailboxes report it:
+///
+/// ```
+/// use acto::{actor, Result};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (aref, join_handle) = actor!(name = "greeter", TokioMailbox, TokioSpawner, |ctx| {
+///         let msg: String = ctx.receive().await?;
+///         println!("got msg: {}", msg);
+///         Ok(())
+///     });
+///     aref.tell("Betty".to_owned());
+///     join_handle.await.unwrap().unwrap();
+/// }
+/// ```
+///
+/// An [`ActorConfig`](struct.ActorConfig.html) can be given with `config = <expr>`, after the name
+/// if there is one. For example, an actor that should not work off its backlog once nobody can
+/// send to it anymore:
+///
+/// ```
+/// use acto::{actor, ActorConfig, NoRefsPolicy, Result};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let config = ActorConfig {
+///         on_no_refs: NoRefsPolicy::StopImmediately,
+///         ..ActorConfig::default()
+///     };
+///     let (aref, join_handle) = actor!(config = config, TokioMailbox, TokioSpawner, |ctx| {
+///         let msg: String = ctx.receive().await?;
+///         println!("got msg: {}", msg);
+///         Ok(())
+///     });
+///     aref.tell("Pebbles".to_owned());
+///     drop(aref);
+///     assert!(join_handle.await.unwrap().is_err());
+/// }
+/// ```
+///
+/// All forms accept a `hooks = <expr>` argument before the actor’s body, where the expression
+/// evaluates to an implementation of [`Lifecycle`](trait.Lifecycle.html). Its `started` method is
+/// awaited before the body runs, its `stopped` method after the body has finished — regardless
+/// of whether the body returned successfully, with an error, or due to
+/// [`NoActorRef`](struct.NoActorRef.html).
+///
+/// ```
+/// use acto::{actor, Context, Lifecycle, Result};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// struct Logging;
+///
+/// impl Lifecycle<String> for Logging {
+///     async fn started(&mut self, _ctx: &mut Context<String>) -> Result<()> {
+///         println!("started");
+///         Ok(())
+///     }
+///     async fn stopped(&mut self) {
+///         println!("stopped");
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (aref, join_handle) = actor!(TokioMailbox, TokioSpawner, hooks = Logging, |ctx| {
+///         let msg = ctx.receive().await?;
+///         println!("got msg: {}", msg);
+///         Ok(())
+///     });
+/// }
+/// ```
 #[macro_export]
 macro_rules! actor {
-    ($mailbox:expr, $spawner:expr, fn $f:ident($ctx:ident$(,$arg:expr)*)) => {{
+    (@spawn [] $spawner:expr, $log:expr, $fut:expr) => {
+        $crate::spawn($spawner, $log.wrap($fut))
+    };
+    (@spawn [$name:expr] $spawner:expr, $log:expr, $fut:expr) => {
+        $crate::spawn_named($spawner, $name, $log.wrap($fut))
+    };
+    (@child [] $ctx:ident, $log:expr, $fut:expr) => {
+        $ctx.spawn($log.wrap($fut))
+    };
+    (@child [$name:expr] $ctx:ident, $log:expr, $fut:expr) => {
+        $ctx.spawn_named($name, $log.wrap($fut))
+    };
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, $spawner:expr, state = $state:expr, fn $f:ident($ctx:ident$(,$arg:expr)*)) => {
+        $crate::actor!(@actor [$($name)?; $($config)?] $mailbox, $spawner, fn $f($ctx, $state $(,$arg)*))
+    };
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, $spawner:expr, state = $state:expr, |$ctx:ident, $st:ident| $code:block) => {{
+        #[allow(unused_mut)]
+        let mut $st = $state;
+        $crate::actor!(@actor [$($name)?; $($config)?] $mailbox, $spawner, |$ctx| $code)
+    }};
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, state = $state:expr, |$ctx:ident, $st:ident| $code:block) => {{
+        #[allow(unused_mut)]
+        let mut $st = $state;
+        $crate::actor!(@actor [$($name)?; $($config)?] $mailbox, |$ctx| $code)
+    }};
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, $spawner:expr, hooks = $hooks:expr, fn $f:ident($ctx:ident$(,$arg:expr)*)) => {{
+        let _spawner = ::std::sync::Arc::new($spawner);
+        let mut $ctx = $crate::Context::new($mailbox, _spawner.clone());
+        $($ctx.set_config($config);)?
+        $($ctx.set_name($name);)?
+        let _aref = $ctx.me();
+        let _log = $ctx.error_log();
+        let mut hooks = $hooks;
+        let fut = async move {
+            $crate::Lifecycle::started(&mut hooks, &mut $ctx).await?;
+            let result = $f($ctx, $($arg),*).await;
+            $crate::Lifecycle::stopped(&mut hooks).await;
+            result
+        };
+        (_aref, $crate::actor!(@spawn [$($name)?] &*_spawner, _log, fut))
+    }};
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, $spawner:expr, hooks = $hooks:expr, |$ctx:ident| $code:block) => {{
         let _spawner = ::std::sync::Arc::new($spawner);
-        let $ctx = $crate::Context::new($mailbox, _spawner.clone());
+        let mut $ctx = $crate::Context::new($mailbox, _spawner.clone());
+        $($ctx.set_config($config);)?
+        $($ctx.set_name($name);)?
         let _aref = $ctx.me();
+        let _log = $ctx.error_log();
+        let mut hooks = $hooks;
+        let fut = async move {
+            $crate::Lifecycle::started(&mut hooks, &mut $ctx).await?;
+            #[allow(unreachable_code, unused_variables)]
+            let result = async {
+                let result: Result<_> = $code;
+                result
+            }
+            .await;
+            $ctx.stop_children();
+            $crate::Lifecycle::stopped(&mut hooks).await;
+            result
+        };
+        (_aref, $crate::actor!(@spawn [$($name)?] &*_spawner, _log, fut))
+    }};
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, hooks = $hooks:expr, |$ctx:ident| $code:block) => {{
+        let (fut, aref, log) = {
+            let mut $ctx = $ctx.inherit($mailbox);
+            $($ctx.set_config($config);)?
+            $($ctx.set_name($name);)?
+            let _aref = $ctx.me();
+            let _log = $ctx.error_log();
+            let mut hooks = $hooks;
+            let fut = async move {
+                $crate::Lifecycle::started(&mut hooks, &mut $ctx).await?;
+                #[allow(unreachable_code, unused_variables)]
+                let result = async {
+                    let result: Result<_> = $code;
+                    result
+                }
+                .await;
+                $ctx.stop_children();
+                $crate::Lifecycle::stopped(&mut hooks).await;
+                result
+            };
+            (fut, _aref, _log)
+        };
+        (aref, $crate::actor!(@child [$($name)?] $ctx, log, fut))
+    }};
+    (@actor [; $($config:expr)?] $mailbox:expr, $spawner:expr, local |$ctx:ident| $code:block) => {{
+        let _spawner = ::std::sync::Arc::new($spawner);
+        let mut $ctx = $crate::Context::new($mailbox, _spawner.clone());
+        $($ctx.set_config($config);)?
+        let _aref = $ctx.me();
+        let _log = $ctx.error_log();
+        let fut = async move {
+            let result: Result<_> = $code;
+            result
+        };
+        (_aref, $crate::spawn_local(&*_spawner, _log.wrap(fut)))
+    }};
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, $spawner:expr, fn $f:ident($ctx:ident$(,$arg:expr)*)) => {{
+        let _spawner = ::std::sync::Arc::new($spawner);
+        #[allow(unused_mut)]
+        let mut $ctx = $crate::Context::new($mailbox, _spawner.clone());
+        $($ctx.set_config($config);)?
+        $($ctx.set_name($name);)?
+        let _aref = $ctx.me();
+        let _log = $ctx.error_log();
         let fut = Box::pin($f($ctx, $($arg),*));
-        (_aref, $crate::spawn(&*_spawner, fut))
+        (_aref, $crate::actor!(@spawn [$($name)?] &*_spawner, _log, fut))
     }};
-    ($mailbox:expr, $spawner:expr, |$ctx:ident| $code:block) => {{
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, $spawner:expr, |$ctx:ident| $code:block) => {{
         let _spawner = ::std::sync::Arc::new($spawner);
+        // the body may move the Context, e.g. into a Dispatcher
+        #[allow(unused_mut)]
         let mut $ctx = $crate::Context::new($mailbox, _spawner.clone());
+        $($ctx.set_config($config);)?
+        $($ctx.set_name($name);)?
         let _aref = $ctx.me();
+        let _log = $ctx.error_log();
         let fut = async move {
             let result: Result<_> = $code;
             result
         };
-        (_aref, $crate::spawn(&*_spawner, fut))
+        (_aref, $crate::actor!(@spawn [$($name)?] &*_spawner, _log, fut))
     }};
-    ($mailbox:expr, |$ctx:ident| $code:block) => {{
-        let (fut, aref) = {
+    (@actor [$($name:expr)?; $($config:expr)?] $mailbox:expr, |$ctx:ident| $code:block) => {{
+        let (fut, aref, log) = {
+            #[allow(unused_mut)]
             let mut $ctx = $ctx.inherit($mailbox);
+            $($ctx.set_config($config);)?
+            $($ctx.set_name($name);)?
             let _aref = $ctx.me();
+            let _log = $ctx.error_log();
             let fut = async move {
                 let result: Result<_> = $code;
                 result
             };
-            (fut, _aref)
+            (fut, _aref, _log)
         };
-        (aref, $ctx.spawn(fut))
-    }}
+        (aref, $crate::actor!(@child [$($name)?] $ctx, log, fut))
+    }};
+    (@actor $($rest:tt)*) => {
+        compile_error!("unsupported form of the actor! macro")
+    };
+    (name = $name:expr, config = $config:expr, $($rest:tt)*) => {
+        $crate::actor!(@actor [$name; $config] $($rest)*)
+    };
+    (name = $name:expr, $($rest:tt)*) => {
+        $crate::actor!(@actor [$name;] $($rest)*)
+    };
+    (config = $config:expr, $($rest:tt)*) => {
+        $crate::actor!(@actor [; $config] $($rest)*)
+    };
+    ($($rest:tt)*) => {
+        $crate::actor!(@actor [;] $($rest)*)
+    };
+}
+
+/// Define a typed address for an actor, with one method per message
+///
+/// Callers of a command-style actor should not have to construct its message enum themselves.
+/// This macro generates a wrapper around the actor’s `ActorRef` with a method for each variant:
+///
+/// - `fn name(args) => Variant;` sends `Variant(args)`, or just `Variant` if there are no
+///   arguments
+/// - `fn name(args) -> R => Variant;` sends `Variant(args, reply)` with a
+///   [`Reply<R>`](struct.Reply.html) as last field and returns an `async fn` yielding the answer;
+///   it fails with [`NoActorRef`](struct.NoActorRef.html) if the actor drops the `Reply`
+///
+/// ```
+/// use acto::{actor, address, Reply, Result};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// enum Command {
+///     Increment(u64),
+///     Reset,
+///     Get(Reply<u64>),
+/// }
+///
+/// address! {
+///     /// The public interface of the counter actor
+///     pub struct CounterAddr(Command) {
+///         fn increment(by: u64) => Command::Increment;
+///         fn reset() => Command::Reset;
+///         fn get() -> u64 => Command::Get;
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let (aref, _join_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+///         let mut count = 0;
+///         while let Ok(cmd) = ctx.receive().await {
+///             match cmd {
+///                 Command::Increment(by) => count += by,
+///                 Command::Reset => count = 0,
+///                 Command::Get(reply) => drop(reply.send(count)),
+///             }
+///         }
+///         Ok(())
+///     });
+///     let counter = CounterAddr::from(aref);
+///     counter.increment(3);
+///     assert_eq!(counter.get().await?, 3);
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! address {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($msg:ty) {
+            $($methods:tt)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        $vis struct $name(pub $crate::ActorRef<$msg>);
+
+        impl $name {
+            $crate::address!(@methods $($methods)*);
+        }
+
+        impl ::std::convert::From<$crate::ActorRef<$msg>> for $name {
+            fn from(aref: $crate::ActorRef<$msg>) -> Self {
+                Self(aref)
+            }
+        }
+    };
+    (@methods) => {};
+    (@methods $(#[$m:meta])* fn $f:ident($($arg:ident: $argty:ty),*) -> $ret:ty => $variant:path; $($rest:tt)*) => {
+        $(#[$m])*
+        pub async fn $f(&self, $($arg: $argty),*) -> $crate::Result<$ret> {
+            let (reply, answer) = $crate::Reply::new();
+            self.0.tell($variant($($arg,)* reply));
+            answer.await
+        }
+        $crate::address!(@methods $($rest)*);
+    };
+    (@methods $(#[$m:meta])* fn $f:ident() => $variant:path; $($rest:tt)*) => {
+        $(#[$m])*
+        pub fn $f(&self) {
+            self.0.tell($variant);
+        }
+        $crate::address!(@methods $($rest)*);
+    };
+    (@methods $(#[$m:meta])* fn $f:ident($($arg:ident: $argty:ty),*) => $variant:path; $($rest:tt)*) => {
+        $(#[$m])*
+        pub fn $f(&self, $($arg: $argty),*) {
+            self.0.tell($variant($($arg),*));
+        }
+        $crate::address!(@methods $($rest)*);
+    };
 }
 
+mod ack;
 mod actor;
+mod breaker;
+mod broadcast;
+mod builder;
+mod clock;
+mod coalescing;
+mod dead_letter;
+#[cfg(feature = "with_deadlock_detection")]
+mod deadlock;
+mod dedup;
+mod dispatch;
+mod erased;
+mod event_sourced;
+mod handle;
+mod handler;
+mod lifecycle;
+#[cfg(feature = "with_metrics")]
+mod metrics;
+mod persistence;
+mod pipeline;
+mod priority;
+mod pubsub;
+mod rate_limit;
+mod registry;
+#[cfg(feature = "with_remote")]
+pub mod remote;
+mod retry;
+mod ring;
+mod router;
+mod scatter;
+mod select;
+mod shared;
+mod shutdown;
+mod sink;
+#[cfg(feature = "with_spill")]
+mod spill;
+mod stream;
+mod supervision;
+mod system;
+mod timeout;
+mod traced;
+mod watch;
+mod watchdog;
 
 #[cfg(feature = "with_async-std")]
 pub mod async_std;
+#[cfg(feature = "with_crossbeam")]
+pub mod crossbeam;
+#[cfg(feature = "with_smol")]
+pub mod smol;
+#[cfg(feature = "with_tracing")]
+mod span;
+#[cfg(feature = "with_testing")]
+pub mod testing;
 #[cfg(feature = "with_futures")]
 pub mod thread;
 
 #[cfg(feature = "with_tokio")]
 pub mod tokio;
 
-pub use actor::{ActorRef, Context, Mailbox, NoActorRef, Receiver, Spawner};
+pub use ack::{deliver_reliably, Ack, AckStatus, Envelope, NotAcknowledged};
+pub use actor::{
+    ActorConfig, ActorId, ActorRef, AskTimeout, BoxedMailbox, BoxedSpawner, CancelHandle, Context,
+    Event, LabeledSendError, Mailbox, MailboxFor, NoActorRef, NoRefsPolicy, Receiver, RecvResult,
+    Reply, SendError, Sender, SpawnError, SpawnLocal, Spawner, WeakActorRef,
+};
 pub use anyhow::Result;
+pub use breaker::{BreakerConfig, BreakerOpen, BreakerState, CircuitBreaker};
+pub use broadcast::Broadcast;
+pub use builder::ActorBuilder;
+pub use clock::{Clock, WithClock};
+pub use coalescing::{CoalesceOrder, CoalescingMailbox};
+pub use dead_letter::{DeadLetter, DeadLetterReason};
+#[cfg(feature = "with_deadlock_detection")]
+pub use deadlock::Deadlock;
+pub use dedup::{Dedup, DedupWindow};
+pub use dispatch::{AnyMailbox, AnyMessage, Dispatcher};
+pub use erased::{DynMailbox, MailboxFactory};
+pub use event_sourced::{event_sourced, EventSourced, EventStore, MemoryEventStore};
+pub use handle::{ActorHandle, TaskHandle};
+pub use handler::{spawn_actor, Actor};
+pub use lifecycle::Lifecycle;
+#[cfg(feature = "with_metrics")]
+pub use metrics::{ActorMetrics, Metered, MetricsHandle, MetricsSink};
+pub use persistence::{persistent, Cadence, FileStore, Persistent, SnapshotConfig, SnapshotStore};
+pub use pipeline::Pipeline;
+pub use priority::{Priority, PriorityMailbox};
+pub use pubsub::{pubsub_actor, PubSub};
+pub use rate_limit::{RateLimitError, RateLimited};
+pub use registry::Registry;
+pub use retry::{retry, Backoff, RetryPolicy};
+pub use ring::RingMailbox;
+pub use router::Router;
+pub use scatter::{scatter_gather, GatherPolicy};
+pub use select::{select, BothClosed, Either, Select};
+pub use shared::SharedRuntime;
+pub use shutdown::{Shutdown, ShutdownToken};
+pub use sink::ActorSink;
+#[cfg(feature = "with_tracing")]
+pub use span::WithSpan;
+#[cfg(feature = "with_spill")]
+pub use spill::SpillMailbox;
+pub use stream::ReceiverStream;
+pub use supervision::{Directive, RestartStrategy, Supervised};
+pub use system::{ActorSystem, ShutdownReport};
+pub use timeout::{Timeout, WithTimeout};
+pub use traced::Traced;
+pub use watch::{Watchable, Watcher};
 
 use std::{any::Any, future::Future, pin::Pin};
 
 pub type FutureBox = Pin<Box<dyn Future<Output = Box<dyn Any + Send + 'static>> + Send + 'static>>;
 pub type FutureResultBox =
     Pin<Box<dyn Future<Output = anyhow::Result<Box<dyn Any + Send + 'static>>> + Send + 'static>>;
+pub type LocalFutureBox = Pin<Box<dyn Future<Output = Box<dyn Any + 'static>> + 'static>>;
+pub type LocalFutureResultBox =
+    Pin<Box<dyn Future<Output = anyhow::Result<Box<dyn Any + 'static>>> + 'static>>;
+pub type DelayBox = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+/// A reserved slot in a bounded mailbox, consumed by sending one message into it
+pub type Permit<M> = Box<dyn FnOnce(M) + Send + 'static>;
+/// Future for obtaining a [`Permit`](type.Permit.html), resolving to `None` if the mailbox is closed
+pub type ReserveBox<M> = Pin<Box<dyn Future<Output = Option<Permit<M>>> + Send + 'static>>;
 
 pub fn any_box<F>(fut: F) -> FutureBox
 where
@@ -123,16 +575,63 @@ where
     })
 }
 
-pub fn spawn<S, F>(
-    spawner: &S,
-    fut: F,
-) -> impl Future<Output = anyhow::Result<F::Output>> + Send + 'static
+// the message given to `panic!`, if any
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&'static str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "<non-string payload>".to_owned()
+    }
+}
+
+/// Spawn the future using the given spawner, returning its [`ActorHandle`](struct.ActorHandle.html)
+pub fn spawn<S, F>(spawner: &S, fut: F) -> ActorHandle<F::Output>
 where
     S: Spawner + ?Sized,
     F: Future + Send + 'static,
     F::Output: Send + 'static,
 {
-    let fut = spawner.spawn(any_box(fut));
+    let (fut, control) = handle::abortable(any_box(fut));
+    ActorHandle::new(spawner.spawn(fut), control)
+}
+
+/// Like [`spawn`](fn.spawn.html), but giving the task a name
+///
+/// The name is handed to [`Spawner::spawn_named`](trait.Spawner.html#method.spawn_named), e.g.
+/// for display in tokio-console. With the `with_tracing` feature the task also runs within an
+/// `actor` span that records the name, so it shows up in the task’s log output.
+pub fn spawn_named<S, F>(spawner: &S, name: &str, fut: F) -> ActorHandle<F::Output>
+where
+    S: Spawner + ?Sized,
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "with_tracing")]
+    let fut = tracing::Instrument::instrument(fut, tracing::info_span!("actor", name));
+    let (fut, control) = handle::abortable(any_box(fut));
+    ActorHandle::new(spawner.spawn_named(name, fut), control)
+}
+
+pub fn any_box_local<F>(fut: F) -> LocalFutureBox
+where
+    F: Future + 'static,
+    F::Output: Any + 'static,
+{
+    Box::pin(async move {
+        let result = fut.await;
+        Box::new(result) as Box<dyn Any + 'static>
+    })
+}
+
+pub fn spawn_local<S, F>(spawner: &S, fut: F) -> impl Future<Output = anyhow::Result<F::Output>>
+where
+    S: SpawnLocal + ?Sized,
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    let fut = spawner.spawn_local(any_box_local(fut));
     async move {
         let result = fut.await?;
         match result.downcast::<F::Output>() {
@@ -0,0 +1,23 @@
+use crate::Context;
+use anyhow::Result;
+use std::future::Future;
+
+/// Setup and teardown code to run around an actor’s body
+///
+/// Both methods do nothing by default. Use it via the `hooks = <expr>` argument to the
+/// [`actor`](macro.actor.html) macro.
+pub trait Lifecycle<M>: Send + 'static {
+    /// Run once before the actor’s body, an error prevents the body from running
+    fn started(&mut self, ctx: &mut Context<M>) -> impl Future<Output = Result<()>> + Send {
+        let _ = ctx;
+        async { Ok(()) }
+    }
+
+    /// Run once after the actor’s body has finished, unless the actor panicked
+    fn stopped(&mut self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+/// No hooks at all
+impl<M> Lifecycle<M> for () {}
@@ -0,0 +1,204 @@
+use crate::{ActorRef, MailboxFor, Permit, Receiver, ReserveBox, Sender};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+/// Receiver of the measurements taken by [`Metered`](struct.Metered.html) mailboxes
+///
+/// Implement this to forward the numbers to a metrics library; all methods default to doing nothing.
+pub trait MetricsSink: Send + Sync + 'static {
+    fn message_sent(&self) {}
+    fn message_received(&self) {}
+    /// The time between `receive()` returning a message and the next call to `receive()`
+    fn processing_time(&self, _dur: Duration) {}
+}
+
+/// Snapshot of an actor’s counters, see [`MetricsHandle::snapshot`](struct.MetricsHandle.html#method.snapshot)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ActorMetrics {
+    /// The name the actor was spawned with, the most recent one if the handle is shared
+    pub name: Option<String>,
+    /// The number of messages taken out of the mailbox by the actor
+    pub received: u64,
+    /// The number of messages that were accepted by the mailbox
+    pub sent: u64,
+    /// The number of messages currently waiting in the mailbox
+    pub depth: u64,
+}
+
+/// Shared counters of one or more actors, attached by spawning them with a [`Metered`](struct.Metered.html) mailbox
+#[derive(Clone, Default)]
+pub struct MetricsHandle(Arc<MetricsInner>);
+
+#[derive(Default)]
+struct MetricsInner {
+    name: Mutex<Option<String>>,
+    sent: AtomicU64,
+    received: AtomicU64,
+    sink: Option<Box<dyn MetricsSink>>,
+}
+
+impl MetricsHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Additionally pass all measurements to the given sink
+    pub fn with_sink(sink: impl MetricsSink) -> Self {
+        Self(Arc::new(MetricsInner {
+            sink: Some(Box::new(sink)),
+            ..MetricsInner::default()
+        }))
+    }
+
+    pub fn snapshot(&self) -> ActorMetrics {
+        let received = self.0.received.load(Ordering::SeqCst);
+        let sent = self.0.sent.load(Ordering::SeqCst);
+        ActorMetrics {
+            name: self.0.name.lock().clone(),
+            received,
+            sent,
+            // a message may be received before its sender has counted it
+            depth: sent.saturating_sub(received),
+        }
+    }
+
+    fn set_name(&self, name: &str) {
+        *self.0.name.lock() = Some(name.to_owned());
+    }
+
+    fn sent(&self) {
+        self.0.sent.fetch_add(1, Ordering::SeqCst);
+        if let Some(sink) = &self.0.sink {
+            sink.message_sent();
+        }
+    }
+
+    fn received(&self) {
+        self.0.received.fetch_add(1, Ordering::SeqCst);
+        if let Some(sink) = &self.0.sink {
+            sink.message_received();
+        }
+    }
+
+    fn processing_time(&self, dur: Duration) {
+        if let Some(sink) = &self.0.sink {
+            sink.processing_time(dur);
+        }
+    }
+}
+
+/// Mailbox decorator that records metrics for the actor using it
+///
+/// Messages are counted when they are accepted by the mailbox and when the actor receives them.
+pub struct Metered<MB> {
+    inner: MB,
+    handle: MetricsHandle,
+}
+
+impl<MB> Metered<MB> {
+    pub fn new(inner: MB, handle: MetricsHandle) -> Self {
+        Self { inner, handle }
+    }
+}
+
+impl<M: Send + 'static, MB: MailboxFor<M>> MailboxFor<M> for Metered<MB> {
+    fn make_mailbox_for(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let (aref, recv) = self.inner.make_mailbox_for();
+        let id = aref.id();
+        let sender = MeteredSender {
+            target: aref,
+            handle: self.handle.clone(),
+        };
+        let recv = MeteredReceiver {
+            inner: recv,
+            handle: self.handle.clone(),
+            delivered: None,
+        };
+        (
+            ActorRef::with_id(Box::new(sender), id, None),
+            Box::new(recv),
+        )
+    }
+}
+
+struct MeteredSender<M> {
+    target: ActorRef<M>,
+    handle: MetricsHandle,
+}
+
+impl<M: Send + 'static> Sender<M> for MeteredSender<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        self.target.try_tell(msg).map_err(|e| e.0)?;
+        self.handle.sent();
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.target.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.target.capacity()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.target.is_closed()
+    }
+
+    fn reserve(&self) -> Option<ReserveBox<M>> {
+        let reserve = self.target.reserve()?;
+        let handle = self.handle.clone();
+        Some(Box::pin(async move {
+            let permit = reserve.await?;
+            Some(Box::new(move |msg: M| {
+                permit(msg);
+                handle.sent();
+            }) as Permit<M>)
+        }))
+    }
+}
+
+struct MeteredReceiver<M> {
+    inner: Box<dyn Receiver<M>>,
+    handle: MetricsHandle,
+    // when the last message was handed to the actor
+    delivered: Option<Instant>,
+}
+
+impl<M: Send + 'static> Receiver<M> for MeteredReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.handle.set_name(name);
+        self.inner.set_name(name);
+    }
+}
+
+impl<M: Send + 'static> Future for MeteredReceiver<M> {
+    type Output = Result<M>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if let Some(delivered) = this.delivered.take() {
+            this.handle.processing_time(delivered.elapsed());
+        }
+        let result = Pin::new(this.inner.receive()).poll(cx);
+        if let Poll::Ready(Ok(_)) = &result {
+            this.handle.received();
+            this.delivered = Some(Instant::now());
+        }
+        result
+    }
+}
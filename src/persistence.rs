@@ -0,0 +1,155 @@
+use crate::{Context, Event, NoActorRef, Shutdown};
+use anyhow::Result;
+use std::{fs, io, path::PathBuf, sync::Arc, time::Duration};
+
+/// Actor state that can be saved to and restored from a byte representation
+///
+/// Implementations will usually delegate to a serde format like JSON or bincode.
+pub trait Persistent: Sized + Send + 'static {
+    fn snapshot(&self) -> Vec<u8>;
+    fn restore(bytes: &[u8]) -> Self;
+}
+
+/// Durable storage for snapshots, keyed by the persistent actor’s name
+pub trait SnapshotStore: Send + Sync + 'static {
+    /// The latest snapshot saved under `id`, if any
+    fn load(&self, id: &str) -> Result<Option<Vec<u8>>>;
+    /// Replace the snapshot saved under `id`
+    fn save(&self, id: &str, bytes: &[u8]) -> Result<()>;
+}
+
+/// Snapshot store keeping one file per actor in the given directory
+///
+/// Snapshots are first written to a temporary file and then moved into place, so a crash while
+/// saving leaves the previous snapshot intact. The file names are derived from the ids with path
+/// separators, `%` and a leading `.` percent-escaped, so that every id stays within the directory.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// The directory is created when saving the first snapshot
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str, ext: &str) -> PathBuf {
+        let mut name = String::with_capacity(id.len() + ext.len() + 1);
+        for (i, c) in id.char_indices() {
+            match c {
+                '/' | '\\' | ':' | '%' | '\0' => name.push_str(&format!("%{:02X}", c as u32)),
+                // neither `.` nor `..`, nor hidden files
+                '.' if i == 0 => name.push_str("%2E"),
+                c => name.push(c),
+            }
+        }
+        name.push('.');
+        name.push_str(ext);
+        self.dir.join(name)
+    }
+}
+
+impl SnapshotStore for FileStore {
+    fn load(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path(id, "snapshot")) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&self, id: &str, bytes: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp = self.path(id, "tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, self.path(id, "snapshot"))?;
+        Ok(())
+    }
+}
+
+/// When a [`persistent`](fn.persistent.html) actor saves its state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cadence {
+    /// Only when the actor stops
+    OnStop,
+    /// After every `n` messages, and when the actor stops
+    EveryMessages(usize),
+    /// Periodically while messages are processed, and when the actor stops
+    Interval(Duration),
+}
+
+/// Where and how often a [`persistent`](fn.persistent.html) actor saves its state
+#[derive(Clone)]
+pub struct SnapshotConfig {
+    pub store: Arc<dyn SnapshotStore>,
+    pub id: String,
+    pub cadence: Cadence,
+}
+
+impl SnapshotConfig {
+    /// Configuration that saves the state only when the actor stops
+    pub fn new(store: Arc<dyn SnapshotStore>, id: impl Into<String>) -> Self {
+        Self {
+            store,
+            id: id.into(),
+            cadence: Cadence::OnStop,
+        }
+    }
+
+    pub fn with_cadence(self, cadence: Cadence) -> Self {
+        Self { cadence, ..self }
+    }
+}
+
+/// Actor body that keeps its state of type `T` in a [`SnapshotStore`](trait.SnapshotStore.html)
+///
+/// The state is restored from the latest snapshot, or created with `init` if there is none. Each
+/// message is then passed to `handler` together with the state, which is saved according to the
+/// configured [`Cadence`](enum.Cadence.html). Use it with the `fn` form of the
+/// [`actor`](macro.actor.html) macro or as the factory of a
+/// [`Supervised`](struct.Supervised.html) actor, whose every incarnation resumes from the latest
+/// snapshot.
+///
+/// When the actor stops because there is no external `ActorRef` anymore or because it was asked
+/// to [`Shutdown`](struct.Shutdown.html), the state is saved a final time and returned. When the
+/// `handler` fails, the error is returned without saving, since the state may be inconsistent.
+pub async fn persistent<M, T, F>(
+    mut ctx: Context<M>,
+    config: SnapshotConfig,
+    init: impl FnOnce() -> T,
+    mut handler: F,
+) -> Result<T>
+where
+    M: Send + 'static,
+    T: Persistent,
+    F: FnMut(&mut T, M) -> Result<()>,
+{
+    let mut state = match config.store.load(&config.id)? {
+        Some(bytes) => T::restore(&bytes),
+        None => init(),
+    };
+    let mut unsaved = 0;
+    loop {
+        let event = match config.cadence {
+            Cadence::Interval(period) => ctx.receive_or_tick(period).await,
+            _ => ctx.receive().await.map(Event::Message),
+        };
+        let save = match event {
+            Ok(Event::Message(msg)) => {
+                handler(&mut state, msg)?;
+                unsaved += 1;
+                matches!(config.cadence, Cadence::EveryMessages(n) if unsaved >= n)
+            }
+            Ok(Event::Tick) => unsaved > 0,
+            Err(err) if err.is::<NoActorRef>() || err.is::<Shutdown>() => {
+                config.store.save(&config.id, &state.snapshot())?;
+                return Ok(state);
+            }
+            Err(err) => return Err(err),
+        };
+        if save {
+            config.store.save(&config.id, &state.snapshot())?;
+            unsaved = 0;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{spawn, ActorRef, Context, Mailbox, NoActorRef, Spawner};
+use anyhow::Result;
+use std::{future::Future, sync::Arc};
+
+type Build<I, O> = Box<dyn FnOnce(ActorRef<O>) -> ActorRef<I> + Send>;
+
+/// Builder for a chain of actors, each feeding its output to the next one
+///
+/// Every [`stage`](#method.stage) is an actor with a mailbox from the given factory, which
+/// transforms each message and then [`send`](struct.ActorRef.html#method.send)s the result
+/// downstream. With bounded mailboxes this means that a stage waits while the next stage’s
+/// queue is full, so a slow end of the pipeline throttles its beginning — provided the source
+/// uses `send` as well.
+///
+/// The pipeline is wired up back to front when [`into_ref`](#method.into_ref) is given the final
+/// destination. Stages stop when their upstream is gone (after processing the queued messages) or
+/// their downstream has stopped, so dropping either end shuts down the whole pipeline.
+pub struct Pipeline<MB, I, O> {
+    mailbox: Arc<MB>,
+    spawner: Arc<dyn Spawner>,
+    build: Build<I, O>,
+}
+
+impl<MB, I> Pipeline<MB, I, I>
+where
+    MB: Mailbox + Send + Sync + 'static,
+    I: Send + 'static,
+{
+    /// A pipeline without stages, spawning them with the given `spawner`
+    pub fn new(mailbox: MB, spawner: impl Spawner) -> Self {
+        Self {
+            mailbox: Arc::new(mailbox),
+            spawner: Arc::new(spawner),
+            build: Box::new(|downstream| downstream),
+        }
+    }
+}
+
+impl<MB, I, O> Pipeline<MB, I, O>
+where
+    MB: Mailbox + Send + Sync + 'static,
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    /// Append a stage that transforms each message with `f`
+    pub fn stage<P, F, Fut>(self, f: F) -> Pipeline<MB, I, P>
+    where
+        P: Send + 'static,
+        F: FnMut(O) -> Fut + Send + 'static,
+        Fut: Future<Output = P> + Send + 'static,
+    {
+        let Pipeline {
+            mailbox,
+            spawner,
+            build,
+        } = self;
+        let (mb, sp) = (mailbox.clone(), spawner.clone());
+        let build = Box::new(move |downstream: ActorRef<P>| {
+            let (aref, recv) = mb.make_mailbox::<O>();
+            let ctx = Context::from_parts(aref.inner(), recv, sp.clone());
+            // the stage’s result is only ever NoActorRef, there is nobody to report it to
+            drop(spawn(&*sp, run_stage(ctx, f, downstream)));
+            build(aref)
+        });
+        Pipeline {
+            mailbox,
+            spawner,
+            build,
+        }
+    }
+
+    /// Spawn all stages, sending the last stage’s output to `downstream`
+    ///
+    /// The returned `ActorRef` accepts the input of the first stage.
+    pub fn into_ref(self, downstream: ActorRef<O>) -> ActorRef<I> {
+        (self.build)(downstream)
+    }
+}
+
+async fn run_stage<O, P, F, Fut>(
+    mut ctx: Context<O>,
+    mut f: F,
+    downstream: ActorRef<P>,
+) -> Result<()>
+where
+    O: Send + 'static,
+    P: Send + 'static,
+    F: FnMut(O) -> Fut,
+    Fut: Future<Output = P>,
+{
+    loop {
+        let msg = ctx.receive().await?;
+        if downstream.send(f(msg).await).await.is_err() {
+            return Err(NoActorRef.into());
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{ActorRef, MailboxFor, Receiver, Sender};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Poll, Waker},
+};
+
+/// Priority of a message in a [`PriorityMailbox`](struct.PriorityMailbox.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+const LEVELS: usize = 3;
+
+/// Mailbox that lets messages of higher [`Priority`](enum.Priority.html) overtake others
+///
+/// Each priority has its own queue, and the actor receives from the highest non-empty one; within
+/// each queue messages are received in the order in which they were sent. This means that a steady
+/// flow of high-priority messages starves all others, unless a `fairness` limit is set: after that
+/// many messages in a row were received while lower-priority ones were waiting, the next message
+/// is taken from the next lower non-empty queue.
+pub struct PriorityMailbox<M> {
+    pub priority: fn(&M) -> Priority,
+    pub fairness: Option<usize>,
+}
+
+impl<M> PriorityMailbox<M> {
+    pub fn new(priority: fn(&M) -> Priority) -> Self {
+        Self {
+            priority,
+            fairness: None,
+        }
+    }
+
+    pub fn with_fairness(self, fairness: usize) -> Self {
+        Self {
+            fairness: Some(fairness),
+            ..self
+        }
+    }
+}
+
+impl<M: Send + 'static> MailboxFor<M> for PriorityMailbox<M> {
+    fn make_mailbox_for(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let queues = Arc::new(Mutex::new(Queues {
+            queues: Default::default(),
+            waker: None,
+            closed: false,
+        }));
+        let sender = PrioritySender {
+            queues: queues.clone(),
+            priority: self.priority,
+        };
+        let receiver = PriorityReceiver {
+            queues,
+            fairness: self.fairness,
+            streak: 0,
+        };
+        (ActorRef::new(Box::new(sender)), Box::new(receiver))
+    }
+}
+
+struct Queues<M> {
+    // indexed by Priority, lowest first
+    queues: [VecDeque<M>; LEVELS],
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+struct PrioritySender<M> {
+    queues: Arc<Mutex<Queues<M>>>,
+    priority: fn(&M) -> Priority,
+}
+
+impl<M: Send> Sender<M> for PrioritySender<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        let prio = (self.priority)(&msg);
+        let mut queues = self.queues.lock();
+        if queues.closed {
+            return Err(msg);
+        }
+        queues.queues[prio as usize].push_back(msg);
+        if let Some(waker) = queues.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.queues.lock().queues.iter().map(|q| q.len()).sum()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.queues.lock().closed
+    }
+}
+
+struct PriorityReceiver<M> {
+    queues: Arc<Mutex<Queues<M>>>,
+    fairness: Option<usize>,
+    // number of messages received in a row while lower-priority ones were waiting
+    streak: usize,
+}
+
+impl<M> Drop for PriorityReceiver<M> {
+    fn drop(&mut self) {
+        self.queues.lock().closed = true;
+    }
+}
+
+impl<M: Send + 'static> Receiver<M> for PriorityReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M: Send + 'static> Future for PriorityReceiver<M> {
+    type Output = Result<M>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut queues = this.queues.lock();
+        let mut non_empty = (0..LEVELS).rev().filter(|l| !queues.queues[*l].is_empty());
+        let highest = match non_empty.next() {
+            Some(level) => level,
+            None => {
+                queues.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        };
+        let lower = non_empty.next();
+        let level = match lower {
+            Some(lower) if this.fairness.is_some_and(|n| this.streak >= n) => lower,
+            _ => highest,
+        };
+        this.streak = if level == highest && lower.is_some() {
+            this.streak + 1
+        } else {
+            0
+        };
+        Poll::Ready(Ok(queues.queues[level].pop_front().unwrap()))
+    }
+}
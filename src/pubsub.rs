@@ -0,0 +1,32 @@
+use crate::{ActorRef, Broadcast, Context, MailboxFor, Spawner};
+use anyhow::Result;
+
+/// Messages understood by the actor created with [`pubsub_actor`](fn.pubsub_actor.html)
+pub enum PubSub<M> {
+    Subscribe(ActorRef<M>),
+    Publish(M),
+}
+
+/// Spawn an actor that sends each published message to all its subscribers
+///
+/// Subscribers that have stopped are removed on the next `Publish`. The actor stops once all
+/// `ActorRef`s to it have been dropped, which also drops its references to the subscribers.
+pub fn pubsub_actor<M, MB, S>(mailbox: MB, spawner: S) -> ActorRef<PubSub<M>>
+where
+    M: Clone + Send + 'static,
+    MB: MailboxFor<PubSub<M>>,
+    S: Spawner,
+{
+    let (aref, _join_handle) = actor!(mailbox, spawner, fn pubsub(ctx));
+    aref
+}
+
+async fn pubsub<M: Clone + Send + 'static>(mut ctx: Context<PubSub<M>>) -> Result<()> {
+    let mut subscribers = Broadcast::new();
+    loop {
+        match ctx.receive().await? {
+            PubSub::Subscribe(aref) => subscribers.subscribe(aref),
+            PubSub::Publish(msg) => subscribers.tell(msg),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{ActorRef, LabeledSendError, SendError, Spawner};
+use parking_lot::Mutex;
+use std::{
+    fmt::{self, Debug, Display},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Wrapper around an `ActorRef` that caps the rate at which messages are sent
+///
+/// This is a token bucket holding up to one second’s worth of permits, which is full initially:
+/// bursts of up to `permits_per_sec` messages go through right away, after that the permits are
+/// replenished evenly over time. [`try_tell`](#method.try_tell) hands back messages for which
+/// there is no permit, while [`send`](#method.send) waits for one. The bucket uses the clock and
+/// timers of the given `Spawner`, it is shared between clones.
+pub struct RateLimited<M> {
+    target: ActorRef<M>,
+    permits_per_sec: u32,
+    spawner: Arc<dyn Spawner>,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl<M> Clone for RateLimited<M> {
+    fn clone(&self) -> Self {
+        Self {
+            target: self.target.clone(),
+            permits_per_sec: self.permits_per_sec,
+            spawner: self.spawner.clone(),
+            bucket: self.bucket.clone(),
+        }
+    }
+}
+
+impl<M: Send + 'static> RateLimited<M> {
+    /// Wrap the given `ActorRef`, `permits_per_sec` must not be zero
+    pub fn wrap(target: ActorRef<M>, permits_per_sec: u32, spawner: Arc<dyn Spawner>) -> Self {
+        assert!(permits_per_sec > 0, "permits_per_sec must not be zero");
+        let bucket = Bucket {
+            tokens: permits_per_sec as f64,
+            last: spawner.now(),
+        };
+        Self {
+            target,
+            permits_per_sec,
+            spawner,
+            bucket: Arc::new(Mutex::new(bucket)),
+        }
+    }
+
+    /// The wrapped `ActorRef`, which is not rate limited
+    pub fn inner(&self) -> &ActorRef<M> {
+        &self.target
+    }
+
+    // take a permit, or return how long it takes until the next one is available
+    fn acquire(&self) -> Result<(), Duration> {
+        let rate = self.permits_per_sec as f64;
+        let mut bucket = self.bucket.lock();
+        let now = self.spawner.now();
+        let elapsed = now.saturating_duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+        bucket.last = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+
+    /// Send the message if a permit is available, like [`ActorRef::try_tell`](struct.ActorRef.html#method.try_tell)
+    ///
+    /// A permit is used up even if the message is then not delivered.
+    pub fn try_tell(&self, msg: M) -> Result<(), RateLimitError<M>> {
+        match self.acquire() {
+            Ok(()) => self
+                .target
+                .try_tell(msg)
+                .map_err(RateLimitError::Undelivered),
+            Err(_) => Err(RateLimitError::RateLimited(msg)),
+        }
+    }
+
+    /// Wait for a permit, then send the message like [`ActorRef::send`](struct.ActorRef.html#method.send)
+    pub async fn send(&self, msg: M) -> Result<(), LabeledSendError<M>> {
+        while let Err(wait) = self.acquire() {
+            self.spawner.delay(wait).await;
+        }
+        self.target.send(msg).await
+    }
+}
+
+/// Error returned from [`RateLimited::try_tell`](struct.RateLimited.html#method.try_tell), containing the message
+pub enum RateLimitError<M> {
+    /// No permit was available
+    RateLimited(M),
+    /// The target did not accept the message
+    Undelivered(SendError<M>),
+}
+
+impl<M> RateLimitError<M> {
+    /// The message that was not sent
+    pub fn into_inner(self) -> M {
+        match self {
+            RateLimitError::RateLimited(msg) => msg,
+            RateLimitError::Undelivered(err) => err.0,
+        }
+    }
+}
+
+impl<M> Display for RateLimitError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitError::RateLimited(_) => f.write_str("cannot send: rate limit exceeded"),
+            RateLimitError::Undelivered(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl<M> Debug for RateLimitError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitError::RateLimited(_) => f.write_str("RateLimited(..)"),
+            RateLimitError::Undelivered(err) => write!(f, "Undelivered({:?})", err),
+        }
+    }
+}
+
+impl<M> std::error::Error for RateLimitError<M> {}
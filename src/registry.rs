@@ -0,0 +1,65 @@
+use crate::ActorRef;
+use parking_lot::Mutex;
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::Arc,
+};
+
+/// Directory of actors that can be looked up by name
+///
+/// The registry can be cloned and shared between threads, all clones refer to the same entries.
+/// Each entry remembers the actor’s message type, so that looking it up with a different type
+/// yields `None` instead of a wrongly typed `ActorRef`.
+///
+/// A registered `ActorRef` keeps its actor alive until it is [`remove`](#method.remove)d.
+/// Entries for actors that have stopped are pruned when they are looked up.
+#[derive(Clone, Default)]
+pub struct Registry(Arc<Mutex<HashMap<String, Entry>>>);
+
+struct Entry {
+    type_id: TypeId,
+    // always an ActorRef<M> for the M identified by type_id
+    aref: Box<dyn Any + Send>,
+    is_closed: fn(&(dyn Any + Send)) -> bool,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an actor under the given name, replacing any previous entry
+    pub fn register<M: Send + 'static>(&self, name: &str, aref: ActorRef<M>) {
+        let entry = Entry {
+            type_id: TypeId::of::<M>(),
+            aref: Box::new(aref),
+            is_closed: |aref| {
+                aref.downcast_ref::<ActorRef<M>>()
+                    .is_some_and(|aref| aref.is_closed())
+            },
+        };
+        self.0.lock().insert(name.to_owned(), entry);
+    }
+
+    /// Look up the actor registered under the given name
+    ///
+    /// Returns `None` if there is no such actor, if it has stopped, or if its message type is not `M`.
+    pub fn lookup<M: 'static>(&self, name: &str) -> Option<ActorRef<M>> {
+        let mut entries = self.0.lock();
+        let entry = entries.get(name)?;
+        if (entry.is_closed)(&*entry.aref) {
+            entries.remove(name);
+            return None;
+        }
+        if entry.type_id != TypeId::of::<M>() {
+            return None;
+        }
+        entry.aref.downcast_ref::<ActorRef<M>>().cloned()
+    }
+
+    /// Remove the entry with the given name, returning whether there was one
+    pub fn remove(&self, name: &str) -> bool {
+        self.0.lock().remove(name).is_some()
+    }
+}
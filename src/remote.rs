@@ -0,0 +1,933 @@
+//! Sending messages to actors in other processes
+//!
+//! A [`RemoteReceiver`](struct.RemoteReceiver.html) accepts TCP connections and delivers the
+//! messages arriving on them to actors that have been registered by name in a
+//! [`Registry`](../struct.Registry.html); [`connect`](fn.connect.html) yields an `ActorRef` whose
+//! messages are sent over such a connection.
+//!
+//! Alternatively, [`resolve`](fn.resolve.html) addresses remote actors by URIs like
+//! `actor://host:port/name`, sharing one lazily established connection per host.
+//!
+//! Every frame on the wire is a big-endian `u32` length followed by that many bytes of payload.
+//! The connecting side starts with a JSON hello frame naming the protocol version, the
+//! [`Format`](enum.Format.html) of the following messages and optionally the target actor, which
+//! the receiving side answers with either acceptance or the reason for rejecting it — peers
+//! speaking a different protocol version are always rejected. After that
+//! only messages flow, from the connecting to the receiving side; without a target actor in the
+//! hello each message is accompanied by the name of its recipient.
+//!
+//! When the connection drops — or the receiving side closes it because the target actor has
+//! stopped or a message could not be decoded — the `ActorRef` returned from `connect` is closed
+//! and its join handle yields [`NoActorRef`](../struct.NoActorRef.html). For long-lived
+//! references that shall survive transient network failures use
+//! [`connect_reconnecting`](fn.connect_reconnecting.html) instead.
+
+use crate::{
+    tokio::{TokioMailbox, TokioSpawner},
+    ActorHandle, ActorId, ActorRef, Context, DeadLetter, DeadLetterReason, NoActorRef, Registry,
+    Result, Sender, Spawner, WeakActorRef,
+};
+use derive_more::{Display, Error};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, VecDeque},
+    future::Future,
+    io,
+    marker::PhantomData,
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream, ToSocketAddrs,
+    },
+    sync::watch,
+};
+
+// 2 since the hello names the format and the optional target actor
+const VERSION: u32 = 2;
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Encoding of the messages on a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Format {
+    Json,
+    Bincode,
+}
+
+impl Format {
+    fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>> {
+        Ok(match self {
+            Format::Json => serde_json::to_vec(value)?,
+            Format::Bincode => bincode::serialize(value)?,
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T> {
+        Ok(match self {
+            Format::Json => serde_json::from_slice(bytes)?,
+            Format::Bincode => bincode::deserialize(bytes)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Hello {
+    version: u32,
+    format: Format,
+    // `None` when the recipient is named in each message
+    name: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, M> {
+    to: &'a str,
+    msg: &'a M,
+}
+
+#[derive(Deserialize)]
+struct Incoming<M> {
+    to: String,
+    msg: M,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Welcome {
+    Accepted,
+    Rejected(String),
+}
+
+/// Error returned from [`connect`](fn.connect.html) when the remote side refused the connection
+#[derive(Debug, Display, Error)]
+#[display(fmt = "remote actor refused the connection: {}", reason)]
+pub struct HandshakeError {
+    pub reason: String,
+}
+
+/// Error returned from [`resolve`](fn.resolve.html) for malformed URIs
+#[derive(Debug, Display, Error)]
+#[display(fmt = "invalid actor URI `{}`: {}", uri, reason)]
+pub struct InvalidUri {
+    pub uri: String,
+    pub reason: &'static str,
+}
+
+/// Connect to the actor registered under `name` at the [`RemoteReceiver`](struct.RemoteReceiver.html) listening on `addr`
+///
+/// The connection is served by an actor spawned with the given spawner, which is what the
+/// returned `ActorRef` and join handle belong to. Dropping all references to it closes the
+/// connection, in which case the join handle yields `Ok(())`.
+pub async fn connect<M, S>(
+    spawner: S,
+    addr: impl ToSocketAddrs,
+    name: &str,
+) -> Result<(
+    ActorRef<M>,
+    impl Future<Output = Result<Result<()>>> + Send + 'static,
+)>
+where
+    M: Serialize + Send + 'static,
+    S: Spawner,
+{
+    let stream = handshake(addr, Format::Json, Some(name)).await?;
+    Ok(actor!(TokioMailbox, spawner, fn writer(ctx, stream)))
+}
+
+async fn handshake(
+    addr: impl ToSocketAddrs,
+    format: Format,
+    name: Option<&str>,
+) -> Result<TcpStream> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    let hello = Hello {
+        version: VERSION,
+        format,
+        name: name.map(ToOwned::to_owned),
+    };
+    write_frame(&mut stream, &serde_json::to_vec(&hello)?).await?;
+    match serde_json::from_slice(&read_frame(&mut stream).await?)? {
+        Welcome::Accepted => Ok(stream),
+        Welcome::Rejected(reason) => Err(HandshakeError { reason }.into()),
+    }
+}
+
+async fn writer<M: Serialize + Send + 'static>(
+    mut ctx: Context<M>,
+    stream: TcpStream,
+) -> Result<()> {
+    let (mut read, mut write) = stream.into_split();
+    loop {
+        tokio::select! {
+            msg = ctx.receive() => {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(err) if err.is::<NoActorRef>() => return Ok(()),
+                    Err(err) => return Err(err),
+                };
+                let bytes = serde_json::to_vec(&msg)?;
+                if write_frame(&mut write, &bytes).await.is_err() {
+                    return Err(NoActorRef.into());
+                }
+            }
+            // nothing is ever sent back after the handshake, so this only completes when the
+            // connection is closed
+            _ = read.read_u8() => return Err(NoActorRef.into()),
+        }
+    }
+}
+
+/// State of the connection of a [`Reconnecting`](struct.Reconnecting.html) reference
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    /// The connection is being established, for the first time or after waiting for the backoff
+    Connecting,
+    /// Messages are sent right away
+    Connected,
+    /// The connection was lost or could not be established, messages are buffered until the next attempt
+    Disconnected,
+    /// The connection actor has stopped because all references to it were dropped
+    Closed,
+}
+
+/// Settings for [`connect_reconnecting`](fn.connect_reconnecting.html)
+#[derive(Debug, Clone)]
+pub struct ReconnectConfig {
+    /// How many messages are kept while disconnected
+    pub buffer: usize,
+    /// The delay before the first reconnection attempt, doubled after each failed one
+    pub initial_backoff: Duration,
+    /// The longest delay between two attempts
+    pub max_backoff: Duration,
+    /// How long connecting and the handshake may take before the attempt counts as failed
+    pub handshake_timeout: Duration,
+    /// Where messages that do not fit into the buffer are sent, they are dropped if `None`
+    pub dead_letters: Option<ActorRef<DeadLetter>>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            buffer: 1000,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            handshake_timeout: Duration::from_secs(10),
+            dead_letters: None,
+        }
+    }
+}
+
+/// Reference to a remote actor that reconnects when the connection drops, see [`connect_reconnecting`](fn.connect_reconnecting.html)
+#[derive(Debug, Clone)]
+pub struct Reconnecting<M> {
+    aref: ActorRef<M>,
+    status: watch::Receiver<ConnectionStatus>,
+}
+
+impl<M> Reconnecting<M> {
+    /// The reference for sending messages, which stays usable across reconnections
+    pub fn actor_ref(&self) -> &ActorRef<M> {
+        &self.aref
+    }
+
+    /// Watch the state of the connection, e.g. for waiting until it is established
+    pub fn connection_status(&self) -> watch::Receiver<ConnectionStatus> {
+        self.status.clone()
+    }
+}
+
+/// Like [`connect`](fn.connect.html), but re-establishing the connection when it drops
+///
+/// The connection actor is spawned right away and connects in the background; no error is
+/// reported for an unreachable or refusing remote side, it is retried like a dropped connection.
+/// Between attempts it waits with exponential backoff as configured, using the timers of the
+/// given `Spawner`; meanwhile and while connecting, messages are buffered and sent in order once
+/// the connection is back.
+/// When the buffer is full, further messages are passed to the configured dead-letter sink with
+/// [`DeadLetterReason::Rejected`](../enum.DeadLetterReason.html#variant.Rejected). A message
+/// whose sending fails is sent again after reconnecting, so it may arrive twice if the connection
+/// dropped after it had been transmitted.
+///
+/// Must be called within a tokio runtime. The actor stops once all references are dropped, its
+/// join handle then yields `Ok(())`.
+pub fn connect_reconnecting<M, S, A>(
+    spawner: S,
+    addr: A,
+    name: &str,
+    config: ReconnectConfig,
+) -> (Reconnecting<M>, ActorHandle<Result<()>>)
+where
+    M: Serialize + Send + 'static,
+    S: Spawner,
+    A: ToSocketAddrs + Clone + Send + Sync + 'static,
+{
+    let (tx, status) = watch::channel(ConnectionStatus::Connecting);
+    let spawner: Arc<dyn Spawner> = Arc::new(spawner);
+    let link = Link {
+        addr,
+        name: name.to_owned(),
+        config,
+        status: tx,
+        spawner: spawner.clone(),
+    };
+    let (aref, handle) = actor!(TokioMailbox, spawner, fn reconnecting(ctx, link));
+    (Reconnecting { aref, status }, handle)
+}
+
+struct Link<A> {
+    addr: A,
+    name: String,
+    config: ReconnectConfig,
+    status: watch::Sender<ConnectionStatus>,
+    // for the backoff timer
+    spawner: Arc<dyn Spawner>,
+}
+
+impl<A> Drop for Link<A> {
+    fn drop(&mut self) {
+        self.status.send_replace(ConnectionStatus::Closed);
+    }
+}
+
+async fn reconnecting<M, A>(mut ctx: Context<M>, link: Link<A>) -> Result<()>
+where
+    M: Serialize + Send + 'static,
+    A: ToSocketAddrs + Clone + Send + Sync + 'static,
+{
+    let mut buffer = VecDeque::new();
+    let mut backoff = link.config.initial_backoff;
+    loop {
+        link.status.send_replace(ConnectionStatus::Connecting);
+        let connecting = handshake(link.addr.clone(), Format::Json, Some(&link.name));
+        tokio::pin!(connecting);
+        let mut timeout = link.spawner.delay(link.config.handshake_timeout);
+        let connected = loop {
+            tokio::select! {
+                connected = &mut connecting => break connected.ok(),
+                _ = &mut timeout => break None,
+                msg = ctx.receive() => if !buffer_received(&link, &mut buffer, msg)? {
+                    return Ok(());
+                },
+            }
+        };
+        if let Some(stream) = connected {
+            link.status.send_replace(ConnectionStatus::Connected);
+            backoff = link.config.initial_backoff;
+            let (read, write) = stream.into_split();
+            if !send_connected(&mut ctx, &link, &mut buffer, read, write).await? {
+                return Ok(());
+            }
+        }
+        link.status.send_replace(ConnectionStatus::Disconnected);
+        let mut delay = link.spawner.delay(backoff);
+        loop {
+            tokio::select! {
+                _ = &mut delay => break,
+                msg = ctx.receive() => if !buffer_received(&link, &mut buffer, msg)? {
+                    return Ok(());
+                },
+            }
+        }
+        backoff = (backoff * 2).min(link.config.max_backoff);
+    }
+}
+
+fn buffer_msg<M: Send + 'static, A>(link: &Link<A>, buffer: &mut VecDeque<M>, msg: M) {
+    if buffer.len() < link.config.buffer {
+        buffer.push_back(msg);
+    } else if let Some(sink) = &link.config.dead_letters {
+        sink.tell(DeadLetter::new(msg, DeadLetterReason::Rejected, None));
+    }
+}
+
+// returns false when the actor shall stop
+fn buffer_received<M: Send + 'static, A>(
+    link: &Link<A>,
+    buffer: &mut VecDeque<M>,
+    msg: Result<M>,
+) -> Result<bool> {
+    match msg {
+        Ok(msg) => {
+            buffer_msg(link, buffer, msg);
+            Ok(true)
+        }
+        Err(err) if err.is::<NoActorRef>() => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+// returns false when the actor shall stop, true when the connection dropped
+async fn send_connected<M: Serialize + Send + 'static, A>(
+    ctx: &mut Context<M>,
+    link: &Link<A>,
+    buffer: &mut VecDeque<M>,
+    mut read: OwnedReadHalf,
+    mut write: OwnedWriteHalf,
+) -> Result<bool> {
+    // keep buffering while replaying, so that the buffer’s bound holds; once the last reference
+    // is gone the replay is still finished
+    let mut stopping = false;
+    while let Some(msg) = buffer.front() {
+        let bytes = serde_json::to_vec(msg)?;
+        let writing = write_frame(&mut write, &bytes);
+        tokio::pin!(writing);
+        let written = loop {
+            tokio::select! {
+                written = &mut writing => break written,
+                msg = ctx.receive(), if !stopping => stopping = !buffer_received(link, buffer, msg)?,
+            }
+        };
+        if written.is_err() {
+            return Ok(!stopping);
+        }
+        buffer.pop_front();
+    }
+    if stopping {
+        return Ok(false);
+    }
+    loop {
+        tokio::select! {
+            msg = ctx.receive() => {
+                let msg = match msg {
+                    Ok(msg) => msg,
+                    Err(err) if err.is::<NoActorRef>() => return Ok(false),
+                    Err(err) => return Err(err),
+                };
+                if write_frame(&mut write, &serde_json::to_vec(&msg)?).await.is_err() {
+                    buffer_msg(link, buffer, msg);
+                    return Ok(true);
+                }
+            }
+            // as in `writer`, the receiving side only ever closes the connection
+            _ = read.read_u8() => return Ok(true),
+        }
+    }
+}
+
+/// Listener that delivers messages from remote [`connect`](fn.connect.html)ions to local actors
+///
+/// Connecting sides name their target actor, which is looked up with message type `M` in the
+/// given [`Registry`](../struct.Registry.html); if there is no such actor the connection is rejected.
+pub struct RemoteReceiver<M> {
+    listener: TcpListener,
+    registry: Registry,
+    _msg: PhantomData<fn(M)>,
+}
+
+impl<M: DeserializeOwned + Send + 'static> RemoteReceiver<M> {
+    pub async fn bind(addr: impl ToSocketAddrs, registry: Registry) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            registry,
+            _msg: PhantomData,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accept connections until accepting fails, serving each one from a task spawned with the
+    /// given spawner
+    pub async fn run<S: Spawner>(self, spawner: S) -> Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            drop(crate::spawn(
+                &spawner,
+                serve::<M>(stream, self.registry.clone()),
+            ));
+        }
+    }
+}
+
+async fn serve<M: DeserializeOwned + Send + 'static>(
+    mut stream: TcpStream,
+    registry: Registry,
+) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let hello: Hello = serde_json::from_slice(&read_frame(&mut stream).await?)?;
+    let target = match (hello.version, &hello.name) {
+        (VERSION, Some(name)) => registry
+            .lookup::<M>(name)
+            .map(Some)
+            .ok_or_else(|| format!("no actor named `{}`", name)),
+        (VERSION, None) => Ok(None),
+        (version, _) => Err(format!("unsupported protocol version {}", version)),
+    };
+    let target = match target {
+        Ok(target) => {
+            write_frame(&mut stream, &serde_json::to_vec(&Welcome::Accepted)?).await?;
+            target
+        }
+        Err(reason) => {
+            write_frame(
+                &mut stream,
+                &serde_json::to_vec(&Welcome::Rejected(reason))?,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let format = hello.format;
+    match target {
+        Some(target) => loop {
+            let msg = format.decode(&read_frame(&mut stream).await?)?;
+            if target.try_tell(msg).is_err() {
+                return Err(NoActorRef.into());
+            }
+        },
+        // the connection is shared by several senders, so unknown recipients don’t close it
+        None => loop {
+            let Incoming { to, msg } = format.decode(&read_frame(&mut stream).await?)?;
+            if let Some(target) = registry.lookup::<M>(&to) {
+                target.tell(msg);
+            }
+        },
+    }
+}
+
+type PoolKey = (String, Format, TypeId);
+
+type PoolMap = HashMap<PoolKey, (ActorId, Box<dyn Any + Send>)>;
+
+// one connection actor per host, format and message type
+fn pool() -> &'static Mutex<PoolMap> {
+    static POOL: OnceLock<Mutex<PoolMap>> = OnceLock::new();
+    POOL.get_or_init(Default::default)
+}
+
+/// Obtain an `ActorRef` for the actor addressed by a URI like `actor://host:port/name`
+///
+/// The named actor is looked up in the registry of the [`RemoteReceiver`](struct.RemoteReceiver.html)
+/// listening on that host and port. The encoding of the messages can be chosen by appending
+/// `?format=json` (the default) or `?format=bincode`.
+///
+/// Resolving checks with a short-lived connection that the remote side knows an actor of that
+/// name, failing with [`HandshakeError`](struct.HandshakeError.html) otherwise, or with the I/O
+/// error if the host cannot be reached.
+///
+/// All `ActorRef`s for the same host, format and message type share one connection, which is
+/// established when the first message is sent and re-established with the next message after it
+/// dropped. Messages are lost while the connection cannot be established, and the receiving side
+/// drops messages for actors it doesn’t know (anymore).
+///
+/// Must be called within a tokio runtime, where the connection actor is spawned.
+pub async fn resolve<M: Serialize + Send + 'static>(uri: &str) -> Result<ActorRef<M>> {
+    let invalid = |reason| InvalidUri {
+        uri: uri.to_owned(),
+        reason,
+    };
+    let rest = uri
+        .strip_prefix("actor://")
+        .ok_or_else(|| invalid("scheme must be `actor`"))?;
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    let (host, name) = rest
+        .split_once('/')
+        .ok_or_else(|| invalid("missing actor name"))?;
+    if host.is_empty() || name.is_empty() {
+        return Err(invalid("missing host or actor name").into());
+    }
+    let mut format = Format::Json;
+    for param in query.into_iter().flat_map(|q| q.split('&')) {
+        format = match param {
+            "format=json" => Format::Json,
+            "format=bincode" => Format::Bincode,
+            _ => return Err(invalid("unsupported query parameter").into()),
+        };
+    }
+
+    // the probe is closed right away, the receiving side then stops serving it
+    drop(handshake(host, format, Some(name)).await?);
+
+    let key = (host.to_owned(), format, TypeId::of::<M>());
+    let mut pool = pool().lock();
+    let existing = pool
+        .get(&key)
+        .and_then(|(_, weak)| weak.downcast_ref::<WeakActorRef<(String, M)>>())
+        .and_then(|weak| weak.upgrade())
+        .filter(|conn| !conn.is_closed());
+    let conn = match existing {
+        Some(conn) => conn,
+        None => {
+            let entry = key.clone();
+            let (conn, _handle) = actor!(TokioMailbox, TokioSpawner, fn pooled(ctx, entry, format));
+            pool.insert(key, (conn.id(), Box::new(conn.downgrade())));
+            conn
+        }
+    };
+    Ok(ActorRef::new(Box::new(Resolved {
+        conn,
+        name: name.to_owned(),
+    })))
+}
+
+// removes the pool entry for its connection actor when that stops
+struct PoolEntry {
+    key: PoolKey,
+    id: ActorId,
+}
+
+impl Drop for PoolEntry {
+    fn drop(&mut self) {
+        let mut pool = pool().lock();
+        // the entry may already have been replaced by a newer connection actor
+        if pool.get(&self.key).map(|(id, _)| *id) == Some(self.id) {
+            pool.remove(&self.key);
+        }
+    }
+}
+
+async fn pooled<M: Serialize + Send + 'static>(
+    mut ctx: Context<(String, M)>,
+    key: PoolKey,
+    format: Format,
+) -> Result<()> {
+    let host = key.0.clone();
+    let _entry = PoolEntry {
+        key,
+        id: ctx.me().id(),
+    };
+    let mut stream: Option<TcpStream> = None;
+    loop {
+        let (name, msg) = match ctx.receive().await {
+            Ok(msg) => msg,
+            Err(err) if err.is::<NoActorRef>() => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        // the receiving side never sends after the handshake, so readable means closed
+        if let Some(s) = &stream {
+            match s.try_read(&mut [0]) {
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                _ => stream = None,
+            }
+        }
+        if stream.is_none() {
+            stream = handshake(&*host, format, None).await.ok();
+        }
+        let bytes = match format.encode(&Envelope {
+            to: &name,
+            msg: &msg,
+        }) {
+            Ok(bytes) => bytes,
+            Err(_) => continue,
+        };
+        if let Some(s) = &mut stream {
+            if write_frame(s, &bytes).await.is_err() {
+                stream = None;
+            }
+        }
+    }
+}
+
+// addresses one actor over the host’s shared connection
+struct Resolved<M> {
+    conn: ActorRef<(String, M)>,
+    name: String,
+}
+
+impl<M: Send + 'static> Sender<M> for Resolved<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        self.conn
+            .try_tell((self.name.clone(), msg))
+            .map_err(|err| err.0 .1)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.conn.is_closed()
+    }
+}
+
+async fn write_frame(stream: &mut (impl AsyncWrite + Unpin), bytes: &[u8]) -> io::Result<()> {
+    if bytes.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+    stream.write_u32(bytes.len() as u32).await?;
+    stream.write_all(bytes).await?;
+    stream.flush().await
+}
+
+async fn read_frame(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<Vec<u8>> {
+    let len = stream.read_u32().await? as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+    let mut bytes = vec![0; len];
+    stream.read_exact(&mut bytes).await?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokio::TokioSpawner;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn round_trip() {
+        let registry = Registry::new();
+        let (target, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            while received.len() < 3 {
+                let msg: (u32, String) = ctx.receive().await?;
+                received.push(msg);
+            }
+            Ok(received)
+        });
+        registry.register("target", target);
+        let receiver = RemoteReceiver::<(u32, String)>::bind("127.0.0.1:0", registry)
+            .await
+            .unwrap();
+        let addr = receiver.local_addr().unwrap();
+        drop(crate::spawn(&TokioSpawner, receiver.run(TokioSpawner)));
+
+        let err = connect::<(u32, String), _>(TokioSpawner, addr, "nobody")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.downcast_ref::<HandshakeError>().unwrap().reason,
+            "no actor named `nobody`"
+        );
+
+        let (remote, writer) = connect(TokioSpawner, addr, "target").await.unwrap();
+        for i in 0..3 {
+            remote.tell((i, i.to_string()));
+        }
+        assert_eq!(
+            handle.await.unwrap().unwrap(),
+            vec![
+                (0, "0".to_owned()),
+                (1, "1".to_owned()),
+                (2, "2".to_owned())
+            ]
+        );
+
+        // the target has stopped, so the receiving side closes the connection on the next message
+        remote.tell((3, "3".to_owned()));
+        let err = writer.await.unwrap().unwrap_err();
+        assert!(err.is::<NoActorRef>());
+        assert!(remote.try_tell((4, "4".to_owned())).is_err());
+    }
+
+    // accepts connections for any name, passing on the messages until the task is aborted
+    async fn mock_transport(listener: TcpListener, tx: tokio::sync::mpsc::UnboundedSender<u32>) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_frame(&mut stream).await.unwrap();
+            write_frame(
+                &mut stream,
+                &serde_json::to_vec(&Welcome::Accepted).unwrap(),
+            )
+            .await
+            .unwrap();
+            while let Ok(bytes) = read_frame(&mut stream).await {
+                tx.send(serde_json::from_slice(&bytes).unwrap()).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnect() {
+        use tokio::sync::mpsc;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let server = tokio::spawn(mock_transport(listener, tx.clone()));
+
+        let (letters, letters_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let letter: DeadLetter = ctx.receive().await?;
+            Ok(*letter.message.downcast::<u32>().unwrap())
+        });
+        let config = ReconnectConfig {
+            buffer: 2,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+            handshake_timeout: Duration::from_secs(5),
+            dead_letters: Some(letters),
+        };
+        let (remote, handle) = connect_reconnecting::<u32, _, _>(TokioSpawner, addr, "x", config);
+        let mut status = remote.connection_status();
+        let connected = |s: &ConnectionStatus| *s == ConnectionStatus::Connected;
+        status.wait_for(connected).await.unwrap();
+        remote.actor_ref().tell(1);
+        assert_eq!(rx.recv().await, Some(1));
+
+        // kill the transport: messages are buffered up to the bound, the rest is dead-lettered
+        server.abort();
+        status
+            .wait_for(|s| *s != ConnectionStatus::Connected)
+            .await
+            .unwrap();
+        remote.actor_ref().tell_all(vec![2, 3, 4]);
+        assert_eq!(letters_handle.await.unwrap().unwrap(), 4);
+
+        // restore it on the same port: the buffered messages are replayed and new ones flow
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let server = tokio::spawn(mock_transport(listener, tx));
+        status.wait_for(connected).await.unwrap();
+        remote.actor_ref().tell(5);
+        for expected in [2, 3, 5] {
+            let msg = timeout(Duration::from_secs(5), rx.recv()).await.unwrap();
+            assert_eq!(msg, Some(expected));
+        }
+
+        drop(remote);
+        handle.await.unwrap().unwrap();
+        assert_eq!(*status.borrow(), ConnectionStatus::Closed);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn reconnect_while_connecting() {
+        // a peer that accepts connections but never answers the hello
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let (letters, letters_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            while received.len() < 2 {
+                let letter: DeadLetter = ctx.receive().await?;
+                received.push(*letter.message.downcast::<u32>().unwrap());
+            }
+            Ok(received)
+        });
+        let config = ReconnectConfig {
+            buffer: 2,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+            handshake_timeout: Duration::from_millis(300),
+            dead_letters: Some(letters),
+        };
+        let (remote, handle) = connect_reconnecting::<u32, _, _>(TokioSpawner, addr, "x", config);
+        let mut status = remote.connection_status();
+
+        // the buffer’s bound also holds while the handshake is pending
+        remote.actor_ref().tell_all(vec![1, 2, 3, 4]);
+        let letters = timeout(Duration::from_millis(200), letters_handle).await;
+        assert_eq!(letters.unwrap().unwrap().unwrap(), vec![3, 4]);
+        assert_eq!(*status.borrow(), ConnectionStatus::Connecting);
+
+        // and the attempt is given up after the timeout
+        let disconnected = status.wait_for(|s| *s == ConnectionStatus::Disconnected);
+        timeout(Duration::from_secs(5), disconnected)
+            .await
+            .unwrap()
+            .unwrap();
+
+        drop(remote);
+        handle.await.unwrap().unwrap();
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn version_mismatch() {
+        let receiver = RemoteReceiver::<u32>::bind("127.0.0.1:0", Registry::new())
+            .await
+            .unwrap();
+        let addr = receiver.local_addr().unwrap();
+        drop(crate::spawn(&TokioSpawner, receiver.run(TokioSpawner)));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let hello = Hello {
+            version: 1,
+            format: Format::Json,
+            name: None,
+        };
+        write_frame(&mut stream, &serde_json::to_vec(&hello).unwrap())
+            .await
+            .unwrap();
+        let welcome = serde_json::from_slice(&read_frame(&mut stream).await.unwrap()).unwrap();
+        match welcome {
+            Welcome::Rejected(reason) => assert_eq!(reason, "unsupported protocol version 1"),
+            Welcome::Accepted => panic!("accepted protocol version 1"),
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_uri() {
+        for uri in [
+            "tcp://localhost:1/a",
+            "actor://localhost:1",
+            "actor:///a",
+            "actor://localhost:1/a?format=xml",
+        ] {
+            let err = resolve::<String>(uri).await.unwrap_err();
+            assert!(err.is::<InvalidUri>(), "{}", uri);
+        }
+
+        let registry = Registry::new();
+        let (target_a, handle_a) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let first: String = ctx.receive().await?;
+            let second: String = ctx.receive().await?;
+            Ok((first, second))
+        });
+        let (target_b, handle_b) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: String = ctx.receive().await?;
+            Ok(msg)
+        });
+        registry.register("a", target_a);
+        registry.register("b", target_b);
+        let receiver = RemoteReceiver::<String>::bind("127.0.0.1:0", registry)
+            .await
+            .unwrap();
+        let addr = receiver.local_addr().unwrap();
+        drop(crate::spawn(&TokioSpawner, receiver.run(TokioSpawner)));
+
+        let err = resolve::<String>(&format!("actor://{}/c?format=bincode", addr))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<HandshakeError>().unwrap().reason,
+            "no actor named `c`"
+        );
+
+        let a = resolve::<String>(&format!("actor://{}/a?format=bincode", addr))
+            .await
+            .unwrap();
+        let b = resolve::<String>(&format!("actor://{}/b?format=bincode", addr))
+            .await
+            .unwrap();
+        assert_ne!(a, b);
+
+        a.tell("hello".to_owned());
+        b.tell("world".to_owned());
+        a.tell("again".to_owned());
+        assert_eq!(
+            handle_a.await.unwrap().unwrap(),
+            ("hello".to_owned(), "again".to_owned())
+        );
+        assert_eq!(handle_b.await.unwrap().unwrap(), "world");
+
+        // the connection actor stops with the last reference and takes its pool entry along
+        drop((a, b));
+        let key = (addr.to_string(), Format::Bincode, TypeId::of::<String>());
+        timeout(Duration::from_secs(5), async {
+            while pool().lock().contains_key(&key) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use crate::Spawner;
+use anyhow::Result;
+use std::{future::Future, time::Duration};
+
+/// How the delay between attempts grows, see [`RetryPolicy`](struct.RetryPolicy.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Always wait `base_delay`
+    Fixed,
+    /// Wait `base_delay` after the first failure and double it after each further one
+    Exponential,
+}
+
+/// Configuration for [`retry`](fn.retry.html)
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// The total number of attempts, including the first one
+    pub max_attempts: usize,
+    pub base_delay: Duration,
+    pub backoff: Backoff,
+    /// Errors for which this returns `false` are returned right away, without further attempts
+    pub retry_if: fn(&anyhow::Error) -> bool,
+}
+
+impl RetryPolicy {
+    /// A policy that retries on all errors
+    pub fn new(max_attempts: usize, base_delay: Duration, backoff: Backoff) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            backoff,
+            retry_if: |_| true,
+        }
+    }
+
+    fn delay(&self, failures: usize) -> Duration {
+        match self.backoff {
+            Backoff::Fixed => self.base_delay,
+            Backoff::Exponential => {
+                let factor = 1u32.checked_shl(failures as u32 - 1).unwrap_or(u32::MAX);
+                self.base_delay.saturating_mul(factor)
+            }
+        }
+    }
+}
+
+/// Run `op` until it succeeds, waiting between attempts as configured by the `policy`
+///
+/// This is meant for operations like [`ActorRef::ask_timeout_with`](struct.ActorRef.html#method.ask_timeout_with)
+/// whose target may be temporarily unavailable. The delays are obtained from the given `Spawner`.
+/// When giving up, the last error is returned.
+pub async fn retry<S, F, Fut, T>(spawner: &S, policy: RetryPolicy, op: F) -> Result<T>
+where
+    S: Spawner + ?Sized,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut failures = 0;
+    loop {
+        let err = match op().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        failures += 1;
+        if failures >= policy.max_attempts || !(policy.retry_if)(&err) {
+            return Err(err);
+        }
+        spawner.delay(policy.delay(failures)).await;
+    }
+}
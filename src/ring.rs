@@ -0,0 +1,117 @@
+use crate::{ActorRef, Mailbox, Receiver, Sender};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Poll, Waker},
+};
+
+/// Mailbox that retains only the most recent `capacity` messages
+///
+/// When the queue is full, sending a message discards the oldest queued one, so senders are
+/// never blocked and never see a failure while the actor is running; the actor receives the
+/// retained messages in the order in which they were sent. This suits telemetry and similar
+/// flows where only the latest values matter: memory use is bounded regardless of the rate of
+/// messages. A capacity of zero discards all messages.
+pub struct RingMailbox {
+    pub capacity: usize,
+}
+
+impl RingMailbox {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity }
+    }
+}
+
+impl Mailbox for RingMailbox {
+    fn make_mailbox<M: Send + 'static>(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let ring = Arc::new(Mutex::new(Ring {
+            queue: VecDeque::with_capacity(self.capacity),
+            waker: None,
+            closed: false,
+        }));
+        let sender = RingSender {
+            ring: ring.clone(),
+            capacity: self.capacity,
+        };
+        (
+            ActorRef::new(Box::new(sender)),
+            Box::new(RingReceiver(ring)),
+        )
+    }
+}
+
+struct Ring<M> {
+    queue: VecDeque<M>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+struct RingSender<M> {
+    ring: Arc<Mutex<Ring<M>>>,
+    capacity: usize,
+}
+
+impl<M: Send> Sender<M> for RingSender<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        let mut ring = self.ring.lock();
+        if ring.closed {
+            return Err(msg);
+        }
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        if ring.queue.len() >= self.capacity {
+            ring.queue.pop_front();
+        }
+        ring.queue.push_back(msg);
+        if let Some(waker) = ring.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.ring.lock().queue.len()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.capacity)
+    }
+
+    fn is_closed(&self) -> bool {
+        self.ring.lock().closed
+    }
+}
+
+struct RingReceiver<M>(Arc<Mutex<Ring<M>>>);
+
+impl<M> Drop for RingReceiver<M> {
+    fn drop(&mut self) {
+        self.0.lock().closed = true;
+    }
+}
+
+impl<M: Send + 'static> Receiver<M> for RingReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M: Send + 'static> Future for RingReceiver<M> {
+    type Output = Result<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut ring = self.0.lock();
+        match ring.queue.pop_front() {
+            Some(msg) => Poll::Ready(Ok(msg)),
+            None => {
+                ring.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{ActorRef, Sender};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// Spreads messages across a pool of actors
+///
+/// The router can be used directly via [`tell`](#method.tell) or wrapped up as an
+/// [`ActorRef`](struct.ActorRef.html) with [`as_actor_ref`](#method.as_actor_ref), so that
+/// downstream code does not need to know that it talks to a pool. The pool’s actors are kept
+/// alive as long as the router exists.
+pub struct Router<M> {
+    refs: Vec<ActorRef<M>>,
+    strategy: Strategy,
+    index: AtomicUsize,
+    seed: AtomicU64,
+}
+
+enum Strategy {
+    RoundRobin,
+    Random,
+    LeastLoaded,
+}
+
+impl<M> Router<M> {
+    fn new(refs: Vec<ActorRef<M>>, strategy: Strategy) -> Self {
+        let seed = RandomState::new().build_hasher().finish() | 1;
+        Self {
+            refs,
+            strategy,
+            index: AtomicUsize::new(0),
+            seed: AtomicU64::new(seed),
+        }
+    }
+
+    /// Send to each actor in turn
+    pub fn round_robin(refs: Vec<ActorRef<M>>) -> Self {
+        Self::new(refs, Strategy::RoundRobin)
+    }
+
+    /// Send to a randomly chosen actor
+    pub fn random(refs: Vec<ActorRef<M>>) -> Self {
+        Self::new(refs, Strategy::Random)
+    }
+
+    /// Send to the actor with the fewest messages waiting in its mailbox, see [`ActorRef::len`](struct.ActorRef.html#method.len)
+    ///
+    /// Ties are broken round-robin. The queue lengths are only a snapshot, so concurrent calls to
+    /// `tell` may pick the same actor even though another one would have been less loaded after
+    /// the first message; this is good enough for spreading work of variable cost. Mailboxes
+    /// that do not track their length report zero and thus degrade this to round-robin.
+    pub fn least_loaded(refs: Vec<ActorRef<M>>) -> Self {
+        Self::new(refs, Strategy::LeastLoaded)
+    }
+
+    /// Send a message to one of the pool’s actors, ignoring delivery failures
+    pub fn tell(&self, msg: M) {
+        if let Some(aref) = self.pick() {
+            aref.tell(msg);
+        }
+    }
+
+    /// Turn this router into an `ActorRef` that sends to the pool
+    pub fn as_actor_ref(self) -> ActorRef<M>
+    where
+        M: Send + 'static,
+    {
+        ActorRef::new(Box::new(self))
+    }
+
+    fn pick(&self) -> Option<&ActorRef<M>> {
+        if self.refs.is_empty() {
+            return None;
+        }
+        let idx = match self.strategy {
+            Strategy::RoundRobin => self.index.fetch_add(1, Ordering::Relaxed),
+            Strategy::Random => self.random_index(),
+            Strategy::LeastLoaded => self.least_loaded_index(),
+        };
+        Some(&self.refs[idx % self.refs.len()])
+    }
+
+    fn least_loaded_index(&self) -> usize {
+        let n = self.refs.len();
+        let start = self.index.fetch_add(1, Ordering::Relaxed) % n;
+        (start..start + n)
+            .min_by_key(|idx| self.refs[idx % n].len())
+            .unwrap()
+    }
+
+    // xorshift is plenty for load balancing
+    fn random_index(&self) -> usize {
+        let step = |mut x: u64| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        let prev = self
+            .seed
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| Some(step(x)))
+            .unwrap();
+        (step(prev) >> 32) as usize
+    }
+}
+
+impl<M: Send> Sender<M> for Router<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        match self.pick() {
+            Some(aref) => aref.try_tell(msg).map_err(|e| e.0),
+            None => Err(msg),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.refs.iter().map(|aref| aref.len()).sum()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.refs.iter().all(|aref| aref.is_closed())
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{ActorRef, Reply, Spawner};
+use anyhow::Result;
+use std::{
+    future::{poll_fn, Future},
+    task::Poll,
+    time::Duration,
+};
+
+/// How many replies [`scatter_gather`](fn.scatter_gather.html) waits for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GatherPolicy {
+    /// Wait until every target has replied or dropped its `Reply`
+    All,
+    /// Stop as soon as this many targets have replied successfully
+    FirstN(usize),
+    /// Collect the replies arriving within the given duration
+    WithinTimeout(Duration),
+}
+
+/// Send a request to all `targets` and gather their replies as configured by the `gather` policy
+///
+/// Each target gets its own message created by `make_msg`, which must embed the given
+/// [`Reply`](struct.Reply.html). The results are returned in the order in which they arrived,
+/// including failures of targets that dropped their `Reply` without answering. Once the policy is
+/// satisfied the outstanding requests are abandoned, so the corresponding `Reply::send` calls fail.
+/// The timer for [`GatherPolicy::WithinTimeout`](enum.GatherPolicy.html) is obtained from the
+/// given `Spawner`.
+pub async fn scatter_gather<M, R, S>(
+    spawner: &S,
+    targets: &[ActorRef<M>],
+    make_msg: impl Fn(Reply<R>) -> M,
+    gather: GatherPolicy,
+) -> Vec<Result<R>>
+where
+    R: Send + 'static,
+    S: Spawner + ?Sized,
+{
+    let wanted = match gather {
+        GatherPolicy::FirstN(n) => n,
+        _ => usize::MAX,
+    };
+    let mut pending = targets
+        .iter()
+        .map(|target| {
+            let (reply, answer) = Reply::new();
+            target.tell(make_msg(reply));
+            Some(Box::pin(answer))
+        })
+        .collect::<Vec<_>>();
+    let mut delay = match gather {
+        GatherPolicy::WithinTimeout(timeout) => Some(spawner.delay(timeout)),
+        _ => None,
+    };
+
+    let mut results = Vec::new();
+    let mut successes = 0;
+    poll_fn(|cx| {
+        for slot in pending.iter_mut() {
+            if successes >= wanted {
+                return Poll::Ready(());
+            }
+            if let Some(answer) = slot {
+                if let Poll::Ready(result) = answer.as_mut().poll(cx) {
+                    *slot = None;
+                    successes += result.is_ok() as usize;
+                    results.push(result);
+                }
+            }
+        }
+        if successes >= wanted || pending.iter().all(Option::is_none) {
+            return Poll::Ready(());
+        }
+        match &mut delay {
+            Some(delay) => delay.as_mut().poll(cx),
+            None => Poll::Pending,
+        }
+    })
+    .await;
+    results
+}
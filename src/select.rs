@@ -0,0 +1,122 @@
+use crate::Receiver;
+use anyhow::Result;
+use derive_more::{Display, Error};
+use std::{future::Future, pin::Pin, task::Poll};
+
+/// One of two alternatives, e.g. a message from either of two receivers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Merge two receivers into one, see [`Select`](struct.Select.html)
+pub fn select<A: Send + 'static, B: Send + 'static>(
+    left: Box<dyn Receiver<A>>,
+    right: Box<dyn Receiver<B>>,
+) -> Select<A, B> {
+    Select {
+        left,
+        right,
+        left_closed: false,
+        right_closed: false,
+        left_first: true,
+    }
+}
+
+/// Receiver for messages from two receivers, created with [`select`](fn.select.html)
+///
+/// The policy is round-robin: the side that did not deliver the last message is polled first for
+/// the next one. While both sides have messages queued they therefore strictly alternate, so that
+/// neither can starve the other however high its rate; when only one side has messages, it is
+/// drained without waiting for the other. When
+/// one side fails (usually because it is closed) it is no longer polled, which can be checked
+/// with [`left_closed`](#method.left_closed) and [`right_closed`](#method.right_closed); the
+/// `Select` continues with the other side and fails with [`BothClosed`](struct.BothClosed.html)
+/// once that is closed as well.
+pub struct Select<A: Send + 'static, B: Send + 'static> {
+    left: Box<dyn Receiver<A>>,
+    right: Box<dyn Receiver<B>>,
+    left_closed: bool,
+    right_closed: bool,
+    left_first: bool,
+}
+
+impl<A: Send + 'static, B: Send + 'static> Select<A, B> {
+    pub fn left_closed(&self) -> bool {
+        self.left_closed
+    }
+
+    pub fn right_closed(&self) -> bool {
+        self.right_closed
+    }
+
+    fn poll_left(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Either<A, B>> {
+        if self.left_closed {
+            return Poll::Pending;
+        }
+        match Pin::new(self.left.receive()).poll(cx) {
+            Poll::Ready(Ok(msg)) => Poll::Ready(Either::Left(msg)),
+            Poll::Ready(Err(_)) => {
+                self.left_closed = true;
+                Poll::Pending
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_right(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Either<A, B>> {
+        if self.right_closed {
+            return Poll::Pending;
+        }
+        match Pin::new(self.right.receive()).poll(cx) {
+            Poll::Ready(Ok(msg)) => Poll::Ready(Either::Right(msg)),
+            Poll::Ready(Err(_)) => {
+                self.right_closed = true;
+                Poll::Pending
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<A: Send + 'static, B: Send + 'static> Receiver<Either<A, B>> for Select<A, B> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<Either<A, B>>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<A: Send + 'static, B: Send + 'static> Future for Select<A, B> {
+    type Output = Result<Either<A, B>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let res = if this.left_first {
+            match this.poll_left(cx) {
+                Poll::Pending => this.poll_right(cx),
+                ready => ready,
+            }
+        } else {
+            match this.poll_right(cx) {
+                Poll::Pending => this.poll_left(cx),
+                ready => ready,
+            }
+        };
+        match res {
+            Poll::Ready(msg) => {
+                // the side that did not deliver goes first next time
+                this.left_first = matches!(msg, Either::Right(_));
+                Poll::Ready(Ok(msg))
+            }
+            Poll::Pending if this.left_closed && this.right_closed => {
+                Poll::Ready(Err(BothClosed.into()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Error returned from a [`Select`](struct.Select.html) after both its receivers have been closed
+#[derive(Debug, Display, Error)]
+#[display(fmt = "cannot receive: both receivers are closed")]
+pub struct BothClosed;
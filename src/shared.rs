@@ -0,0 +1,266 @@
+use crate::{any_box, panic_message, DelayBox, FutureBox, FutureResultBox, SpawnError, Spawner};
+use parking_lot::Mutex;
+use std::{
+    any::Any,
+    cell::Cell,
+    collections::VecDeque,
+    future::{poll_fn, Future},
+    panic::{catch_unwind, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Poll, Wake, Waker},
+    time::{Duration, Instant},
+};
+
+/// Spawner that multiplexes many actors onto a small number of driver tasks
+///
+/// Spawning a runtime task per actor is wasteful when there are thousands of small actors that
+/// mostly sit idle. `SharedRuntime` instead spawns a fixed number of driver tasks on the given
+/// spawner; each actor is assigned to one of them in turn and polled by it whenever it is woken.
+///
+/// Actors take turns: a driver lets each actor process at most one message per poll, the next
+/// [`receive`](struct.Context.html#method.receive) returns `Pending` and puts the actor at the back
+/// of its driver’s run queue, so that a busy actor cannot starve the others assigned to the same
+/// driver. After one pass over its run queue the driver also yields to the underlying runtime.
+/// Since the actors of one driver run on a single task, an actor that blocks the thread or runs
+/// long computations between two `await` points holds up all of them.
+///
+/// The drivers are spawned by [`new`](#method.new), hence e.g. with the
+/// [`TokioSpawner`](tokio/struct.TokioSpawner.html) it must be called within a tokio runtime.
+/// They stop once the `SharedRuntime` and all clones are dropped and their actors have finished.
+/// Timers are taken from the underlying spawner.
+pub struct SharedRuntime<S> {
+    spawner: Arc<S>,
+    drivers: Arc<[Arc<DriverState>]>,
+    next: Arc<AtomicUsize>,
+}
+
+impl<S> Clone for SharedRuntime<S> {
+    fn clone(&self) -> Self {
+        Self {
+            spawner: self.spawner.clone(),
+            drivers: self.drivers.clone(),
+            next: self.next.clone(),
+        }
+    }
+}
+
+impl<S: Spawner> SharedRuntime<S> {
+    /// Spawn `drivers` driver tasks (at least one) on the given spawner
+    pub fn new(spawner: S, drivers: usize) -> Self {
+        let drivers = (0..drivers.max(1))
+            .map(|_| {
+                let state = Arc::new(DriverState::default());
+                let driver = Driver(state.clone());
+                // tokio’s cooperative budget is per task, i.e. it would be shared by all actors of
+                // a driver and make them yield in turn; the driver takes care of fairness instead
+                #[cfg(feature = "with_tokio")]
+                let driver = tokio::task::unconstrained(driver);
+                drop(spawner.spawn(any_box(driver)));
+                state
+            })
+            .collect();
+        Self {
+            spawner: Arc::new(spawner),
+            drivers,
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl<S: Spawner> Spawner for SharedRuntime<S> {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        let n = self.next.fetch_add(1, Ordering::Relaxed) % self.drivers.len();
+        let join = Arc::new(Mutex::new(JoinState::default()));
+        let task = Arc::new(Task {
+            fut: Mutex::new(Some(fut)),
+            queued: AtomicBool::new(false),
+            driver: self.drivers[n].clone(),
+            join: join.clone(),
+        });
+        task.wake_by_ref();
+        Box::pin(poll_fn(move |cx| {
+            let mut join = join.lock();
+            match join.result.take() {
+                Some(result) => Poll::Ready(result),
+                None => {
+                    join.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }))
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        self.spawner.delay(dur)
+    }
+
+    fn now(&self) -> Instant {
+        self.spawner.now()
+    }
+
+    fn takes_turns(&self) -> bool {
+        true
+    }
+}
+
+thread_local! {
+    // messages the currently polled actor may still receive, None outside of a driver
+    static BUDGET: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+const MESSAGES_PER_POLL: usize = 1;
+
+// called by Context::receive for each message on a SharedRuntime, Pending means that the actor must yield first
+pub(crate) fn poll_budget(cx: &mut std::task::Context<'_>) -> Poll<()> {
+    BUDGET.with(|b| match b.get() {
+        Some(0) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(n) => {
+            b.set(Some(n - 1));
+            Poll::Ready(())
+        }
+        None => Poll::Ready(()),
+    })
+}
+
+// resets the budget also when the actor panics
+struct BudgetGuard;
+
+impl BudgetGuard {
+    fn new() -> Self {
+        BUDGET.with(|b| b.set(Some(MESSAGES_PER_POLL)));
+        Self
+    }
+}
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        BUDGET.with(|b| b.set(None));
+    }
+}
+
+#[derive(Default)]
+struct DriverState {
+    inner: Mutex<DriverInner>,
+}
+
+#[derive(Default)]
+struct DriverInner {
+    queue: VecDeque<Arc<Task>>,
+    waker: Option<Waker>,
+}
+
+impl DriverState {
+    fn schedule(&self, task: Arc<Task>) {
+        let mut inner = self.inner.lock();
+        inner.queue.push_back(task);
+        if let Some(waker) = inner.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Driver(Arc<DriverState>);
+
+impl Future for Driver {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        // only the actors queued so far, those woken meanwhile wait for the next pass
+        let pass = std::mem::take(&mut self.0.inner.lock().queue);
+        for task in pass {
+            task.run();
+        }
+        let mut inner = self.0.inner.lock();
+        if !inner.queue.is_empty() {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else if Arc::strong_count(&self.0) == 1 {
+            // neither the SharedRuntime nor any actor can schedule anything anymore
+            Poll::Ready(())
+        } else {
+            inner.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+type Outcome = anyhow::Result<Box<dyn Any + Send + 'static>>;
+
+#[derive(Default)]
+struct JoinState {
+    result: Option<Outcome>,
+    waker: Option<Waker>,
+    done: bool,
+}
+
+impl JoinState {
+    fn complete(&mut self, result: Outcome) {
+        self.result = Some(result);
+        self.done = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Task {
+    fut: Mutex<Option<FutureBox>>,
+    // whether the task is in its driver’s run queue
+    queued: AtomicBool,
+    driver: Arc<DriverState>,
+    join: Arc<Mutex<JoinState>>,
+}
+
+impl Task {
+    fn run(self: Arc<Self>) {
+        let mut slot = self.fut.lock();
+        let fut = match slot.as_mut() {
+            Some(fut) => fut,
+            None => return,
+        };
+        self.queued.store(false, Ordering::SeqCst);
+        let waker = Waker::from(self.clone());
+        let mut cx = std::task::Context::from_waker(&waker);
+        let result = {
+            let _budget = BudgetGuard::new();
+            catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(&mut cx)))
+        };
+        let outcome = match result {
+            Ok(Poll::Pending) => return,
+            Ok(Poll::Ready(value)) => Ok(value),
+            Err(payload) => Err(SpawnError::Panicked(panic_message(&*payload)).into()),
+        };
+        *slot = None;
+        drop(slot);
+        self.join.lock().complete(outcome);
+    }
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.queued.swap(true, Ordering::SeqCst) {
+            self.driver.schedule(self.clone());
+        }
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        // the driver went away before the actor finished
+        let mut join = self.join.lock();
+        if !join.done {
+            join.complete(Err(SpawnError::Cancelled.into()));
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use derive_more::{Display, Error};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Poll, Waker},
+};
+
+/// Cooperative shutdown signal for a group of actors
+///
+/// Hand a clone of the token to each actor’s [`Context`](struct.Context.html) with
+/// [`set_shutdown_token`](struct.Context.html#method.set_shutdown_token); child contexts created
+/// with `inherit` take it over. Once [`trigger`](#method.trigger) has been called, `receive()`
+/// fails with [`Shutdown`](struct.Shutdown.html) in all these actors, which gives them the chance
+/// to run their teardown before exiting.
+#[derive(Clone, Default)]
+pub struct ShutdownToken(Arc<ShutdownInner>);
+
+#[derive(Default)]
+struct ShutdownInner {
+    triggered: AtomicBool,
+    next_id: AtomicU64,
+    wakers: Mutex<HashMap<u64, Waker>>,
+}
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signal shutdown to all actors holding this token
+    pub fn trigger(&self) {
+        self.0.triggered.store(true, Ordering::SeqCst);
+        let wakers = std::mem::take(&mut *self.0.wakers.lock());
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.0.triggered.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn listener(&self) -> ShutdownListener {
+        ShutdownListener {
+            token: self.clone(),
+            id: self.0.next_id.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+// one registration per Context, so that waiting actors can be woken and finished ones forgotten
+pub(crate) struct ShutdownListener {
+    token: ShutdownToken,
+    id: u64,
+}
+
+impl ShutdownListener {
+    pub(crate) fn token(&self) -> &ShutdownToken {
+        &self.token
+    }
+
+    pub(crate) fn poll(&self, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        if self.token.is_triggered() {
+            return Poll::Ready(());
+        }
+        self.token
+            .0
+            .wakers
+            .lock()
+            .insert(self.id, cx.waker().clone());
+        // in case the token was triggered between the check and installing the waker
+        if self.token.is_triggered() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for ShutdownListener {
+    fn drop(&mut self) {
+        self.token.0.wakers.lock().remove(&self.id);
+    }
+}
+
+/// Error returned from `receive()` after the actor’s [`ShutdownToken`](struct.ShutdownToken.html) was triggered
+#[derive(Debug, Display, Error)]
+#[display(fmt = "cannot receive: actor is shutting down")]
+pub struct Shutdown;
@@ -0,0 +1,80 @@
+use crate::{ActorRef, Permit, ReserveBox, SendError};
+use futures_sink::Sink;
+use std::{pin::Pin, task::Poll};
+
+/// An [`ActorRef`](struct.ActorRef.html) turned into a [`Sink`](https://docs.rs/futures/0.3/futures/sink/trait.Sink.html)
+///
+/// For bounded mailboxes `poll_ready` waits until there is room in the queue, for unbounded ones it
+/// is always ready. Closing the sink drops the `ActorRef`, so that the actor sees
+/// [`NoActorRef`](struct.NoActorRef.html) if it was the last one.
+pub struct ActorSink<M> {
+    aref: Option<ActorRef<M>>,
+    reserve: Option<ReserveBox<M>>,
+    permit: Option<Permit<M>>,
+}
+
+impl<M> ActorSink<M> {
+    pub(crate) fn new(aref: ActorRef<M>) -> Self {
+        Self {
+            aref: Some(aref),
+            reserve: None,
+            permit: None,
+        }
+    }
+}
+
+impl<M: 'static> Sink<M> for ActorSink<M> {
+    type Error = SendError<M>;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        if self.permit.is_some() {
+            return Poll::Ready(Ok(()));
+        }
+        if self.reserve.is_none() {
+            match self.aref.as_ref().and_then(|aref| aref.reserve()) {
+                Some(reserve) => self.reserve = Some(reserve),
+                // unbounded or closed, either way start_send will tell
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+        let permit = match self.reserve.as_mut().unwrap().as_mut().poll(cx) {
+            Poll::Ready(permit) => permit,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.reserve = None;
+        // without a permit the mailbox is closed, which start_send will report
+        self.permit = permit;
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: M) -> Result<(), Self::Error> {
+        if let Some(permit) = self.permit.take() {
+            permit(item);
+            return Ok(());
+        }
+        match &self.aref {
+            Some(aref) => aref.try_tell(item),
+            None => Err(SendError(item)),
+        }
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.permit = None;
+        self.reserve = None;
+        self.aref = None;
+        Poll::Ready(Ok(()))
+    }
+}
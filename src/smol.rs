@@ -0,0 +1,191 @@
+use crate::{
+    panic_message, DelayBox, FutureBox, FutureResultBox, Mailbox, NoActorRef, Receiver, Sender,
+    SpawnError, Spawner,
+};
+use futures::{FutureExt, StreamExt};
+use smol::channel;
+use std::{future::Future, panic::AssertUnwindSafe, pin::Pin, task::Poll, time::Duration};
+
+/// Spawner that uses the global smol executor
+///
+/// The spawned `Task` is awaited by the join handle, a panic in the actor is surfaced as an error.
+pub struct SmolSpawner;
+
+impl Spawner for SmolSpawner {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        let task = DetachOnDrop(Some(smol::spawn(AssertUnwindSafe(fut).catch_unwind())));
+        Box::pin(async move {
+            match task.await {
+                Ok(result) => Ok(result),
+                Err(payload) => Err(SpawnError::Panicked(panic_message(&*payload)).into()),
+            }
+        })
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        let timer = smol::Timer::after(dur);
+        Box::pin(async move {
+            timer.await;
+        })
+    }
+}
+
+// dropping a smol Task cancels it, but dropping the join handle shall keep the actor running
+struct DetachOnDrop<T>(Option<smol::Task<T>>);
+
+impl<T> Future for DetachOnDrop<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        Pin::new(self.0.as_mut().unwrap()).poll(cx)
+    }
+}
+
+impl<T> Drop for DetachOnDrop<T> {
+    fn drop(&mut self) {
+        if let Some(task) = self.0.take() {
+            task.detach();
+        }
+    }
+}
+
+pub struct SmolMailbox;
+
+impl Mailbox for SmolMailbox {
+    fn make_mailbox<M: Send + 'static>(&self) -> (super::ActorRef<M>, Box<dyn Receiver<M>>) {
+        let (tx, rx) = channel::unbounded::<M>();
+        let aref = super::ActorRef::new(Box::new(SmolSender(tx)));
+        (aref, Box::new(SmolReceiver(Box::pin(rx))))
+    }
+}
+
+struct SmolSender<M>(channel::Sender<M>);
+
+impl<M: Send> Sender<M> for SmolSender<M> {
+    fn tell(&self, msg: M) -> Result<(), M> {
+        self.0.try_send(msg).map_err(|e| e.into_inner())
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+// the async-channel receiver is not Unpin, hence the extra box
+pub struct SmolReceiver<M>(Pin<Box<channel::Receiver<M>>>);
+
+impl<M: Send + 'static> super::Receiver<M> for SmolReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = anyhow::Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M: Send + 'static> Future for SmolReceiver<M> {
+    type Output = anyhow::Result<M>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        match self.as_mut().0.poll_next_unpin(cx) {
+            Poll::Ready(Some(msg)) => Poll::Ready(Ok(msg)),
+            Poll::Ready(None) => Poll::Ready(Err(NoActorRef.into())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActorRef, Context, NoActorRef};
+    use anyhow::Result;
+    use futures::{channel::oneshot, poll};
+    use smol::block_on;
+    use std::{task::Poll, thread::sleep, time::Duration};
+
+    async fn actor(mut ctx: Context<(String, ActorRef<String>)>) -> Result<()> {
+        loop {
+            let (name, sender) = ctx.receive().await?;
+            let (responder, handle) = actor!(SmolMailbox, |ctx| {
+                let m = ctx.receive().await?;
+                sender.tell(format!("Hello {}!", m));
+                Ok(())
+            });
+            responder.tell(name);
+            let _ = handle.await;
+        }
+    }
+
+    #[test]
+    fn smoke() {
+        block_on(async {
+            let (aref, join_handle) = actor!(SmolMailbox, SmolSpawner, fn actor(ctx));
+
+            let (tx, rx) = oneshot::channel();
+            let (receiver, jr) = actor!(SmolMailbox, SmolSpawner, |ctx| {
+                let msg = ctx.receive().await?;
+                let _ = tx.send(msg);
+                Ok("buh")
+            });
+            aref.tell(("Fred".to_owned(), receiver));
+            assert_eq!(rx.await.unwrap(), "Hello Fred!");
+            assert_eq!(jr.await.unwrap().unwrap(), "buh");
+
+            drop(aref);
+            join_handle
+                .await
+                .unwrap()
+                .unwrap_err()
+                .downcast::<NoActorRef>()
+                .unwrap();
+        })
+    }
+
+    #[test]
+    fn dropped() {
+        block_on(async {
+            let (tx, mut rx) = oneshot::channel();
+            let (aref, handle) = actor!(SmolMailbox, SmolSpawner, |ctx| {
+                let result: Result<()> = ctx.receive().await;
+                let _ = tx.send(result);
+                Ok(())
+            });
+
+            sleep(Duration::from_millis(200));
+            match poll!(&mut rx) {
+                Poll::Pending => {}
+                x => panic!("unexpected result: {:?}", x),
+            }
+
+            drop(aref);
+            handle.await.unwrap().unwrap();
+            let err = match poll!(rx) {
+                Poll::Ready(Ok(e)) => e.unwrap_err(),
+                x => panic!("unexpected poll result: {:?}", x),
+            };
+            err.downcast::<NoActorRef>()
+                .unwrap_or_else(|e| panic!("unexpected error type: {}", e));
+        })
+    }
+
+    #[test]
+    fn panicked() {
+        block_on(async {
+            let (aref, handle) = actor!(SmolMailbox, SmolSpawner, |ctx| {
+                let msg: u32 = ctx.receive().await?;
+                if msg == 0 {
+                    panic!("boom");
+                }
+                Ok(msg)
+            });
+            aref.tell(0);
+            let err = handle.await.unwrap_err();
+            assert_eq!(err.to_string(), "actor task panicked: boom");
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use crate::ActorRef;
+use tracing::Span;
+
+/// A message together with the [`Span`](https://docs.rs/tracing/0.1/tracing/struct.Span.html) it was sent in
+///
+/// An actor receiving `WithSpan<M>` messages can enter the span while processing each message,
+/// which correlates its logs with those of the sender. Senders obtain an `ActorRef<M>` that
+/// attaches the current span to each message with [`ActorRef::with_current_span`](struct.ActorRef.html#method.with_current_span).
+#[derive(Debug, Clone)]
+pub struct WithSpan<M> {
+    pub span: Span,
+    pub msg: M,
+}
+
+impl<M> WithSpan<M> {
+    /// Wrap the message together with the current span
+    pub fn new(msg: M) -> Self {
+        Self {
+            span: Span::current(),
+            msg,
+        }
+    }
+
+    /// Run `f` with the message while the span is entered
+    ///
+    /// For asynchronous processing use `tracing::Instrument` with the `span` instead.
+    pub fn in_span<R>(self, f: impl FnOnce(M) -> R) -> R {
+        let Self { span, msg } = self;
+        span.in_scope(|| f(msg))
+    }
+}
+
+impl<M: Send + 'static> ActorRef<WithSpan<M>> {
+    /// Create an `ActorRef` that wraps each message in the span current at the time of sending
+    ///
+    /// This is a [`contramap`](#method.contramap), so the same caveats apply.
+    pub fn with_current_span(self) -> ActorRef<M> {
+        self.contramap(WithSpan::new)
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{ActorRef, MailboxFor, NoActorRef, Receiver, Sender};
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{Read, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::PathBuf,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Poll, Waker},
+};
+
+/// Mailbox that spills messages to disk when more than `mem_capacity` of them are queued
+///
+/// The first `mem_capacity` waiting messages are kept in memory, all further ones are appended to
+/// a file in `spill_dir` (created if needed) in bincode format. Once messages have been spilled,
+/// new ones are spilled as well until the actor has caught up, so that it receives all messages in
+/// the order they were sent. The spilled messages are read back in batches of up to
+/// `mem_capacity` when the in-memory queue runs empty; the file is truncated whenever it has been
+/// drained and removed as soon as the actor stops.
+///
+/// The file is written and read synchronously, within `tell` and `receive`. A message that cannot
+/// be spilled is rejected, i.e. it goes to the dead letters if the `ActorRef` has them; a spilled
+/// message that cannot be read back fails the actor’s `receive()`.
+pub struct SpillMailbox<M> {
+    pub mem_capacity: usize,
+    pub spill_dir: PathBuf,
+    _message: PhantomData<fn(M)>,
+}
+
+impl<M> SpillMailbox<M> {
+    pub fn new(mem_capacity: usize, spill_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            mem_capacity,
+            spill_dir: spill_dir.into(),
+            _message: PhantomData,
+        }
+    }
+}
+
+// distinguishes the spill files of several mailboxes within one process
+static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+
+impl<M> MailboxFor<M> for SpillMailbox<M>
+where
+    M: Serialize + DeserializeOwned + Send + 'static,
+{
+    fn make_mailbox_for(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let path = self.spill_dir.join(format!(
+            "acto-spill-{}-{}.bin",
+            std::process::id(),
+            NEXT_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let queue = Arc::new(Mutex::new(Queue {
+            mem: VecDeque::new(),
+            mem_capacity: self.mem_capacity.max(1),
+            spill: SpillFile {
+                path,
+                file: None,
+                index: VecDeque::new(),
+            },
+            waker: None,
+            closed: false,
+            senders_gone: false,
+        }));
+        (
+            ActorRef::new(Box::new(SpillSender(queue.clone()))),
+            Box::new(SpillReceiver(queue)),
+        )
+    }
+}
+
+struct Queue<M> {
+    mem: VecDeque<M>,
+    mem_capacity: usize,
+    spill: SpillFile,
+    waker: Option<Waker>,
+    closed: bool,
+    senders_gone: bool,
+}
+
+impl<M> Queue<M> {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+// append-only file of spilled messages, the index holds offset and length of the unread ones
+struct SpillFile {
+    path: PathBuf,
+    file: Option<File>,
+    index: VecDeque<(u64, u64)>,
+}
+
+impl SpillFile {
+    fn append(&mut self, bytes: &[u8]) -> Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                if let Some(dir) = self.path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                let file = OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&self.path)?;
+                self.file.insert(file)
+            }
+        };
+        let offset = file.seek(SeekFrom::End(0))?;
+        file.write_all(bytes)?;
+        self.index.push_back((offset, bytes.len() as u64));
+        Ok(())
+    }
+
+    fn read_next(&mut self) -> Result<Option<Vec<u8>>> {
+        let (file, (offset, len)) = match (&mut self.file, self.index.pop_front()) {
+            (Some(file), Some(entry)) => (file, entry),
+            _ => return Ok(None),
+        };
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; len as usize];
+        file.read_exact(&mut bytes)?;
+        if self.index.is_empty() {
+            // everything has been read back, start over to keep the file small
+            file.set_len(0)?;
+        }
+        Ok(Some(bytes))
+    }
+}
+
+impl SpillFile {
+    fn remove(&mut self) {
+        self.index.clear();
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+struct SpillSender<M>(Arc<Mutex<Queue<M>>>);
+
+impl<M: Serialize + Send> Sender<M> for SpillSender<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        let mut queue = self.0.lock();
+        if queue.closed {
+            return Err(msg);
+        }
+        if queue.spill.index.is_empty() && queue.mem.len() < queue.mem_capacity {
+            queue.mem.push_back(msg);
+        } else {
+            let spilled = bincode::serialize(&msg)
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| queue.spill.append(&bytes));
+            if spilled.is_err() {
+                return Err(msg);
+            }
+        }
+        queue.wake();
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        let queue = self.0.lock();
+        queue.mem.len() + queue.spill.index.len()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.lock().closed
+    }
+}
+
+impl<M> Drop for SpillSender<M> {
+    fn drop(&mut self) {
+        let mut queue = self.0.lock();
+        queue.senders_gone = true;
+        queue.wake();
+    }
+}
+
+struct SpillReceiver<M>(Arc<Mutex<Queue<M>>>);
+
+impl<M> Drop for SpillReceiver<M> {
+    fn drop(&mut self) {
+        let mut queue = self.0.lock();
+        queue.closed = true;
+        queue.mem.clear();
+        // remaining ActorRefs keep the queue alive, but nothing is read anymore
+        queue.spill.remove();
+    }
+}
+
+impl<M: DeserializeOwned + Send + 'static> Receiver<M> for SpillReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M: DeserializeOwned> Future for SpillReceiver<M> {
+    type Output = Result<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut queue = self.0.lock();
+        if queue.mem.is_empty() {
+            // drain back into memory as far as it goes
+            while queue.mem.len() < queue.mem_capacity {
+                match queue.spill.read_next()? {
+                    Some(bytes) => {
+                        let msg = bincode::deserialize(&bytes)?;
+                        queue.mem.push_back(msg);
+                    }
+                    None => break,
+                }
+            }
+        }
+        if let Some(msg) = queue.mem.pop_front() {
+            return Poll::Ready(Ok(msg));
+        }
+        if queue.senders_gone {
+            return Poll::Ready(Err(NoActorRef.into()));
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
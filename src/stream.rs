@@ -0,0 +1,48 @@
+use crate::Receiver;
+use futures_core::{FusedStream, Stream};
+use std::{pin::Pin, task::Poll};
+
+/// Adapter for consuming a mailbox’s receiving side as a [`Stream`](https://docs.rs/futures/0.3/futures/stream/trait.Stream.html)
+///
+/// The stream ends when the mailbox is closed (i.e. when the receiver would return an error),
+/// which happens once all `ActorRef`s for it have been dropped.
+pub struct ReceiverStream<M: Send + 'static> {
+    recv: Box<dyn Receiver<M>>,
+    done: bool,
+}
+
+impl<M: Send + 'static> ReceiverStream<M> {
+    pub fn new(recv: Box<dyn Receiver<M>>) -> Self {
+        Self { recv, done: false }
+    }
+}
+
+impl<M: Send + 'static> From<Box<dyn Receiver<M>>> for ReceiverStream<M> {
+    fn from(recv: Box<dyn Receiver<M>>) -> Self {
+        Self::new(recv)
+    }
+}
+
+impl<M: Send + 'static> Stream for ReceiverStream<M> {
+    type Item = M;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Option<M>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match Pin::new(self.recv.receive()).poll(cx) {
+            Poll::Ready(Ok(msg)) => Poll::Ready(Some(msg)),
+            Poll::Ready(Err(_)) => {
+                self.done = true;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<M: Send + 'static> FusedStream for ReceiverStream<M> {
+    fn is_terminated(&self) -> bool {
+        self.done
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    spawn, ActorRef, Context, MailboxFor, NoActorRef, Receiver, Shutdown, ShutdownToken,
+    SpawnError, Spawner,
+};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// When to restart a failed actor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartStrategy {
+    /// Let the actor stop on the first failure
+    Never,
+    /// Restart the actor after every failure
+    Always,
+    /// Restart the actor unless it has already been restarted `n` times within the given duration
+    UpTo(usize, Duration),
+}
+
+/// Spawner wrapper that restarts actors when they fail
+///
+/// An actor fails when its future panics or returns an error other than
+/// [`NoActorRef`](struct.NoActorRef.html) or [`Shutdown`](struct.Shutdown.html): the former means
+/// that nobody can send messages to it anymore, the latter that it was asked to stop, so both are
+/// treated as a normal stop.
+///
+/// Actors whose task was cancelled ([`SpawnError::Cancelled`](enum.SpawnError.html)) are not
+/// restarted either, while panics count as failures.
+///
+/// The actor’s mailbox is created once and handed to every incarnation, hence the `ActorRef`
+/// returned from [`spawn`](#method.spawn) stays valid across restarts. Messages that are queued
+/// while the actor is restarted are not lost, but the message being processed during the failure is.
+pub struct Supervised<S> {
+    spawner: Arc<S>,
+    strategy: RestartStrategy,
+}
+
+impl<S: Spawner> Supervised<S> {
+    pub fn new(spawner: S, strategy: RestartStrategy) -> Self {
+        Self {
+            spawner: Arc::new(spawner),
+            strategy,
+        }
+    }
+
+    /// Spawn a supervised actor, using `factory` to create its future for every incarnation
+    ///
+    /// Like the join handle returned from the [`actor`](macro.actor.html) macro, the returned future
+    /// yields the last incarnation’s result once the actor is no longer restarted.
+    pub fn spawn<M, MB, F, Fut, T>(
+        &self,
+        mailbox: MB,
+        factory: F,
+    ) -> (
+        ActorRef<M>,
+        impl Future<Output = Result<Result<T>>> + Send + 'static,
+    )
+    where
+        M: Send + 'static,
+        MB: MailboxFor<M>,
+        F: Fn(Context<M>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (aref, recv) = mailbox.make_mailbox_for();
+        let recv = Arc::new(Mutex::new(recv));
+        let spawner = self.spawner.clone();
+        let mut restarts = Restarts::new(self.strategy);
+        let inner = aref.inner();
+        let supervisor = async move {
+            loop {
+                let recv = Box::new(SharedReceiver(recv.clone()));
+                let ctx = Context::from_parts(inner.clone(), recv, spawner.clone());
+                let result = match spawn(&*spawner, factory(ctx)).await {
+                    Ok(result) => result,
+                    Err(panicked) => Err(panicked),
+                };
+                match result {
+                    Ok(value) => return Ok(value),
+                    Err(err) if err.is::<NoActorRef>() || err.is::<Shutdown>() => return Err(err),
+                    // deliberately stopped from the outside
+                    Err(err) if err.downcast_ref() == Some(&SpawnError::Cancelled) => {
+                        return Err(err)
+                    }
+                    Err(err) => {
+                        if !restarts.allow() {
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+        };
+        (aref, spawn(&*self.spawner, supervisor))
+    }
+}
+
+/// What to do about a failed child actor, see [`Context::spawn_supervised`](struct.Context.html#method.spawn_supervised)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive {
+    /// Start a new incarnation of the child with the same mailbox
+    Restart,
+    /// Let the child stay stopped
+    Stop,
+    /// Stop the child and fail the parent’s next `receive()` with the child’s error
+    Escalate,
+}
+
+pub(crate) type SupervisorFn = Arc<Mutex<Box<dyn FnMut(&anyhow::Error) -> Directive + Send>>>;
+
+// child failures handed up to the parent, to be returned from its next receive()
+#[derive(Clone, Default)]
+pub(crate) struct Escalation(Arc<Mutex<EscalationState>>);
+
+#[derive(Default)]
+struct EscalationState {
+    errors: VecDeque<anyhow::Error>,
+    waker: Option<Waker>,
+}
+
+impl Escalation {
+    fn escalate(&self, err: anyhow::Error) {
+        let mut state = self.0.lock();
+        state.errors.push_back(err);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        !self.0.lock().errors.is_empty()
+    }
+
+    pub(crate) fn poll(&self, cx: &mut std::task::Context<'_>) -> Poll<anyhow::Error> {
+        let mut state = self.0.lock();
+        match state.errors.pop_front() {
+            Some(err) => Poll::Ready(err),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// runs the incarnations of a child actor, consulting the parent’s supervisor on failures
+pub(crate) fn spawn_supervised_child<C, MB, F, Fut, T>(
+    mailbox: MB,
+    spawner: Arc<dyn Spawner>,
+    token: ShutdownToken,
+    supervisor: Option<SupervisorFn>,
+    escalation: Escalation,
+    factory: F,
+) -> ActorRef<C>
+where
+    C: Send + 'static,
+    MB: MailboxFor<C>,
+    F: Fn(Context<C>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let (aref, recv) = mailbox.make_mailbox_for();
+    let recv = Arc::new(Mutex::new(recv));
+    let inner = aref.inner();
+    let id = aref.id();
+    let task_spawner = spawner.clone();
+    let supervisor_task = async move {
+        loop {
+            let recv = Box::new(SharedReceiver(recv.clone()));
+            let mut ctx = Context::from_parts(inner.clone(), recv, spawner.clone());
+            ctx.set_shutdown_token(token.clone());
+            let err = match spawn(&*spawner, factory(ctx)).await {
+                Ok(Ok(_)) => return,
+                Ok(Err(err)) | Err(err) => err,
+            };
+            if err.is::<NoActorRef>()
+                || err.is::<Shutdown>()
+                || err.downcast_ref() == Some(&SpawnError::Cancelled)
+            {
+                return;
+            }
+            let directive = match &supervisor {
+                Some(supervisor) => (supervisor.lock())(&err),
+                None => Directive::Escalate,
+            };
+            match directive {
+                Directive::Restart => {}
+                Directive::Stop => return,
+                Directive::Escalate => {
+                    escalation.escalate(err.context(format!("child actor {} failed", id)));
+                    return;
+                }
+            }
+        }
+    };
+    drop(spawn(&*task_spawner, supervisor_task));
+    aref
+}
+
+struct Restarts {
+    strategy: RestartStrategy,
+    history: VecDeque<Instant>,
+}
+
+impl Restarts {
+    fn new(strategy: RestartStrategy) -> Self {
+        Self {
+            strategy,
+            history: VecDeque::new(),
+        }
+    }
+
+    fn allow(&mut self) -> bool {
+        match self.strategy {
+            RestartStrategy::Never => false,
+            RestartStrategy::Always => true,
+            RestartStrategy::UpTo(n, within) => {
+                let now = Instant::now();
+                while self.history.front().is_some_and(|t| now - *t > within) {
+                    self.history.pop_front();
+                }
+                if self.history.len() < n {
+                    self.history.push_back(now);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+// gives each incarnation access to the one mailbox
+struct SharedReceiver<M>(Arc<Mutex<Box<dyn Receiver<M>>>>);
+
+impl<M: Send + 'static> Receiver<M> for SharedReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.0.lock().set_name(name)
+    }
+}
+
+impl<M: Send + 'static> Future for SharedReceiver<M> {
+    type Output = Result<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut recv = self.0.lock();
+        Pin::new(recv.receive()).poll(cx)
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    spawn, ActorHandle, ActorRef, BoxedSpawner, Context, Mailbox, MailboxFactory, ShutdownToken,
+    Spawner,
+};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    future::{poll_fn, Future},
+    pin::Pin,
+    sync::Arc,
+    task::Poll,
+    time::Duration,
+};
+
+/// A set of actors that are started and stopped together
+///
+/// All actors are created with the system’s mailbox factory and spawner and share one
+/// [`ShutdownToken`](struct.ShutdownToken.html). [`shutdown`](#method.shutdown) first
+/// [closes](struct.ActorRef.html#method.close) all their mailboxes, so that each actor still
+/// processes the messages queued so far before its `receive()` fails with
+/// [`NoActorRef`](struct.NoActorRef.html), and waits for them to finish. When the timeout
+/// elapses it triggers the token, which makes `receive()` fail with
+/// [`Shutdown`](struct.Shutdown.html), and gives the remaining actors another timeout before
+/// they are [aborted](struct.ActorHandle.html#method.abort).
+///
+/// By default the system stores its mailbox and spawner as trait objects, so that they can be
+/// chosen at runtime, see [`MailboxFactory`](struct.MailboxFactory.html).
+pub struct ActorSystem<MB = MailboxFactory, S = BoxedSpawner> {
+    mailbox: MB,
+    spawner: Arc<S>,
+    token: ShutdownToken,
+    timeout: Duration,
+    actors: Mutex<Vec<Member>>,
+}
+
+struct Member {
+    handle: ActorHandle<()>,
+    // closes the actor’s mailbox without keeping it alive
+    close: Box<dyn Fn() + Send + Sync>,
+}
+
+/// What happened during [`ActorSystem::shutdown`](struct.ActorSystem.html#method.shutdown)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShutdownReport {
+    /// The number of actors that finished on their own, successfully or not
+    pub stopped: usize,
+    /// The number of actors that were aborted after the second timeout
+    pub aborted: usize,
+}
+
+impl<MB: Mailbox, S: Spawner> ActorSystem<MB, S> {
+    /// Create an empty system whose `shutdown` waits up to `timeout` for the actors to drain their
+    /// mailboxes and finish, and up to `timeout` again after triggering the token
+    pub fn new(mailbox: MB, spawner: S, timeout: Duration) -> Self {
+        Self {
+            mailbox,
+            spawner: Arc::new(spawner),
+            token: ShutdownToken::new(),
+            timeout,
+            actors: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The token that is triggered by [`shutdown`](#method.shutdown)
+    pub fn shutdown_token(&self) -> &ShutdownToken {
+        &self.token
+    }
+
+    /// Spawn an actor that belongs to this system
+    ///
+    /// The actor’s result is not reported, only whether it finished in time during shutdown.
+    /// Actors spawned after `shutdown` has been called see the triggered token right away.
+    pub fn spawn<M, F, Fut, T>(&self, body: F) -> ActorRef<M>
+    where
+        M: Send + 'static,
+        F: FnOnce(Context<M>) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (aref, recv) = self.mailbox.make_mailbox();
+        let mut ctx = Context::from_parts(aref.inner(), recv, self.spawner.clone());
+        ctx.set_shutdown_token(self.token.clone());
+        let fut = body(ctx);
+        let handle = spawn(&*self.spawner, async move {
+            let _ = fut.await;
+        });
+        let weak = aref.downgrade();
+        let close = Box::new(move || {
+            if let Some(aref) = weak.upgrade() {
+                aref.close();
+            }
+        });
+        let mut actors = self.actors.lock();
+        actors.retain(|a| !a.handle.is_finished());
+        actors.push(Member { handle, close });
+        aref
+    }
+
+    /// Stop all actors of this system, see above
+    pub async fn shutdown(&self) -> ShutdownReport {
+        let mut actors = std::mem::take(&mut *self.actors.lock());
+        for actor in &actors {
+            (actor.close)();
+        }
+        let mut report = ShutdownReport::default();
+        self.wait(&mut actors, &mut report).await;
+        // also for actors spawned from now on
+        self.token.trigger();
+        if !actors.is_empty() {
+            self.wait(&mut actors, &mut report).await;
+        }
+        for actor in actors {
+            actor.handle.abort();
+            report.aborted += 1;
+        }
+        report
+    }
+
+    // wait up to the timeout, removing the actors that finished
+    async fn wait(&self, actors: &mut Vec<Member>, report: &mut ShutdownReport) {
+        let mut delay = self.spawner.delay(self.timeout);
+        poll_fn(|cx| {
+            actors.retain_mut(|a| {
+                let done = Pin::new(&mut a.handle).poll(cx).is_ready();
+                report.stopped += done as usize;
+                !done
+            });
+            if actors.is_empty() {
+                Poll::Ready(())
+            } else {
+                delay.as_mut().poll(cx)
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,531 @@
+//! Utilities for testing actors deterministically
+//!
+//! The [`TestSpawner`](struct.TestSpawner.html) runs actors only when asked to, so a test can
+//! send messages, pump the executor with [`run_until_stalled`](struct.TestSpawner.html#method.run_until_stalled),
+//! and then make its assertions, without relying on real time passing:
+//!
+//! ```
+//! use acto::{actor, testing::{TestMailbox, TestSpawner}, Result};
+//!
+//! let spawner = TestSpawner::new();
+//! let (aref, handle) = actor!(TestMailbox, spawner.clone(), |ctx| {
+//!     let msg: u32 = ctx.receive().await?;
+//!     Ok(msg + 1)
+//! });
+//! aref.tell(41);
+//! let result = spawner.run_until(handle).expect("actor should have finished");
+//! assert_eq!(result.unwrap().unwrap(), 42);
+//! ```
+//!
+//! A [`Probe`](struct.Probe.html) stands in for an actor whose incoming messages the test wants
+//! to check, see [`probe`](fn.probe.html).
+
+use crate::{
+    actor::thread_delay, ActorRef, Clock, DelayBox, FutureBox, FutureResultBox, Mailbox, Receiver,
+    Sender, Spawner,
+};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    future::{poll_fn, Future},
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Poll, Wake, Waker},
+    time::{Duration, Instant},
+};
+
+type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+type Output = Box<dyn Any + Send + 'static>;
+
+/// Spawner backed by an executor that only runs when told to
+///
+/// All clones share the same executor. Spawned actors do not make progress until
+/// [`run_until_stalled`](#method.run_until_stalled) or [`run_until`](#method.run_until) is
+/// called, which poll them on the calling thread. A panicking actor makes these methods panic.
+#[derive(Clone, Default)]
+pub struct TestSpawner(Arc<Executor>);
+
+#[derive(Default)]
+struct Executor {
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<u64, Task>>,
+    ready: Arc<Mutex<VecDeque<u64>>>,
+}
+
+struct TaskWaker {
+    id: u64,
+    ready: Arc<Mutex<VecDeque<u64>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        let mut ready = self.ready.lock();
+        if !ready.contains(&self.id) {
+            ready.push_back(self.id);
+        }
+    }
+}
+
+#[derive(Default)]
+struct JoinState {
+    result: Option<Output>,
+    waker: Option<Waker>,
+}
+
+impl TestSpawner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Poll all actors until none of them can make progress, returning whether any of them did
+    pub fn run_until_stalled(&self) -> bool {
+        let mut progress = false;
+        loop {
+            let id = match self.0.ready.lock().pop_front() {
+                Some(id) => id,
+                None => return progress,
+            };
+            // the task is taken out while polling, so that it can spawn further tasks
+            let mut task = match self.0.tasks.lock().remove(&id) {
+                Some(task) => task,
+                None => continue,
+            };
+            progress = true;
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                ready: self.0.ready.clone(),
+            }));
+            if task
+                .as_mut()
+                .poll(&mut std::task::Context::from_waker(&waker))
+                .is_pending()
+            {
+                self.0.tasks.lock().insert(id, task);
+            }
+        }
+    }
+
+    /// Run the actors until the given future completes, or `None` if everything stalls before that
+    pub fn run_until<F: Future>(&self, fut: F) -> Option<F::Output> {
+        let woken = Arc::new(Flag(AtomicBool::new(true)));
+        let waker = Waker::from(woken.clone());
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut fut = pin!(fut);
+        loop {
+            if woken.0.swap(false, Ordering::SeqCst) {
+                if let Poll::Ready(result) = fut.as_mut().poll(&mut cx) {
+                    return Some(result);
+                }
+            }
+            if !self.run_until_stalled() && !woken.0.load(Ordering::SeqCst) {
+                return None;
+            }
+        }
+    }
+
+    /// The number of actors that have not yet finished
+    pub fn task_count(&self) -> usize {
+        self.0.tasks.lock().len()
+    }
+}
+
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Spawner for TestSpawner {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        let state = Arc::new(Mutex::new(JoinState::default()));
+        let join = state.clone();
+        let task = Box::pin(async move {
+            let result = fut.await;
+            let mut state = state.lock();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        let id = self.0.next_id.fetch_add(1, Ordering::Relaxed);
+        self.0.tasks.lock().insert(id, task);
+        self.0.ready.lock().push_back(id);
+        Box::pin(poll_fn(move |cx| {
+            let mut state = join.lock();
+            match state.result.take() {
+                Some(result) => Poll::Ready(Ok(result)),
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }))
+    }
+}
+
+/// Mailbox that works with any executor, in particular the [`TestSpawner`](struct.TestSpawner.html)
+///
+/// Sent messages are immediately available to the actor, which is woken up.
+pub struct TestMailbox;
+
+impl Mailbox for TestMailbox {
+    fn make_mailbox<M: Send + 'static>(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let queue = Arc::new(Mutex::new(Queue {
+            msgs: VecDeque::new(),
+            waker: None,
+            closed: false,
+        }));
+        let aref = ActorRef::new(Box::new(TestSender(queue.clone())));
+        (aref, Box::new(TestReceiver(queue)))
+    }
+}
+
+struct Queue<M> {
+    msgs: VecDeque<M>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+struct TestSender<M>(Arc<Mutex<Queue<M>>>);
+
+impl<M: Send> Sender<M> for TestSender<M> {
+    fn tell(&self, msg: M) -> std::result::Result<(), M> {
+        let mut queue = self.0.lock();
+        if queue.closed {
+            return Err(msg);
+        }
+        queue.msgs.push_back(msg);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.0.lock().msgs.len()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.lock().closed
+    }
+}
+
+struct TestReceiver<M>(Arc<Mutex<Queue<M>>>);
+
+impl<M> Drop for TestReceiver<M> {
+    fn drop(&mut self) {
+        self.0.lock().closed = true;
+    }
+}
+
+impl<M: Send + 'static> Receiver<M> for TestReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M: Send + 'static> Future for TestReceiver<M> {
+    type Output = Result<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut queue = self.0.lock();
+        match queue.msgs.pop_front() {
+            Some(msg) => Poll::Ready(Ok(msg)),
+            None => {
+                queue.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Clock that only advances when told to
+///
+/// Use it with [`WithClock`](../struct.WithClock.html) to control the timers of actors, e.g.
+/// those of [`Context::receive_timeout`](../struct.Context.html#method.receive_timeout). All
+/// clones share the same time.
+#[derive(Clone)]
+pub struct TestClock(Arc<Mutex<ClockState>>);
+
+struct ClockState {
+    now: Instant,
+    next_id: u64,
+    // deadline and waker of pending delays
+    timers: HashMap<u64, (Instant, Waker)>,
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(ClockState {
+            now: Instant::now(),
+            next_id: 0,
+            timers: HashMap::new(),
+        })))
+    }
+}
+
+impl TestClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move time forward, waking up all delays that are due by then
+    ///
+    /// The woken actors run during the next [`run_until_stalled`](struct.TestSpawner.html#method.run_until_stalled).
+    pub fn advance(&self, dur: Duration) {
+        let mut state = self.0.lock();
+        state.now += dur;
+        let now = state.now;
+        let due = state
+            .timers
+            .iter()
+            .filter(|(_, (deadline, _))| *deadline <= now)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in due {
+            if let Some((_, waker)) = state.timers.remove(&id) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> Instant {
+        self.0.lock().now
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        let mut state = self.0.lock();
+        let deadline = state.now + dur;
+        let id = state.next_id;
+        state.next_id += 1;
+        Box::pin(TestDelay {
+            clock: self.clone(),
+            id,
+            deadline,
+        })
+    }
+}
+
+struct TestDelay {
+    clock: TestClock,
+    id: u64,
+    deadline: Instant,
+}
+
+impl Future for TestDelay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        let mut state = self.clock.0.lock();
+        if state.now >= self.deadline {
+            Poll::Ready(())
+        } else {
+            state
+                .timers
+                .insert(self.id, (self.deadline, cx.waker().clone()));
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for TestDelay {
+    fn drop(&mut self) {
+        self.clock.0.lock().timers.remove(&self.id);
+    }
+}
+
+/// Create an `ActorRef` whose messages can be inspected by the test
+///
+/// ```
+/// use acto::testing::probe;
+/// # futures::executor::block_on(async {
+///
+/// let (aref, mut probe) = probe::<u32>();
+/// aref.tell(42);
+/// assert_eq!(probe.expect_message().await, 42);
+/// probe.expect_no_message(std::time::Duration::from_millis(10)).await;
+/// assert_eq!(probe.received_count(), 1);
+/// # });
+/// ```
+pub fn probe<M: Send + 'static>() -> (ActorRef<M>, Probe<M>) {
+    let (aref, recv) = TestMailbox.make_mailbox();
+    let probe = Probe {
+        recv,
+        queued: aref.clone(),
+        taken: 0,
+        timeout: Duration::from_secs(3),
+    };
+    (aref, probe)
+}
+
+/// The receiving side of a [`probe`](fn.probe.html)
+///
+/// Its methods panic when the expectation is not met, failing the test. The timers are real
+/// time, backed by a thread each, so a probe can be used with any runtime.
+pub struct Probe<M: Send + 'static> {
+    recv: Box<dyn Receiver<M>>,
+    // for looking at the queue length, this keeps the probe’s ActorRef valid
+    queued: ActorRef<M>,
+    taken: usize,
+    timeout: Duration,
+}
+
+impl<M: Send + 'static> Probe<M> {
+    /// Change the time `expect_message` waits for, which defaults to three seconds
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// Wait for the next message, panicking if none arrives in time
+    pub async fn expect_message(&mut self) -> M {
+        match self.next(thread_delay(self.timeout)).await {
+            Some(msg) => msg,
+            None => panic!("no message received within {:?}", self.timeout),
+        }
+    }
+
+    /// Wait for the given duration, panicking if a message arrives in the meantime
+    pub async fn expect_no_message(&mut self, dur: Duration) {
+        if self.next(thread_delay(dur)).await.is_some() {
+            panic!("unexpected message received within {:?}", dur);
+        }
+    }
+
+    /// The number of messages sent to the probe so far, including those not yet expected
+    pub fn received_count(&self) -> usize {
+        self.taken + self.queued.len()
+    }
+
+    async fn next(&mut self, mut delay: DelayBox) -> Option<M> {
+        let recv = &mut self.recv;
+        let msg = poll_fn(|cx| {
+            if let Poll::Ready(Ok(msg)) = Pin::new(recv.receive()).poll(cx) {
+                return Poll::Ready(Some(msg));
+            }
+            delay.as_mut().poll(cx).map(|_| None)
+        })
+        .await;
+        if msg.is_some() {
+            self.taken += 1;
+        }
+        msg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoActorRef;
+
+    #[test]
+    fn dropped() {
+        let spawner = TestSpawner::new();
+        let (aref, handle) = actor!(TestMailbox, spawner.clone(), |ctx| {
+            let result: Result<()> = ctx.receive().await;
+            Ok(result)
+        });
+        let mut handle = Box::pin(handle);
+
+        assert!(spawner.run_until(handle.as_mut()).is_none());
+        assert_eq!(spawner.task_count(), 1);
+
+        drop(aref);
+        let result = spawner.run_until(handle).unwrap().unwrap().unwrap();
+        result.unwrap_err().downcast::<NoActorRef>().unwrap();
+        assert_eq!(spawner.task_count(), 0);
+    }
+
+    #[test]
+    fn children() {
+        let spawner = TestSpawner::new();
+        let (aref, handle) = actor!(TestMailbox, spawner.clone(), |ctx| {
+            let (child, child_handle) = actor!(TestMailbox, |ctx| {
+                let msg: u32 = ctx.receive().await?;
+                Ok(msg * 2)
+            });
+            let msg: u32 = ctx.receive().await?;
+            child.tell(msg + 1);
+            Ok(child_handle.await??)
+        });
+        assert!(spawner.run_until_stalled());
+        assert_eq!(spawner.task_count(), 2);
+        assert!(!spawner.run_until_stalled());
+
+        aref.tell(20);
+        assert_eq!(spawner.run_until(handle).unwrap().unwrap().unwrap(), 42);
+    }
+
+    #[test]
+    fn clock() {
+        use crate::{Event, WithClock};
+
+        let spawner = TestSpawner::new();
+        let clock = TestClock::new();
+        let (aref, handle) = actor!(
+            TestMailbox,
+            WithClock(spawner.clone(), clock.clone()),
+            |ctx| {
+                let first: Option<u32> = ctx.receive_timeout(Duration::from_secs(10)).await?;
+                let second = ctx.receive_timeout(Duration::from_secs(10)).await?;
+                let tick = ctx.receive_or_tick(Duration::from_secs(60)).await?;
+                Ok((first, second, tick))
+            }
+        );
+        let mut handle = Box::pin(handle);
+
+        assert!(spawner.run_until(handle.as_mut()).is_none());
+        clock.advance(Duration::from_secs(9));
+        assert!(spawner.run_until(handle.as_mut()).is_none());
+        aref.tell(1);
+        assert!(spawner.run_until(handle.as_mut()).is_none());
+        clock.advance(Duration::from_secs(10));
+        assert!(spawner.run_until(handle.as_mut()).is_none());
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(
+            spawner.run_until(handle).unwrap().unwrap().unwrap(),
+            (Some(1), None, Event::Tick)
+        );
+    }
+
+    #[test]
+    fn probe() {
+        futures::executor::block_on(async {
+            let (aref, mut probe) = super::probe::<u32>();
+            let spawner = TestSpawner::new();
+            let (echo, _handle) = actor!(TestMailbox, spawner.clone(), |ctx| {
+                let mut msg: u32 = 1;
+                while msg > 0 {
+                    msg = ctx.receive().await?;
+                    aref.tell(msg);
+                }
+                Ok(())
+            });
+            echo.tell(2);
+            echo.tell(1);
+            assert_eq!(probe.received_count(), 0);
+            spawner.run_until_stalled();
+            assert_eq!(probe.received_count(), 2);
+            assert_eq!(probe.expect_message().await, 2);
+            assert_eq!(probe.expect_message().await, 1);
+            probe.expect_no_message(Duration::from_millis(10)).await;
+            assert_eq!(probe.received_count(), 2);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "no message received")]
+    fn probe_timeout() {
+        let (_aref, probe) = super::probe::<u32>();
+        let mut probe = probe.with_timeout(Duration::from_millis(10));
+        futures::executor::block_on(probe.expect_message());
+    }
+}
@@ -1,19 +1,37 @@
-//! Primitive spawner that creates a new thread for each actor
+//! Spawners and mailbox based only on the `futures` crate, without an async runtime
+//!
+//! [`ThreadSpawner`](struct.ThreadSpawner.html) creates a new thread for each actor while
+//! [`FuturesSpawner`](struct.FuturesSpawner.html) runs them on a `futures::executor::ThreadPool`.
 
-use crate::{FutureBox, FutureResultBox, Mailbox, Receiver, Spawner};
+use crate::{
+    panic_message, DelayBox, FutureBox, FutureResultBox, Mailbox, NoActorRef, Receiver, Sender,
+    SpawnError, Spawner,
+};
 use futures::{
     channel::{
-        mpsc::{unbounded, UnboundedReceiver},
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
         oneshot,
     },
-    executor::block_on,
-    StreamExt,
+    executor::{block_on, ThreadPool},
+    future::RemoteHandle,
+    task::SpawnExt,
+    FutureExt, StreamExt,
+};
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::OnceLock,
+    task::{Poll, Waker},
+    time::{Duration, Instant},
 };
-use std::{future::Future, pin::Pin, task::Poll};
 
 /// Spawner that creates a new thread for each actor
 ///
-/// The threads are named according to the given string.
+/// The threads are named according to the given string. Timers are served by a single shared
+/// timer thread, see [`FuturesSpawner`](struct.FuturesSpawner.html).
 pub struct ThreadSpawner(String);
 
 impl ThreadSpawner {
@@ -37,6 +55,153 @@ impl Spawner for ThreadSpawner {
             Ok(result)
         })
     }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        Box::pin(Timer::get().delay(dur))
+    }
+}
+
+/// Spawner that runs the actors on a `futures` thread pool
+///
+/// A panic in the actor is caught and surfaced as an error from the join handle. Since the pool
+/// has no timers, [`delay`](../trait.Spawner.html#method.delay) is served by one timer thread
+/// shared by all spawners in this module; dropping the delay future cancels its timer.
+#[derive(Clone)]
+pub struct FuturesSpawner(pub ThreadPool);
+
+impl Spawner for FuturesSpawner {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        let handle = match self
+            .0
+            .spawn_with_handle(AssertUnwindSafe(fut).catch_unwind())
+        {
+            Ok(handle) => ForgetOnDrop(Some(handle)),
+            Err(err) => return Box::pin(async move { Err(err.into()) }),
+        };
+        Box::pin(async move {
+            match handle.await {
+                Ok(result) => Ok(result),
+                Err(payload) => Err(SpawnError::Panicked(panic_message(&*payload)).into()),
+            }
+        })
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        Box::pin(Timer::get().delay(dur))
+    }
+}
+
+// one thread waking up the delays in deadline order, keyed by deadline and a unique id
+struct Timer {
+    state: Mutex<TimerState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct TimerState {
+    next_id: u64,
+    // an entry is removed once it has fired or its Delay has been dropped
+    timers: BTreeMap<(Instant, u64), Option<Waker>>,
+}
+
+impl Timer {
+    fn get() -> &'static Timer {
+        static TIMER: OnceLock<&'static Timer> = OnceLock::new();
+        TIMER.get_or_init(|| {
+            let timer: &'static Timer = Box::leak(Box::new(Timer {
+                state: Mutex::new(TimerState::default()),
+                changed: Condvar::new(),
+            }));
+            std::thread::Builder::new()
+                .name("acto-timer".to_owned())
+                .spawn(move || timer.run())
+                .unwrap();
+            timer
+        })
+    }
+
+    fn delay(&'static self, dur: Duration) -> Delay {
+        let deadline = Instant::now() + dur;
+        let mut state = self.state.lock();
+        let key = (deadline, state.next_id);
+        state.next_id += 1;
+        state.timers.insert(key, None);
+        if state.timers.keys().next() == Some(&key) {
+            self.changed.notify_one();
+        }
+        Delay { timer: self, key }
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock();
+        loop {
+            let now = Instant::now();
+            let mut woken = Vec::new();
+            while let Some(entry) = state.timers.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                woken.extend(entry.remove());
+            }
+            if !woken.is_empty() {
+                drop(state);
+                woken.into_iter().for_each(Waker::wake);
+                state = self.state.lock();
+                continue;
+            }
+            match state.timers.keys().next() {
+                Some(&(deadline, _)) => {
+                    self.changed.wait_until(&mut state, deadline);
+                }
+                None => self.changed.wait(&mut state),
+            }
+        }
+    }
+}
+
+struct Delay {
+    timer: &'static Timer,
+    key: (Instant, u64),
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.timer.state.lock();
+        match state.timers.get_mut(&self.key) {
+            Some(waker) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            None => Poll::Ready(()),
+        }
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        self.timer.state.lock().timers.remove(&self.key);
+    }
+}
+
+// dropping a RemoteHandle cancels the task, but dropping the join handle shall keep the actor running
+struct ForgetOnDrop<T>(Option<RemoteHandle<T>>);
+
+impl<T: 'static> Future for ForgetOnDrop<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().unwrap().poll_unpin(cx)
+    }
+}
+
+impl<T> Drop for ForgetOnDrop<T> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.0.take() {
+            handle.forget();
+        }
+    }
 }
 
 pub struct FuturesMailbox;
@@ -44,13 +209,23 @@ pub struct FuturesMailbox;
 impl Mailbox for FuturesMailbox {
     fn make_mailbox<M: Send + 'static>(&self) -> (super::ActorRef<M>, Box<dyn Receiver<M>>) {
         let (tx, rx) = unbounded::<M>();
-        let aref = super::ActorRef::new(Box::new(move |msg| {
-            let _ = tx.unbounded_send(msg);
-        }));
+        let aref = super::ActorRef::new(Box::new(FuturesSender(tx)));
         (aref, Box::new(FuturesReceiver(rx)))
     }
 }
 
+struct FuturesSender<M>(UnboundedSender<M>);
+
+impl<M: Send> Sender<M> for FuturesSender<M> {
+    fn tell(&self, msg: M) -> Result<(), M> {
+        self.0.unbounded_send(msg).map_err(|e| e.into_inner())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
 pub struct FuturesReceiver<M>(UnboundedReceiver<M>);
 
 impl<M: Send + 'static> super::Receiver<M> for FuturesReceiver<M> {
@@ -68,7 +243,7 @@ impl<M: Send + 'static> Future for FuturesReceiver<M> {
     ) -> std::task::Poll<Self::Output> {
         match self.as_mut().0.poll_next_unpin(cx) {
             Poll::Ready(Some(msg)) => Poll::Ready(Ok(msg)),
-            Poll::Ready(None) => Poll::Ready(Err(anyhow::anyhow!("channel closed"))),
+            Poll::Ready(None) => Poll::Ready(Err(NoActorRef.into())),
             Poll::Pending => Poll::Pending,
         }
     }
@@ -157,4 +332,134 @@ mod tests {
                 .unwrap_or_else(|e| panic!("unexpected error type: {}", e));
         })
     }
+
+    fn pool() -> FuturesSpawner {
+        FuturesSpawner(ThreadPool::new().unwrap())
+    }
+
+    #[test]
+    fn pool_smoke() {
+        block_on(async {
+            let spawner = pool();
+            let (aref, join_handle) = actor!(FuturesMailbox, spawner.clone(), fn actor(ctx));
+
+            let (tx, rx) = oneshot::channel();
+            let (receiver, jr) = actor!(FuturesMailbox, spawner.clone(), |ctx| {
+                let msg = ctx.receive().await?;
+                let _ = tx.send(msg);
+                Ok("buh")
+            });
+            aref.tell(("Fred".to_owned(), receiver));
+            assert_eq!(rx.await.unwrap(), "Hello Fred!");
+            assert_eq!(jr.await.unwrap().unwrap(), "buh");
+
+            drop(aref);
+            join_handle
+                .await
+                .unwrap()
+                .unwrap_err()
+                .downcast::<NoActorRef>()
+                .unwrap();
+        })
+    }
+
+    #[test]
+    fn pool_dropped() {
+        block_on(async {
+            let (tx, mut rx) = oneshot::channel();
+            let (aref, handle) = actor!(FuturesMailbox, pool(), |ctx| {
+                let result: Result<()> = ctx.receive().await;
+                let _ = tx.send(result);
+                Ok(())
+            });
+
+            sleep(Duration::from_millis(200));
+            match poll!(&mut rx) {
+                Poll::Pending => {}
+                x => panic!("unexpected result: {:?}", x),
+            }
+
+            drop(aref);
+            handle.await.unwrap().unwrap();
+            let err = match poll!(rx) {
+                Poll::Ready(Ok(e)) => e.unwrap_err(),
+                x => panic!("unexpected poll result: {:?}", x),
+            };
+            err.downcast::<NoActorRef>()
+                .unwrap_or_else(|e| panic!("unexpected error type: {}", e));
+        })
+    }
+
+    #[test]
+    fn pool_detached() {
+        block_on(async {
+            let (tx, rx) = oneshot::channel();
+            let (aref, handle) = actor!(FuturesMailbox, pool(), |ctx| {
+                let msg: u32 = ctx.receive().await?;
+                let _ = tx.send(msg);
+                Ok(())
+            });
+            drop(handle);
+            aref.tell(42);
+            assert_eq!(rx.await.unwrap(), 42);
+        })
+    }
+
+    #[test]
+    fn pool_panicked() {
+        block_on(async {
+            let (aref, handle) = actor!(FuturesMailbox, pool(), |ctx| {
+                let msg: u32 = ctx.receive().await?;
+                if msg == 0 {
+                    panic!("boom");
+                }
+                Ok(msg)
+            });
+            aref.tell(0);
+            let err = handle.await.unwrap_err();
+            assert_eq!(err.to_string(), "actor task panicked: boom");
+        })
+    }
+
+    #[test]
+    fn pool_receive_timeout() {
+        block_on(async {
+            let (aref, handle) = actor!(FuturesMailbox, pool(), |ctx| {
+                let timed_out: Option<u32> = ctx.receive_timeout(Duration::from_millis(50)).await?;
+                let msg = ctx.receive_timeout(Duration::from_secs(10)).await?;
+                Ok((timed_out, msg))
+            });
+            sleep(Duration::from_millis(200));
+            aref.tell(42);
+            assert_eq!(handle.await.unwrap().unwrap(), (None, Some(42)));
+        })
+    }
+
+    #[test]
+    fn delay_cancelled_on_drop() {
+        block_on(async {
+            let timer = Timer::get();
+            let start = Instant::now();
+            let delay = timer.delay(Duration::from_secs(10));
+            let key = delay.key;
+            drop(delay);
+            assert!(!timer.state.lock().timers.contains_key(&key));
+            pool().delay(Duration::from_millis(50)).await;
+            assert!(start.elapsed() < Duration::from_secs(5));
+        })
+    }
+
+    #[test]
+    fn receive_timeout() {
+        block_on(async {
+            let (aref, handle) = actor!(FuturesMailbox, ThreadSpawner::new("a"), |ctx| {
+                let timed_out: Option<u32> = ctx.receive_timeout(Duration::from_millis(50)).await?;
+                let msg = ctx.receive_timeout(Duration::from_secs(10)).await?;
+                Ok((timed_out, msg))
+            });
+            sleep(Duration::from_millis(200));
+            aref.tell(42);
+            assert_eq!(handle.await.unwrap().unwrap(), (None, Some(42)));
+        })
+    }
 }
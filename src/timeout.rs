@@ -0,0 +1,68 @@
+use crate::{any_box, DelayBox, FutureBox, FutureResultBox, Spawner};
+use derive_more::{Display, Error};
+use std::{
+    any::Any,
+    future::poll_fn,
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+/// Spawner decorator that stops actors running longer than the given duration
+///
+/// The timer starts when the actor is spawned; once it fires the actor’s future is dropped,
+/// releasing all its resources, and its join handle fails with [`Timeout`](struct.Timeout.html).
+/// Child actors spawned via the actor’s `Context` get the same time limit, counting from their
+/// own start.
+pub struct WithTimeout<S>(pub S, pub Duration);
+
+type Outcome = Result<Box<dyn Any + Send + 'static>, Timeout>;
+
+impl<S: Spawner> WithTimeout<S> {
+    fn limit(
+        &self,
+        mut fut: FutureBox,
+        spawn: impl FnOnce(FutureBox) -> FutureResultBox,
+    ) -> FutureResultBox {
+        let mut delay = self.0.delay(self.1);
+        let race = poll_fn(move |cx| {
+            if let Poll::Ready(result) = fut.as_mut().poll(cx) {
+                return Poll::Ready(Ok(result));
+            }
+            delay.as_mut().poll(cx).map(|_| Err(Timeout))
+        });
+        let handle = spawn(any_box(race));
+        Box::pin(async move {
+            match handle.await?.downcast::<Outcome>() {
+                Ok(outcome) => Ok((*outcome)?),
+                Err(_) => Err(anyhow::anyhow!("found wrong type in join handle")),
+            }
+        })
+    }
+}
+
+impl<S: Spawner> Spawner for WithTimeout<S> {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        self.limit(fut, |race| self.0.spawn(race))
+    }
+
+    fn spawn_named(&self, name: &str, fut: FutureBox) -> FutureResultBox {
+        self.limit(fut, |race| self.0.spawn_named(name, race))
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        self.0.delay(dur)
+    }
+
+    fn now(&self) -> Instant {
+        self.0.now()
+    }
+
+    fn takes_turns(&self) -> bool {
+        self.0.takes_turns()
+    }
+}
+
+/// Error from the join handle of an actor that was stopped by [`WithTimeout`](struct.WithTimeout.html)
+#[derive(Debug, Display, Error)]
+#[display(fmt = "actor was stopped after exceeding its time limit")]
+pub struct Timeout;
@@ -1,6 +1,26 @@
-use crate::{FutureBox, FutureResultBox, Mailbox, Receiver, Spawner};
-use std::{future::Future, pin::Pin, task::Poll};
-use tokio::{runtime::Runtime, sync::mpsc};
+use crate::{
+    panic_message, DelayBox, FutureBox, FutureResultBox, LocalFutureBox, LocalFutureResultBox,
+    Mailbox, MailboxFor, NoActorRef, Permit, Receiver, ReserveBox, SendError, Sender, SpawnError,
+    SpawnLocal, Spawner,
+};
+use parking_lot::Mutex;
+use std::{
+    any::Any,
+    future::Future,
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    task::{Poll, Wake, Waker},
+    thread::Thread,
+    time::Duration,
+};
+use tokio::{
+    runtime::Runtime,
+    sync::mpsc::{self, error::TrySendError},
+    task::{JoinError, JoinHandle},
+};
 
 /// Spawner that uses the current tokio context
 ///
@@ -9,44 +29,249 @@ pub struct TokioSpawner;
 
 impl Spawner for TokioSpawner {
     fn spawn(&self, fut: FutureBox) -> FutureResultBox {
-        let fut = tokio::spawn(fut);
+        join(tokio::spawn(fut))
+    }
+
+    /// Names are only passed on to tokio when building with `--cfg tokio_unstable` and the
+    /// `with_tracing` feature, as required by `tokio::task::Builder`.
+    fn spawn_named(&self, name: &str, fut: FutureBox) -> FutureResultBox {
+        #[cfg(all(tokio_unstable, feature = "with_tracing"))]
+        {
+            match tokio::task::Builder::new().name(name).spawn(fut) {
+                Ok(handle) => join(handle),
+                Err(err) => Box::pin(async move { Err(err.into()) }),
+            }
+        }
+        #[cfg(not(all(tokio_unstable, feature = "with_tracing")))]
+        {
+            let _ = name;
+            self.spawn(fut)
+        }
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        Box::pin(tokio::time::sleep(dur))
+    }
+}
+
+/// Spawner that uses the given tokio runtime
+///
+/// The runtime needs to have the time driver enabled for timers to work.
+pub struct TokioRuntimeSpawner(pub Runtime);
+
+impl Spawner for TokioRuntimeSpawner {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        join(self.0.spawn(fut))
+    }
+
+    /// See [`TokioSpawner::spawn_named`](struct.TokioSpawner.html#method.spawn_named)
+    fn spawn_named(&self, name: &str, fut: FutureBox) -> FutureResultBox {
+        #[cfg(all(tokio_unstable, feature = "with_tracing"))]
+        {
+            match tokio::task::Builder::new()
+                .name(name)
+                .spawn_on(fut, self.0.handle())
+            {
+                Ok(handle) => join(handle),
+                Err(err) => Box::pin(async move { Err(err.into()) }),
+            }
+        }
+        #[cfg(not(all(tokio_unstable, feature = "with_tracing")))]
+        {
+            let _ = name;
+            self.spawn(fut)
+        }
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        let _guard = self.0.enter();
+        Box::pin(tokio::time::sleep(dur))
+    }
+}
+
+/// Spawner that drives each actor on its own thread from tokio’s blocking pool
+///
+/// This is intended for actors that mostly compute between messages: their CPU-bound work then
+/// does not starve the actors running on the tokio reactor. The actor can still await its mailbox
+/// and timers, but it occupies a blocking thread for its whole lifetime, so use it sparingly.
+/// Child actors spawned from within such an actor’s `Context` will also run on blocking threads.
+///
+/// Will fail when used on a non-tokio thread.
+pub struct BlockingSpawner;
+
+impl Spawner for BlockingSpawner {
+    fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+        let fut = tokio::task::spawn_blocking(move || block_on(fut));
         Box::pin(async move {
             match fut.await {
                 Ok(result) => Ok(result),
-                Err(err) => Err(err.into()),
+                Err(err) => Err(join_error(err)),
             }
         })
     }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        Box::pin(tokio::time::sleep(dur))
+    }
 }
 
-/// Spawner that uses the given tokio runtime
-pub struct TokioRuntimeSpawner(pub Runtime);
+/// Spawner for actors on the current thread’s [`LocalSet`](https://docs.rs/tokio/1/tokio/task/struct.LocalSet.html)
+///
+/// Use it with the `local` form of the [`actor`](../macro.actor.html) macro to run actors that are
+/// not `Send`, or together with a [`LocalMailbox`](struct.LocalMailbox.html) for messages that are
+/// not `Send`. Will fail when used outside of a `LocalSet`.
+pub struct LocalSpawner;
 
-impl Spawner for TokioRuntimeSpawner {
+impl Spawner for LocalSpawner {
     fn spawn(&self, fut: FutureBox) -> FutureResultBox {
-        let fut = self.0.spawn(fut);
+        let fut = tokio::task::spawn_local(fut);
+        Box::pin(async move {
+            match fut.await {
+                Ok(result) => Ok(result),
+                Err(err) => Err(join_error(err)),
+            }
+        })
+    }
+
+    fn delay(&self, dur: Duration) -> DelayBox {
+        Box::pin(tokio::time::sleep(dur))
+    }
+}
+
+impl SpawnLocal for LocalSpawner {
+    fn spawn_local(&self, fut: LocalFutureBox) -> LocalFutureResultBox {
+        let fut = tokio::task::spawn_local(fut);
         Box::pin(async move {
             match fut.await {
                 Ok(result) => Ok(result),
-                Err(err) => Err(err.into()),
+                Err(err) => Err(join_error(err)),
             }
         })
     }
 }
 
+/// Mailbox for actors on a [`LocalSet`](https://docs.rs/tokio/1/tokio/task/struct.LocalSet.html)
+/// whose messages are not `Send`
+///
+/// `ActorRef` and `Context` may move between threads and therefore require `Send` messages, so
+/// this mailbox hands out the simpler [`LocalActorRef`](struct.LocalActorRef.html) and
+/// [`LocalReceiver`](struct.LocalReceiver.html) instead. Spawn the actor with
+/// [`spawn_local`](../fn.spawn_local.html) and the [`LocalSpawner`](struct.LocalSpawner.html).
+///
+/// Since there is no [`Context`](../struct.Context.html), such an actor goes without everything
+/// built on it: the [`actor`](../macro.actor.html) macro, `receive_timeout` and other timers
+/// (use `tokio::time` directly), stashing, children and supervision, shutdown tokens, and
+/// lifecycle hooks. `LocalActorRef` has no dead letters, labels, `len` or `ask` either; it only
+/// offers `tell`, `try_tell` and `is_closed`. Prefer `Send` messages with the `local` form of the
+/// macro when the actor merely holds non-`Send` state.
+pub struct LocalMailbox;
+
+impl LocalMailbox {
+    pub fn make_mailbox<M: 'static>(&self) -> (LocalActorRef<M>, LocalReceiver<M>) {
+        let (tx, rx) = mpsc::unbounded_channel::<M>();
+        (LocalActorRef(tx), LocalReceiver(rx))
+    }
+}
+
+/// Sending side of a [`LocalMailbox`](struct.LocalMailbox.html), bound to the thread it was
+/// created on if the messages are not `Send`
+pub struct LocalActorRef<M>(mpsc::UnboundedSender<M>);
+
+impl<M> Clone for LocalActorRef<M> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<M> LocalActorRef<M> {
+    /// Send a message to the actor, ignoring delivery failures
+    pub fn tell(&self, msg: M) {
+        let _ = self.0.send(msg);
+    }
+
+    /// Send a message to the actor, handing it back if the actor has stopped
+    pub fn try_tell(&self, msg: M) -> Result<(), SendError<M>> {
+        self.0.send(msg).map_err(|err| SendError(err.0))
+    }
+
+    /// Whether the actor has dropped its [`LocalReceiver`](struct.LocalReceiver.html)
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+/// Receiving side of a [`LocalMailbox`](struct.LocalMailbox.html)
+pub struct LocalReceiver<M>(mpsc::UnboundedReceiver<M>);
+
+impl<M> LocalReceiver<M> {
+    /// Wait for the next message, failing with [`NoActorRef`](../struct.NoActorRef.html) once all
+    /// `LocalActorRef`s have been dropped and the queue is empty
+    pub async fn receive(&mut self) -> anyhow::Result<M> {
+        self.0.recv().await.ok_or_else(|| NoActorRef.into())
+    }
+}
+
 pub struct TokioMailbox;
 
 impl Mailbox for TokioMailbox {
     fn make_mailbox<M: Send + 'static>(&self) -> (super::ActorRef<M>, Box<dyn Receiver<M>>) {
         let (tx, rx) = mpsc::unbounded_channel::<M>();
-        let aref = super::ActorRef::new(Box::new(move |msg| {
-            let _ = tx.send(msg);
-        }));
-        (aref, Box::new(TokioReceiver(rx)))
+        let len = Arc::new(QueueLen::default());
+        let aref = super::ActorRef::from_tokio(TokioSender {
+            tx,
+            len: len.clone(),
+        });
+        (aref, Box::new(TokioReceiver { rx, len }))
+    }
+}
+
+// stored inline in the ActorRef, see MailboxSender
+pub(crate) struct TokioSender<M> {
+    tx: mpsc::UnboundedSender<M>,
+    len: Arc<QueueLen>,
+}
+
+// the number of queued messages; no longer tracked once tokio_sender handed out the channel
+#[derive(Default)]
+struct QueueLen {
+    count: AtomicUsize,
+    untracked: AtomicBool,
+}
+
+impl<M> TokioSender<M> {
+    #[inline]
+    pub(crate) fn tell(&self, msg: M) -> Result<(), M> {
+        // count first, otherwise the receiver could decrement before we increment
+        self.len.count.fetch_add(1, Ordering::Relaxed);
+        self.tx.send(msg).map_err(|e| {
+            self.len.count.fetch_sub(1, Ordering::Relaxed);
+            e.0
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        if self.len.untracked.load(Ordering::Relaxed) {
+            0
+        } else {
+            self.len.count.load(Ordering::Relaxed)
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    // switches off the length tracking, since the messages sent through the result are not counted
+    pub(crate) fn raw(&self) -> mpsc::UnboundedSender<M> {
+        self.len.untracked.store(true, Ordering::Relaxed);
+        self.tx.clone()
     }
 }
 
-pub struct TokioReceiver<M>(mpsc::UnboundedReceiver<M>);
+pub struct TokioReceiver<M> {
+    rx: mpsc::UnboundedReceiver<M>,
+    len: Arc<QueueLen>,
+}
 
 impl<M: Send + 'static> super::Receiver<M> for TokioReceiver<M> {
     fn receive(&mut self) -> &mut (dyn Future<Output = anyhow::Result<M>> + Send + Unpin + '_) {
@@ -61,18 +286,252 @@ impl<M: Send + 'static> Future for TokioReceiver<M> {
         mut self: Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Self::Output> {
-        match self.as_mut().0.poll_recv(cx) {
+        match self.as_mut().rx.poll_recv(cx) {
+            Poll::Ready(Some(msg)) => {
+                // uncounted messages are only sent after tracking has been switched off
+                if !self.len.untracked.load(Ordering::Relaxed) {
+                    self.len.count.fetch_sub(1, Ordering::Relaxed);
+                }
+                Poll::Ready(Ok(msg))
+            }
+            Poll::Ready(None) => Poll::Ready(Err(NoActorRef.into())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Strategy for handling messages sent to a full [`TokioBoundedMailbox`](struct.TokioBoundedMailbox.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnFull {
+    /// Discard the message that was just sent, keeping the queue as it is
+    ///
+    /// This never blocks the sender, but the newest information is lost.
+    DropNewest,
+    /// Discard the oldest queued message to make room for the new one
+    ///
+    /// This never blocks the sender and keeps the newest information, at the cost of
+    /// briefly taking the receiver’s lock from the sending side.
+    DropOldest,
+    /// Block the sending thread until there is room in the queue
+    ///
+    /// No message is lost, but `tell` may now take arbitrarily long, and it parks the calling
+    /// thread — not only the task. Using this from within an actor on a single-threaded runtime
+    /// that also runs the recipient will deadlock.
+    Block,
+}
+
+/// Mailbox factory for tokio MPSC channels with a fixed capacity
+///
+/// Since [`ActorRef::tell`](../struct.ActorRef.html#method.tell) is synchronous and fire-and-forget,
+/// the `on_full` strategy determines what happens when the queue is full.
+pub struct TokioBoundedMailbox {
+    /// Must not be zero, which tokio’s channels do not support
+    pub capacity: usize,
+    pub on_full: OnFull,
+}
+
+impl TokioBoundedMailbox {
+    /// Panics if `capacity` is zero
+    pub fn new(capacity: usize, on_full: OnFull) -> Self {
+        assert!(capacity > 0, "capacity must not be zero");
+        Self { capacity, on_full }
+    }
+
+    /// Count the messages that are discarded because the queue is full, and pass them to `f`
+    ///
+    /// These are the new messages for [`OnFull::DropNewest`](enum.OnFull.html) and the evicted
+    /// ones for `OnFull::DropOldest`; with `OnFull::Block` nothing is discarded. The callback runs
+    /// on the sending thread while the sender waits for it, so it should be cheap — like
+    /// incrementing a metric or logging.
+    pub fn on_reject<M>(self, f: impl Fn(&M) + Send + Sync + 'static) -> RejectingMailbox<M> {
+        RejectingMailbox {
+            mailbox: self,
+            rejections: Arc::new(Rejections {
+                count: AtomicUsize::new(0),
+                f: Box::new(f),
+            }),
+        }
+    }
+
+    fn make<M: Send + 'static>(
+        &self,
+        rejections: Option<Arc<Rejections<M>>>,
+    ) -> (super::ActorRef<M>, Box<dyn Receiver<M>>) {
+        let (tx, rx) = mpsc::channel::<M>(self.capacity);
+        let rx = Arc::new(Mutex::new(rx));
+        let aref = super::ActorRef::new(Box::new(TokioBoundedSender {
+            tx,
+            // only the receiver keeps the queue alive, the weak reference is for OnFull::DropOldest
+            rx: Arc::downgrade(&rx),
+            on_full: self.on_full,
+            rejections,
+        }));
+        (aref, Box::new(TokioBoundedReceiver(rx)))
+    }
+}
+
+impl Mailbox for TokioBoundedMailbox {
+    fn make_mailbox<M: Send + 'static>(&self) -> (super::ActorRef<M>, Box<dyn Receiver<M>>) {
+        self.make(None)
+    }
+}
+
+/// A [`TokioBoundedMailbox`](struct.TokioBoundedMailbox.html) that observes discarded messages,
+/// see [`on_reject`](struct.TokioBoundedMailbox.html#method.on_reject)
+pub struct RejectingMailbox<M> {
+    mailbox: TokioBoundedMailbox,
+    rejections: Arc<Rejections<M>>,
+}
+
+struct Rejections<M> {
+    count: AtomicUsize,
+    f: Box<dyn Fn(&M) + Send + Sync>,
+}
+
+impl<M> Rejections<M> {
+    fn reject(&self, msg: &M) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        (self.f)(msg);
+    }
+}
+
+impl<M> RejectingMailbox<M> {
+    /// The number of messages discarded so far, summed over all mailboxes created by this factory
+    pub fn rejected_count(&self) -> usize {
+        self.rejections.count.load(Ordering::Relaxed)
+    }
+}
+
+impl<M: Send + 'static> MailboxFor<M> for RejectingMailbox<M> {
+    fn make_mailbox_for(&self) -> (super::ActorRef<M>, Box<dyn Receiver<M>>) {
+        self.mailbox.make(Some(self.rejections.clone()))
+    }
+}
+
+struct TokioBoundedSender<M> {
+    tx: mpsc::Sender<M>,
+    rx: Weak<Mutex<mpsc::Receiver<M>>>,
+    on_full: OnFull,
+    rejections: Option<Arc<Rejections<M>>>,
+}
+
+impl<M: Send + 'static> Sender<M> for TokioBoundedSender<M> {
+    fn tell(&self, mut msg: M) -> Result<(), M> {
+        loop {
+            match self.tx.try_send(msg) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Closed(m)) => return Err(m),
+                Err(TrySendError::Full(m)) => match self.on_full {
+                    OnFull::DropNewest => {
+                        if let Some(rejections) = &self.rejections {
+                            rejections.reject(&m);
+                        }
+                        return Err(m);
+                    }
+                    OnFull::DropOldest => match self.rx.upgrade() {
+                        Some(rx) => {
+                            let oldest = rx.lock().try_recv();
+                            if let (Ok(oldest), Some(rejections)) = (oldest, &self.rejections) {
+                                rejections.reject(&oldest);
+                            }
+                            msg = m;
+                        }
+                        None => return Err(m),
+                    },
+                    OnFull::Block => return block_on(self.tx.send(m)).map_err(|e| e.0),
+                },
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.tx.max_capacity())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    fn reserve(&self) -> Option<ReserveBox<M>> {
+        let tx = self.tx.clone();
+        Some(Box::pin(async move {
+            let permit = tx.reserve_owned().await.ok()?;
+            Some(Box::new(move |msg| {
+                permit.send(msg);
+            }) as Permit<M>)
+        }))
+    }
+}
+
+pub struct TokioBoundedReceiver<M>(Arc<Mutex<mpsc::Receiver<M>>>);
+
+impl<M: Send + 'static> super::Receiver<M> for TokioBoundedReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = anyhow::Result<M>> + Send + Unpin + '_) {
+        self
+    }
+}
+
+impl<M: Send + 'static> Future for TokioBoundedReceiver<M> {
+    type Output = anyhow::Result<M>;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        match self.0.lock().poll_recv(cx) {
             Poll::Ready(Some(msg)) => Poll::Ready(Ok(msg)),
-            Poll::Ready(None) => Poll::Ready(Err(anyhow::anyhow!("channel closed"))),
+            Poll::Ready(None) => Poll::Ready(Err(NoActorRef.into())),
             Poll::Pending => Poll::Pending,
         }
     }
 }
 
+fn join(handle: JoinHandle<Box<dyn Any + Send + 'static>>) -> FutureResultBox {
+    Box::pin(async move {
+        match handle.await {
+            Ok(result) => Ok(result),
+            Err(err) => Err(join_error(err)),
+        }
+    })
+}
+
+// keep the panic message, which the JoinError’s Display impl omits
+fn join_error(err: JoinError) -> anyhow::Error {
+    match err.try_into_panic() {
+        Ok(payload) => SpawnError::Panicked(panic_message(&*payload)).into(),
+        Err(_) => SpawnError::Cancelled.into(),
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// park the current thread until the future completes, used for OnFull::Block
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = std::task::Context::from_waker(&waker);
+    let mut fut = pin!(fut);
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(result) => return result,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{ActorRef, Context, NoActorRef};
+    use crate::{ActorRef, Context, Event, NoActorRef};
     use anyhow::Result;
     use futures::poll;
     use std::{task::Poll, thread::sleep, time::Duration};
@@ -148,4 +607,3093 @@ mod tests {
         err.downcast::<NoActorRef>()
             .unwrap_or_else(|e| panic!("unexpected error type: {}", e));
     }
+
+    #[tokio::test]
+    async fn try_tell() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: u32 = ctx.receive().await?;
+            Ok(msg)
+        });
+        aref.try_tell(1).unwrap();
+        assert_eq!(handle.await.unwrap().unwrap(), 1);
+        assert_eq!(aref.try_tell(2).unwrap_err().0, 2);
+
+        let (aref, _recv) = TokioBoundedMailbox::new(1, OnFull::DropNewest).make_mailbox();
+        aref.try_tell(1).unwrap();
+        assert_eq!(aref.try_tell(2).unwrap_err().0, 2);
+    }
+
+    #[tokio::test]
+    async fn receive_timeout() {
+        let (tx, rx) = oneshot::channel();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let timed_out: Option<u32> = ctx.receive_timeout(Duration::from_millis(50)).await?;
+            let _ = tx.send(timed_out);
+            let msg = ctx.receive_timeout(Duration::from_secs(10)).await?;
+            Ok(msg)
+        });
+        assert_eq!(rx.await.unwrap(), None);
+        aref.tell(42);
+        assert_eq!(handle.await.unwrap().unwrap(), Some(42));
+    }
+
+    #[tokio::test]
+    async fn me() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let myself = ctx.me();
+            let first: u32 = ctx.receive().await?;
+            // the external ActorRef is gone now, but our own keeps the mailbox open
+            let timed_out = ctx.receive_timeout(Duration::from_millis(50)).await?;
+            myself.tell(first + 1);
+            let second = ctx.receive().await?;
+            Ok((timed_out, second))
+        });
+        aref.tell(1);
+        drop(aref);
+        assert_eq!(handle.await.unwrap().unwrap(), (None, 2));
+    }
+
+    #[tokio::test]
+    async fn tell_after() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let first: u32 = ctx.receive().await?;
+            let second = ctx.receive_timeout(Duration::from_millis(300)).await?;
+            Ok((first, second))
+        });
+        let _delivered = aref.tell_after_with(&TokioSpawner, 1, Duration::from_millis(50));
+        let cancelled = aref.tell_after_with(&TokioSpawner, 2, Duration::from_millis(100));
+        drop(cancelled);
+        assert_eq!(handle.await.unwrap().unwrap(), (1, None));
+    }
+
+    #[tokio::test]
+    async fn receive_or_tick() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let period = Duration::from_millis(50);
+            let first: Event<u32> = ctx.receive_or_tick(period).await?;
+            // miss three ticks
+            tokio::time::sleep(Duration::from_millis(170)).await;
+            let overdue = ctx.receive_or_tick(period).await?;
+            let msg = ctx.receive_or_tick(period).await?;
+            let start = std::time::Instant::now();
+            let next = ctx.receive_or_tick(period).await?;
+            Ok((vec![first, overdue, msg, next], start.elapsed()))
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        aref.tell(42);
+        let (events, elapsed) = handle.await.unwrap().unwrap();
+        assert_eq!(
+            events,
+            vec![Event::Tick, Event::Tick, Event::Message(42), Event::Tick]
+        );
+        assert!(elapsed >= Duration::from_millis(10), "ticks piled up");
+
+        // a zero period would tick endlessly
+        let (_aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _: Event<u32> = ctx.receive_or_tick(Duration::ZERO).await?;
+            Ok(())
+        });
+        let err = handle.await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&SpawnError::Panicked(
+                "tick period must not be zero".to_owned()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn supervised() {
+        use crate::{RestartStrategy, Supervised};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let incarnations = Arc::new(AtomicUsize::new(0));
+        let supervisor = Supervised::new(
+            TokioSpawner,
+            RestartStrategy::UpTo(2, Duration::from_secs(10)),
+        );
+        let counter = incarnations.clone();
+        let (aref, handle) = supervisor.spawn(TokioMailbox, move |mut ctx: Context<u32>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                loop {
+                    if ctx.receive().await? == 0 {
+                        panic!("boom");
+                    }
+                }
+            }
+        });
+        for msg in [1, 0, 1, 0, 1, 0, 1] {
+            aref.tell(msg);
+        }
+        let result: Result<()> = handle.await.unwrap();
+        result.unwrap_err();
+        assert_eq!(incarnations.load(Ordering::SeqCst), 3);
+
+        // a normal stop is not a failure
+        let (aref, handle) = supervisor.spawn(TokioMailbox, |mut ctx: Context<u32>| async move {
+            ctx.receive().await
+        });
+        drop(aref);
+        handle
+            .await
+            .unwrap()
+            .unwrap_err()
+            .downcast::<NoActorRef>()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn lifecycle() {
+        use crate::Lifecycle;
+
+        struct Hooks(Arc<Mutex<Vec<&'static str>>>);
+
+        impl Lifecycle<u32> for Hooks {
+            async fn started(&mut self, _ctx: &mut Context<u32>) -> Result<()> {
+                self.0.lock().push("started");
+                Ok(())
+            }
+            async fn stopped(&mut self) {
+                self.0.lock().push("stopped");
+            }
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let l = log.clone();
+        let (aref, handle) = actor!(
+            TokioMailbox,
+            TokioSpawner,
+            hooks = Hooks(log.clone()),
+            |ctx| {
+                loop {
+                    ctx.receive().await?;
+                    l.lock().push("received");
+                }
+            }
+        );
+        aref.tell(1);
+        drop(aref);
+        let result: Result<()> = handle.await.unwrap();
+        result.unwrap_err().downcast::<NoActorRef>().unwrap();
+        assert_eq!(*log.lock(), vec!["started", "received", "stopped"]);
+    }
+
+    #[tokio::test]
+    async fn len() {
+        let (aref, mut recv) = TokioMailbox.make_mailbox();
+        assert!(aref.is_empty());
+        aref.tell(1);
+        aref.tell(2);
+        assert_eq!(aref.len(), 2);
+        assert_eq!(aref.capacity(), None);
+        recv.receive().await.unwrap();
+        assert_eq!(aref.len(), 1);
+
+        let (aref, mut recv) = TokioBoundedMailbox::new(5, OnFull::DropNewest).make_mailbox();
+        aref.tell(1);
+        aref.tell(2);
+        assert_eq!(aref.len(), 2);
+        assert_eq!(aref.capacity(), Some(5));
+        recv.receive().await.unwrap();
+        assert_eq!(aref.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "capacity must not be zero")]
+    fn bounded_zero_capacity() {
+        TokioBoundedMailbox::new(0, OnFull::DropNewest);
+    }
+
+    #[tokio::test]
+    async fn stash() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            loop {
+                match ctx.receive().await? {
+                    0 => break,
+                    msg => ctx.stash(msg),
+                }
+            }
+            ctx.unstash_all();
+            let mut received = Vec::new();
+            for _ in 0..3 {
+                received.push(ctx.receive().await?);
+            }
+            Ok(received)
+        });
+        for msg in [1, 2, 0, 3] {
+            aref.tell(msg);
+        }
+        assert_eq!(handle.await.unwrap().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn receive_many() {
+        let mut ctx = Context::new(TokioMailbox, Arc::new(TokioSpawner));
+        let aref = ctx.me();
+        for msg in 1..=5 {
+            aref.tell(msg);
+        }
+        drop(aref);
+        assert_eq!(ctx.receive_many(3).await.unwrap(), vec![1, 2, 3]);
+        assert_eq!(ctx.receive_many(10).await.unwrap(), vec![4, 5]);
+        ctx.receive_many(10)
+            .await
+            .unwrap_err()
+            .downcast::<NoActorRef>()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn contramap() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: String = ctx.receive().await?;
+            Ok(msg)
+        });
+        let mapped = aref.contramap(|n: u32| format!("number {}", n));
+        mapped.try_tell(42).unwrap();
+        assert_eq!(handle.await.unwrap().unwrap(), "number 42");
+        assert_eq!(mapped.try_tell(43).unwrap_err().0, 43);
+    }
+
+    #[tokio::test]
+    async fn receiver_stream() {
+        use crate::ReceiverStream;
+        use futures::StreamExt;
+
+        let (aref, recv) = TokioMailbox.make_mailbox();
+        for msg in 1..=3 {
+            aref.tell(msg);
+        }
+        drop(aref);
+        let received = ReceiverStream::new(recv).collect::<Vec<_>>().await;
+        assert_eq!(received, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn sink() {
+        use futures::{stream, StreamExt};
+
+        let mailbox = TokioBoundedMailbox::new(1, OnFull::DropNewest);
+        let (aref, handle) = actor!(mailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            let stopped = loop {
+                match ctx.receive().await {
+                    Ok(msg) => received.push(msg),
+                    Err(e) => break e.is::<NoActorRef>(),
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            };
+            Ok((received, stopped))
+        });
+        // without backpressure most of these would be dropped
+        stream::iter(1..=5)
+            .map(Ok)
+            .forward(aref.into_sink())
+            .await
+            .unwrap();
+        assert_eq!(handle.await.unwrap().unwrap(), (vec![1, 2, 3, 4, 5], true));
+    }
+
+    #[tokio::test]
+    async fn blocking() {
+        use std::sync::atomic::AtomicBool;
+
+        let flag = Arc::new(AtomicBool::new(false));
+        let seen = flag.clone();
+        let (cruncher, crunched) = actor!(TokioMailbox, BlockingSpawner, |ctx| {
+            let () = ctx.receive().await?;
+            let deadline = std::time::Instant::now() + Duration::from_secs(5);
+            // busy loop that would starve the single-threaded test runtime
+            while !seen.load(Ordering::Acquire) && std::time::Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+            Ok(seen.load(Ordering::Acquire))
+        });
+        let (other, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let () = ctx.receive().await?;
+            flag.store(true, Ordering::Release);
+            Ok(())
+        });
+
+        cruncher.tell(());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        other.tell(());
+        handle.await.unwrap().unwrap();
+        assert!(crunched.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn local() {
+        use std::{cell::RefCell, rc::Rc};
+
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let (aref, handle) = actor!(
+                    TokioMailbox,
+                    LocalSpawner,
+                    local | ctx | {
+                        let state = Rc::new(RefCell::new(Vec::new()));
+                        for _ in 0..2 {
+                            let msg: i32 = ctx.receive().await?;
+                            state.borrow_mut().push(msg);
+                        }
+                        let state = state.borrow().clone();
+                        Ok(state)
+                    }
+                );
+                aref.tell(1);
+                aref.tell(2);
+                assert_eq!(handle.await.unwrap().unwrap(), vec![1, 2]);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn local_mailbox() {
+        use std::{cell::RefCell, rc::Rc};
+
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let (aref, mut recv) = LocalMailbox.make_mailbox::<Rc<RefCell<Vec<i32>>>>();
+                let handle = crate::spawn_local(&LocalSpawner, async move {
+                    let mut count = 0;
+                    while let Ok(msg) = recv.receive().await {
+                        msg.borrow_mut().push(count);
+                        count += 1;
+                    }
+                    count
+                });
+                let state = Rc::new(RefCell::new(Vec::new()));
+                aref.tell(state.clone());
+                aref.try_tell(state.clone()).unwrap();
+                drop(aref);
+                assert_eq!(handle.await.unwrap(), 2);
+                assert_eq!(*state.borrow(), vec![0, 1]);
+                assert_eq!(Rc::strong_count(&state), 1);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn shutdown() {
+        use crate::{Shutdown, ShutdownToken};
+
+        let token = ShutdownToken::new();
+        let t = token.clone();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            ctx.set_shutdown_token(t);
+            let (_child, child_handle) = actor!(TokioMailbox, |ctx| {
+                let result: Result<()> = ctx.receive().await;
+                Ok(result.unwrap_err().is::<Shutdown>())
+            });
+            let first: i32 = ctx.receive().await?;
+            let err = ctx.receive().await.unwrap_err();
+            // teardown after being asked to stop
+            let child = child_handle.await??;
+            Ok((first, err.is::<Shutdown>(), ctx.is_shutting_down(), child))
+        });
+
+        aref.tell(1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!token.is_triggered());
+        token.trigger();
+        assert_eq!(handle.await.unwrap().unwrap(), (1, true, true, true));
+    }
+
+    #[tokio::test]
+    async fn dead_letters() {
+        use crate::{DeadLetter, DeadLetterReason};
+
+        let (sink, dead) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let letter: DeadLetter = ctx.receive().await?;
+            let reason = letter.reason;
+            Ok((*letter.message.downcast::<String>().unwrap(), reason))
+        });
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _: String = ctx.receive().await?;
+            Ok(())
+        });
+        let aref = aref.with_dead_letters(sink);
+        aref.tell("hello".to_owned());
+        handle.await.unwrap().unwrap();
+        aref.tell("world".to_owned());
+        assert_eq!(
+            dead.await.unwrap().unwrap(),
+            ("world".to_owned(), DeadLetterReason::Closed)
+        );
+    }
+
+    #[tokio::test]
+    async fn labeled() {
+        use crate::DeadLetter;
+
+        let (sink, dead) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let letter: DeadLetter = ctx.receive().await?;
+            Ok(letter.label)
+        });
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _: String = ctx.receive().await?;
+            Ok(())
+        });
+        let aref = aref.with_dead_letters(sink).labeled("db-writer");
+        let id = aref.id();
+        assert_eq!(aref.clone().label(), Some("db-writer"));
+        let mapped = aref.clone().contramap(|n: u32| n.to_string());
+        assert_eq!(mapped.label(), Some("db-writer"));
+        assert_eq!(mapped.id(), id);
+        assert_eq!(
+            format!("{:?}", aref),
+            format!("ActorRef({}, db-writer)", id)
+        );
+
+        mapped.tell(1);
+        handle.await.unwrap().unwrap();
+        let err = mapped.try_tell_labeled(2).unwrap_err();
+        assert_eq!(err.label(), Some("db-writer"));
+        assert_eq!(
+            err.to_string(),
+            "cannot send: message was not delivered to actor `db-writer`"
+        );
+        let SendError(msg) = err.into();
+        assert_eq!(msg, 2);
+        aref.tell("lost".to_owned());
+        assert_eq!(dead.await.unwrap().unwrap().as_deref(), Some("db-writer"));
+    }
+
+    #[tokio::test]
+    async fn no_refs_policy() {
+        use crate::{ActorConfig, NoRefsPolicy};
+
+        for (policy, expected) in [
+            (NoRefsPolicy::DrainThenStop, vec![1, 2, 3]),
+            (NoRefsPolicy::StopImmediately, vec![]),
+        ] {
+            let config = ActorConfig {
+                on_no_refs: policy,
+                ..ActorConfig::default()
+            };
+            let (aref, handle) = actor!(config = config, TokioMailbox, TokioSpawner, |ctx| {
+                let mut seen = Vec::new();
+                while let Ok(msg) = ctx.receive().await {
+                    seen.push(msg);
+                }
+                Ok(seen)
+            });
+            aref.tell_all([1u32, 2, 3]);
+            drop(aref);
+            assert_eq!(handle.await.unwrap().unwrap(), expected, "{:?}", policy);
+        }
+    }
+
+    #[tokio::test]
+    async fn spawn_child() {
+        use crate::Shutdown;
+        use tokio::sync::mpsc::unbounded_channel;
+
+        let (stopped_tx, mut stopped) = unbounded_channel();
+        let (refs_tx, mut refs) = unbounded_channel();
+        let (parent, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            for _ in 0..2 {
+                let stopped_tx = stopped_tx.clone();
+                let child = ctx.spawn_child(TokioMailbox, TokioSpawner, |mut ctx| async move {
+                    let err = ctx.receive().await.map(|_: ()| ()).unwrap_err();
+                    let _ = stopped_tx.send(err.is::<Shutdown>());
+                    Ok(())
+                });
+                let _ = refs_tx.send(child);
+            }
+            let msg: u32 = ctx.receive().await?;
+            Ok(msg)
+        });
+        // the children stay referenced, so only the parent can make them stop
+        let children = vec![refs.recv().await.unwrap(), refs.recv().await.unwrap()];
+        parent.tell(42);
+        assert_eq!(handle.await.unwrap().unwrap(), 42);
+        assert!(stopped.recv().await.unwrap());
+        assert!(stopped.recv().await.unwrap());
+        drop(children);
+    }
+
+    #[tokio::test]
+    async fn spawn_supervised() {
+        use crate::Directive;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let starts = Arc::new(AtomicUsize::new(0));
+        let child_starts = starts.clone();
+        let (_parent, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut restarts = 0;
+            ctx.set_supervisor(move |_err| {
+                restarts += 1;
+                if restarts <= 2 {
+                    Directive::Restart
+                } else {
+                    Directive::Escalate
+                }
+            });
+            let _child =
+                ctx.spawn_supervised(TokioMailbox, TokioSpawner, move |_ctx: Context<()>| {
+                    let starts = child_starts.clone();
+                    async move {
+                        starts.fetch_add(1, Ordering::SeqCst);
+                        Err::<(), _>(anyhow::anyhow!("boom"))
+                    }
+                });
+            let _: u32 = ctx.receive().await?;
+            Ok(())
+        });
+        let err = handle.await.unwrap().unwrap_err();
+        assert_eq!(err.root_cause().to_string(), "boom");
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn circuit_breaker() {
+        use crate::{BreakerConfig, BreakerOpen, BreakerState, CircuitBreaker};
+
+        let (target, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            while let Ok((ok, reply)) = ctx.receive().await {
+                let reply: ActorRef<u32> = reply;
+                if ok {
+                    reply.tell(42);
+                }
+            }
+            Ok(())
+        });
+        let breaker = CircuitBreaker::wrap(
+            target,
+            BreakerConfig {
+                failure_threshold: 2,
+                cooldown: Duration::from_millis(100),
+                timeout: Duration::from_secs(1),
+            },
+        );
+        let ask = |ok: bool| {
+            let breaker = breaker.clone();
+            async move {
+                breaker
+                    .ask(TokioMailbox, &TokioSpawner, |reply| (ok, reply))
+                    .await
+            }
+        };
+
+        ask(false).await.unwrap_err();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        ask(false).await.unwrap_err();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        ask(true)
+            .await
+            .unwrap_err()
+            .downcast::<BreakerOpen>()
+            .unwrap();
+
+        // a failed trial opens the breaker again
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        ask(false)
+            .await
+            .unwrap_err()
+            .downcast::<NoActorRef>()
+            .unwrap();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        ask(true)
+            .await
+            .unwrap_err()
+            .downcast::<BreakerOpen>()
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(ask(true).await.unwrap(), 42);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn rate_limited() {
+        use crate::{RateLimitError, RateLimited};
+        use std::time::Instant;
+
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut count = 0;
+            while let Ok(()) = ctx.receive().await {
+                count += 1;
+            }
+            Ok(count)
+        });
+        let limited = RateLimited::wrap(aref, 10, Arc::new(TokioSpawner));
+        let start = Instant::now();
+        for _ in 0..10 {
+            limited.try_tell(()).unwrap();
+        }
+        match limited.try_tell(()) {
+            Err(RateLimitError::RateLimited(())) => {}
+            x => panic!("unexpected result: {:?}", x),
+        }
+        for _ in 0..5 {
+            limited.send(()).await.unwrap();
+        }
+        // one permit per 100ms after the initial burst
+        assert!(start.elapsed() >= Duration::from_millis(450));
+        drop(limited);
+        assert_eq!(handle.await.unwrap().unwrap(), 15);
+
+        // the permits are replenished according to the spawner’s clock
+        #[derive(Clone)]
+        struct Manual(Arc<Mutex<Instant>>);
+        impl crate::Clock for Manual {
+            fn now(&self) -> Instant {
+                *self.0.lock()
+            }
+            fn delay(&self, dur: Duration) -> crate::DelayBox {
+                TokioSpawner.delay(dur)
+            }
+        }
+        let clock = Manual(Arc::new(Mutex::new(Instant::now())));
+        let (aref, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            while let Ok(()) = ctx.receive().await {}
+            Ok(())
+        });
+        let spawner = crate::WithClock(TokioSpawner, clock.clone());
+        let limited = RateLimited::wrap(aref, 10, Arc::new(spawner));
+        for _ in 0..10 {
+            limited.try_tell(()).unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(limited.try_tell(()).is_err());
+        *clock.0.lock() += Duration::from_millis(100);
+        limited.try_tell(()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dedup() {
+        use crate::{Dedup, DedupWindow};
+        use std::time::Instant;
+
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut seen = Vec::new();
+            while let Ok((id, payload)) = ctx.receive().await {
+                let payload: &str = payload;
+                seen.push((id, payload));
+            }
+            Ok(seen)
+        });
+        let aref = Dedup::wrap(
+            aref,
+            |(id, _): &(u32, &str)| *id,
+            DedupWindow::Count(2),
+            Arc::new(TokioSpawner),
+        );
+        aref.tell((1, "a"));
+        aref.tell((1, "again"));
+        aref.tell((2, "b"));
+        aref.tell((3, "c"));
+        // only the two most recent ids are remembered
+        aref.tell((1, "late"));
+        aref.tell((3, "again"));
+        drop(aref);
+        assert_eq!(
+            handle.await.unwrap().unwrap(),
+            vec![(1, "a"), (2, "b"), (3, "c"), (1, "late")]
+        );
+
+        // seeing an id again keeps it from being evicted
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut seen = Vec::new();
+            while let Ok((id, payload)) = ctx.receive().await {
+                let payload: &str = payload;
+                seen.push((id, payload));
+            }
+            Ok(seen)
+        });
+        let aref = Dedup::wrap(
+            aref,
+            |(id, _): &(u32, &str)| *id,
+            DedupWindow::Count(2),
+            Arc::new(TokioSpawner),
+        );
+        aref.tell((1, "a"));
+        aref.tell((2, "b"));
+        aref.tell((1, "again"));
+        aref.tell((3, "c"));
+        aref.tell((1, "still"));
+        aref.tell((2, "late"));
+        drop(aref);
+        assert_eq!(
+            handle.await.unwrap().unwrap(),
+            vec![(1, "a"), (2, "b"), (3, "c"), (2, "late")]
+        );
+
+        // time windows follow the spawner’s clock, also for messages that wait for room
+        #[derive(Clone)]
+        struct Manual(Arc<Mutex<Instant>>);
+        impl crate::Clock for Manual {
+            fn now(&self) -> Instant {
+                *self.0.lock()
+            }
+            fn delay(&self, dur: Duration) -> crate::DelayBox {
+                TokioSpawner.delay(dur)
+            }
+        }
+        let clock = Manual(Arc::new(Mutex::new(Instant::now())));
+        let mailbox = TokioBoundedMailbox::new(1, OnFull::DropNewest);
+        let (aref, handle) = actor!(mailbox, TokioSpawner, |ctx| {
+            let mut seen = Vec::new();
+            while let Ok((id, payload)) = ctx.receive().await {
+                let payload: &str = payload;
+                seen.push((id, payload));
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(seen)
+        });
+        let spawner = crate::WithClock(TokioSpawner, clock.clone());
+        let aref = Dedup::wrap(
+            aref,
+            |(id, _): &(u32, &str)| *id,
+            DedupWindow::Time(Duration::from_secs(1)),
+            Arc::new(spawner),
+        );
+        aref.send((1, "a")).await.unwrap();
+        aref.send((2, "b")).await.unwrap();
+        aref.send((1, "again")).await.unwrap();
+        *clock.0.lock() += Duration::from_secs(2);
+        aref.send((1, "expired")).await.unwrap();
+        drop(aref);
+        assert_eq!(
+            handle.await.unwrap().unwrap(),
+            vec![(1, "a"), (2, "b"), (1, "expired")]
+        );
+    }
+
+    #[tokio::test]
+    async fn abort() {
+        let (_aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _: &mut Context<()> = &mut ctx;
+            let mut spins = 0u64;
+            while spins < u64::MAX {
+                spins += 1;
+                tokio::task::yield_now().await;
+            }
+            Ok(spins)
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!handle.is_finished());
+        handle.abort();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(handle.is_finished());
+        let err = handle.await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&SpawnError::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn address() {
+        use crate::Reply;
+
+        enum Command {
+            Add(u32, u32),
+            Clear,
+            Sum(Reply<u32>),
+            Contains(u32, Reply<bool>),
+        }
+
+        address! {
+            struct SetAddr(Command) {
+                fn add(a: u32, b: u32) => Command::Add;
+                fn clear() => Command::Clear;
+                fn sum() -> u32 => Command::Sum;
+                fn contains(n: u32) -> bool => Command::Contains;
+            }
+        }
+
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut set = Vec::new();
+            while let Ok(cmd) = ctx.receive().await {
+                match cmd {
+                    Command::Add(a, b) => set.extend([a, b]),
+                    Command::Clear => set.clear(),
+                    Command::Sum(reply) => drop(reply.send(set.iter().sum())),
+                    Command::Contains(n, reply) => drop(reply.send(set.contains(&n))),
+                }
+            }
+            Ok(())
+        });
+        let addr = SetAddr::from(aref);
+        addr.add(1, 2);
+        assert_eq!(addr.sum().await.unwrap(), 3);
+        assert!(addr.contains(2).await.unwrap());
+        addr.clear();
+        assert!(!addr.clone().contains(2).await.unwrap());
+        drop(addr);
+        handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn actor_system() {
+        use crate::{ActorSystem, ShutdownReport};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let system = ActorSystem::new(TokioMailbox, TokioSpawner, Duration::from_secs(5));
+        let stopped = Arc::new(AtomicUsize::new(0));
+        let processed = Arc::new(AtomicUsize::new(0));
+        let refs = (0..3)
+            .map(|_| {
+                let stopped = stopped.clone();
+                let processed = processed.clone();
+                system.spawn(|mut ctx: Context<u32>| async move {
+                    let err = loop {
+                        match ctx.receive().await {
+                            Ok(_) => {
+                                tokio::time::sleep(Duration::from_millis(10)).await;
+                                processed.fetch_add(1, Ordering::SeqCst);
+                            }
+                            Err(err) => break err,
+                        }
+                    };
+                    if err.is::<NoActorRef>() {
+                        stopped.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for aref in &refs {
+            aref.tell_all([1, 2, 3]);
+        }
+        assert_eq!(
+            system.shutdown().await,
+            ShutdownReport {
+                stopped: 3,
+                aborted: 0
+            }
+        );
+        assert_eq!(stopped.load(Ordering::SeqCst), 3);
+        // the queued messages were processed before stopping
+        assert_eq!(processed.load(Ordering::SeqCst), 9);
+
+        // an actor not receiving sees the token triggered after the timeout
+        let system = ActorSystem::new(TokioMailbox, TokioSpawner, Duration::from_millis(100));
+        let (tx, rx) = oneshot::channel();
+        let _waiting = system.spawn(|ctx: Context<u32>| async move {
+            while !ctx.is_shutting_down() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            let _ = tx.send(());
+            Ok(())
+        });
+        assert_eq!(
+            system.shutdown().await,
+            ShutdownReport {
+                stopped: 1,
+                aborted: 0
+            }
+        );
+        rx.await.unwrap();
+
+        // an actor ignoring the signal is aborted after another timeout
+        let system = ActorSystem::new(TokioMailbox, TokioSpawner, Duration::from_millis(100));
+        let _stubborn = system.spawn(|_ctx: Context<u32>| async move {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        });
+        assert_eq!(
+            system.shutdown().await,
+            ShutdownReport {
+                stopped: 0,
+                aborted: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn receive_or() {
+        use crate::Either;
+
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut events = Vec::new();
+            let mut sleep = pin!(tokio::time::sleep(Duration::from_millis(100)));
+            while events.len() < 3 {
+                match ctx.receive_or(&mut sleep).await? {
+                    Either::Left(msg) => events.push(msg),
+                    Either::Right(()) => {
+                        events.push("timer");
+                        sleep.set(tokio::time::sleep(Duration::from_secs(10)));
+                    }
+                }
+            }
+            Ok(events)
+        });
+        aref.tell("first");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        aref.tell("second");
+        assert_eq!(
+            handle.await.unwrap().unwrap(),
+            vec!["first", "timer", "second"]
+        );
+    }
+
+    #[test]
+    fn on_reject() {
+        let rejected = Arc::new(Mutex::new(Vec::new()));
+        let seen = rejected.clone();
+        let mailbox = TokioBoundedMailbox::new(1, OnFull::DropNewest)
+            .on_reject(move |msg: &u32| seen.lock().push(*msg));
+        let (aref, _recv) = mailbox.make_mailbox_for();
+        aref.tell_all([1, 2, 3]);
+        assert_eq!(mailbox.rejected_count(), 2);
+        assert_eq!(*rejected.lock(), vec![2, 3]);
+
+        let seen = rejected.clone();
+        let mailbox = TokioBoundedMailbox::new(1, OnFull::DropOldest)
+            .on_reject(move |msg: &u32| seen.lock().push(*msg));
+        let (aref, _recv) = mailbox.make_mailbox_for();
+        aref.tell_all([4, 5, 6]);
+        assert_eq!(mailbox.rejected_count(), 2);
+        assert_eq!(*rejected.lock(), vec![2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn boxed() {
+        use crate::{BoxedMailbox, BoxedSpawner, WithTimeout};
+
+        // as if read from a configuration file
+        for limited in [false, true] {
+            let spawner: BoxedSpawner = if limited {
+                Box::new(WithTimeout(TokioSpawner, Duration::from_secs(10)))
+            } else {
+                Box::new(TokioSpawner)
+            };
+            let mailbox = if limited {
+                BoxedMailbox::new(TokioBoundedMailbox::new(1, OnFull::DropNewest))
+            } else {
+                BoxedMailbox::new(TokioMailbox)
+            };
+            let (aref, handle) = actor!(mailbox, spawner, |ctx| {
+                let msg: u32 = ctx.receive().await?;
+                Ok(msg + 1)
+            });
+            aref.tell(1);
+            assert_eq!(handle.await.unwrap().unwrap(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn mailbox_factory() {
+        use crate::MailboxFactory;
+
+        let factory = MailboxFactory::new(TokioBoundedMailbox::new(1, OnFull::DropNewest));
+        let make = factory.factory::<u32>();
+        let (aref, mut recv) = make();
+        aref.tell(1);
+        aref.try_tell(2).unwrap_err();
+        assert_eq!(aref.capacity(), Some(1));
+        assert_eq!(recv.receive().await.unwrap(), 1);
+
+        let (aref, handle) = actor!(factory.clone(), TokioSpawner, |ctx| {
+            let msg: String = ctx.receive().await?;
+            Ok(msg)
+        });
+        aref.tell("typed".to_owned());
+        assert_eq!(handle.await.unwrap().unwrap(), "typed");
+    }
+
+    #[tokio::test]
+    async fn stop_signal() {
+        use crate::Shutdown;
+
+        let (release, released) = oneshot::channel::<()>();
+        let mailbox = TokioBoundedMailbox::new(1, OnFull::DropNewest);
+        let (aref, handle) = actor!(mailbox, TokioSpawner, |ctx| {
+            let _ = released.await;
+            let mut received = 0;
+            let err = loop {
+                match ctx.receive().await {
+                    Ok(()) => received += 1,
+                    Err(err) => break err,
+                }
+            };
+            Ok((received, err.is::<Shutdown>()))
+        });
+        aref.tell(());
+        aref.try_tell(()).unwrap_err();
+        aref.contramap(|()| ()).stop_signal();
+        release.send(()).unwrap();
+        assert_eq!(handle.await.unwrap().unwrap(), (0, true));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stop_signal_race() {
+        use crate::Shutdown;
+
+        for _ in 0..500 {
+            let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+                let result: Result<()> = ctx.receive().await;
+                Ok(result.unwrap_err().is::<Shutdown>())
+            });
+            // signal from another thread while the actor is about to wait for its first message
+            let stopper = aref.clone();
+            std::thread::spawn(move || stopper.stop_signal());
+            let stopped = tokio::time::timeout(Duration::from_secs(5), handle).await;
+            assert!(stopped
+                .expect("actor missed the stop signal")
+                .unwrap()
+                .unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn spawn_actor() {
+        use crate::{spawn_actor, Actor};
+
+        struct Summer(u32);
+
+        impl Actor for Summer {
+            type Message = u32;
+
+            async fn handle(&mut self, msg: u32, _ctx: &mut Context<u32>) -> Result<()> {
+                anyhow::ensure!(msg > 0, "zero");
+                self.0 += msg;
+                Ok(())
+            }
+        }
+
+        let (aref, handle) = spawn_actor(Summer(0), TokioMailbox, TokioSpawner);
+        aref.tell_all([1, 2, 3]);
+        drop(aref);
+        assert_eq!(handle.await.unwrap().unwrap().0, 6);
+
+        let (aref, handle) = spawn_actor(Summer(0), TokioMailbox, TokioSpawner);
+        aref.tell_all([1, 0, 3]);
+        let err = handle.await.unwrap().map(|s| s.0).unwrap_err();
+        assert_eq!(err.to_string(), "zero");
+    }
+
+    #[tokio::test]
+    async fn deliver_reliably() {
+        use crate::{deliver_reliably, Ack, AckStatus, Envelope, NotAcknowledged};
+
+        // stops with the message in hand, without acking it
+        let (dying, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _: Envelope<String> = ctx.receive().await?;
+            Ok(())
+        });
+        let (tx, rx) = oneshot::channel();
+        let (worker, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let Envelope { msg, ack } = ctx.receive().await?;
+            let _ = tx.send(msg);
+            ack.complete();
+            Ok(())
+        });
+        let workers = vec![dying, worker];
+        let idx = deliver_reliably(
+            &TokioSpawner,
+            &workers,
+            "job".to_owned(),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(idx, 1);
+        assert_eq!(rx.await.unwrap(), "job");
+
+        // both workers are gone now
+        let err = deliver_reliably(
+            &TokioSpawner,
+            &workers,
+            "job".to_owned(),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap_err();
+        err.downcast::<NotAcknowledged>().unwrap();
+
+        let (ack, status) = Ack::new();
+        ack.nack();
+        assert_eq!(status.await, AckStatus::Nacked);
+    }
+
+    #[tokio::test]
+    async fn forward() {
+        let (tx, rx) = oneshot::channel();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let a: u32 = ctx.receive().await?;
+            let b = ctx.receive().await?;
+            let _ = tx.send((a, b));
+            Ok(())
+        });
+        let (sink, _sink_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _: crate::DeadLetter = ctx.receive().await?;
+            Ok(())
+        });
+        let wrapped = aref.with_dead_letters(sink).labeled("wrapped");
+        wrapped.forward(1).unwrap();
+        wrapped.forward(2).unwrap();
+        assert_eq!(rx.await.unwrap(), (1, 2));
+        handle.await.unwrap().unwrap();
+
+        let err = wrapped.forward(3).unwrap_err();
+        assert_eq!(err.0, 3);
+
+        // closing the outer reference also stops forwarding
+        let (aref, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _: u32 = ctx.receive().await?;
+            Ok(())
+        });
+        let wrapped = aref.labeled("x");
+        wrapped.close();
+        assert_eq!(wrapped.forward(4).unwrap_err().0, 4);
+
+        // as does closing a reference in the middle of the chain
+        let (aref, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _: u32 = ctx.receive().await?;
+            Ok(())
+        });
+        let middle = aref.labeled("middle");
+        let outer = middle.clone().labeled("outer");
+        outer.forward(5).unwrap();
+        middle.close();
+        assert_eq!(outer.forward(6).unwrap_err().0, 6);
+    }
+
+    #[tokio::test]
+    async fn shared_runtime() {
+        use crate::SharedRuntime;
+        use std::sync::atomic::AtomicUsize;
+
+        let shared = SharedRuntime::new(TokioSpawner, 2);
+        let handles = (0..100u32)
+            .map(|i| {
+                let (aref, handle) = actor!(TokioMailbox, shared.clone(), |ctx| {
+                    let mut sum = 0;
+                    for _ in 0..10 {
+                        sum += ctx.receive().await?;
+                    }
+                    Ok(sum)
+                });
+                for n in 0..10 {
+                    aref.tell(i * n);
+                }
+                handle
+            })
+            .collect::<Vec<_>>();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.await.unwrap().unwrap(), i as u32 * 45);
+        }
+
+        // a busy actor lets the others on its driver run after every message
+        let shared = SharedRuntime::new(TokioSpawner, 1);
+        let busy_count = Arc::new(AtomicUsize::new(0));
+        let (start_busy, busy_go) = oneshot::channel::<()>();
+        let (start_other, other_go) = oneshot::channel::<()>();
+        let count = busy_count.clone();
+        let (busy, busy_handle) = actor!(TokioMailbox, shared.clone(), |ctx| {
+            busy_go.await?;
+            for _ in 0..1000 {
+                let _: () = ctx.receive().await?;
+                count.fetch_add(1, Ordering::SeqCst);
+            }
+            Ok(())
+        });
+        let count = busy_count.clone();
+        let (other, other_handle) = actor!(TokioMailbox, shared.clone(), |ctx| {
+            other_go.await?;
+            let _: () = ctx.receive().await?;
+            Ok(count.load(Ordering::SeqCst))
+        });
+        busy.tell_all(std::iter::repeat_n((), 1000));
+        other.tell(());
+        start_busy.send(()).unwrap();
+        start_other.send(()).unwrap();
+        assert!(other_handle.await.unwrap().unwrap() < 10);
+        busy_handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn flush() {
+        use crate::Reply;
+        use std::sync::atomic::AtomicUsize;
+
+        enum Msg {
+            Add(usize),
+            Flush(Reply<()>),
+        }
+
+        let sum = Arc::new(AtomicUsize::new(0));
+        let total = sum.clone();
+        let (aref, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            while let Ok(msg) = ctx.receive().await {
+                match msg {
+                    Msg::Add(n) => {
+                        tokio::time::sleep(Duration::from_millis(1)).await;
+                        total.fetch_add(n, Ordering::SeqCst);
+                    }
+                    Msg::Flush(reply) => {
+                        let _ = reply.send(());
+                    }
+                }
+            }
+            Ok(())
+        });
+        for n in 1..=20 {
+            aref.tell(Msg::Add(n));
+        }
+        aref.flush(Msg::Flush).await.unwrap();
+        assert_eq!(sum.load(Ordering::SeqCst), 210);
+
+        aref.close();
+        let err = aref.flush(Msg::Flush).await.unwrap_err();
+        err.downcast::<SendError<()>>().unwrap();
+
+        // the actor stops mid-queue, so the marker is discarded without an answer
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            match ctx.receive().await? {
+                Msg::Add(n) => Ok(n),
+                Msg::Flush(_) => anyhow::bail!("flushed too early"),
+            }
+        });
+        aref.tell_all([Msg::Add(1), Msg::Add(2)]);
+        let err = aref.flush(Msg::Flush).await.unwrap_err();
+        err.downcast::<NoActorRef>().unwrap();
+        assert_eq!(handle.await.unwrap().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn typed_result() {
+        #[derive(Debug, PartialEq)]
+        struct Summary {
+            count: usize,
+        }
+
+        let (aref, text) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let name: String = ctx.receive().await?;
+            Ok(format!("Hello {}!", name))
+        });
+        aref.tell("Fred".to_owned());
+        let text: String = text.join().await.unwrap();
+        assert_eq!(text, "Hello Fred!");
+
+        let (aref, summary) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut count = 0;
+            while let Ok(()) = ctx.receive().await {
+                count += 1;
+            }
+            Ok(Summary { count })
+        });
+        aref.tell_all(vec![(), (), ()]);
+        drop(aref);
+        assert_eq!(summary.join().await.unwrap(), Summary { count: 3 });
+
+        let (aref, failed) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let n: u32 = ctx.receive().await?;
+            if n == 0 {
+                anyhow::bail!("zero");
+            }
+            Ok(n)
+        });
+        aref.tell(0);
+        assert_eq!(failed.join().await.unwrap_err().to_string(), "zero");
+
+        let (_aref, aborted) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let n: u32 = ctx.receive().await?;
+            Ok(vec![n])
+        });
+        aborted.abort();
+        let err = aborted.join().await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&SpawnError::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn peek() {
+        let (tx, rx) = oneshot::channel();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut seen = Vec::new();
+            // wait for the first message without taking it
+            while ctx.peek().is_none() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            seen.push(*ctx.peek().unwrap());
+            // not consumed, so peeking again shows the same message
+            seen.push(*ctx.peek().unwrap());
+            let first: u32 = ctx.consume().unwrap();
+            seen.push(first);
+            // peeked messages can be stashed once consumed, and unstashed ones come first
+            let second = ctx.receive().await?;
+            ctx.stash(second);
+            assert_eq!(ctx.peek(), Some(&3));
+            ctx.unstash_all();
+            seen.push(ctx.receive().await?);
+            seen.push(ctx.receive().await?);
+            let _ = tx.send(seen);
+            Ok(ctx.peek().is_none())
+        });
+        aref.tell_all(vec![1, 2, 3]);
+        assert_eq!(rx.await.unwrap(), vec![1, 1, 1, 2, 3]);
+        assert!(handle.await.unwrap().unwrap());
+
+        // nothing to peek at once receive would fail, even with a message at hand
+        let token = crate::ShutdownToken::new();
+        let t = token.clone();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            ctx.set_shutdown_token(t);
+            while ctx.peek().is_none() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            token.trigger();
+            let peeked = ctx.peek().copied();
+            let result: Result<u32> = ctx.receive().await;
+            Ok((peeked, result.unwrap_err().is::<crate::Shutdown>()))
+        });
+        aref.tell(1);
+        assert_eq!(handle.await.unwrap().unwrap(), (None, true));
+    }
+
+    #[tokio::test]
+    async fn recv() {
+        use crate::{RecvResult, ShutdownToken};
+
+        let (tx, rx) = oneshot::channel();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut sum = 0;
+            loop {
+                match ctx.recv().await {
+                    RecvResult::Message(n) => sum += n,
+                    // keep going after the last sender is gone, until the work is done
+                    RecvResult::Closed => break,
+                    RecvResult::Failed(err) => return Err(err),
+                }
+            }
+            let closed_again = matches!(ctx.recv().await, RecvResult::Closed);
+            let _ = tx.send(sum);
+            Ok(closed_again)
+        });
+        aref.tell_all(vec![1u32, 2, 3]);
+        drop(aref);
+        assert_eq!(rx.await.unwrap(), 6);
+        assert!(handle.await.unwrap().unwrap());
+
+        let token = ShutdownToken::new();
+        let mut ctx = Context::<u32>::new(TokioMailbox, Arc::new(TokioSpawner));
+        ctx.set_shutdown_token(token.clone());
+        let _aref = ctx.me();
+        token.trigger();
+        match ctx.recv().await {
+            RecvResult::Failed(err) => assert!(err.is::<crate::Shutdown>()),
+            x => panic!("unexpected result: {:?}", x),
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatcher() {
+        use crate::{AnyMailbox, DeadLetter, DeadLetterReason, Dispatcher};
+
+        #[derive(Debug, PartialEq)]
+        struct Foo(u32);
+        #[derive(Debug, PartialEq)]
+        struct Bar(&'static str);
+
+        let (letters_tx, letters_rx) = oneshot::channel();
+        let (sink, _sink_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let letter: DeadLetter = ctx.receive().await?;
+            let _ = letters_tx.send(letter);
+            Ok(())
+        });
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let (foos, bars) = (seen.clone(), seen.clone());
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            Dispatcher::new()
+                .on(move |foo: Foo| {
+                    foos.lock().push(format!("{:?}", foo));
+                    Ok(())
+                })
+                .on(move |bar: Bar| {
+                    bars.lock().push(format!("{:?}", bar));
+                    Ok(())
+                })
+                .dead_letters(sink)
+                .run(ctx)
+                .await
+        });
+        let plugin = AnyMailbox::from(aref);
+        plugin.tell(Foo(1));
+        plugin.tell(Bar("x"));
+        plugin.typed::<Foo>().tell(Foo(2));
+        plugin.tell(3u8);
+
+        let letter = letters_rx.await.unwrap();
+        assert_eq!(letter.reason, DeadLetterReason::Unhandled);
+        assert_eq!(letter.type_name, "u8");
+        assert_eq!(letter.message.downcast_ref::<u8>(), Some(&3));
+        assert_eq!(*seen.lock(), vec!["Foo(1)", "Bar(\"x\")", "Foo(2)"]);
+
+        drop(plugin);
+        handle
+            .await
+            .unwrap()
+            .unwrap_err()
+            .downcast::<NoActorRef>()
+            .unwrap();
+    }
+
+    #[cfg(feature = "with_spill")]
+    #[tokio::test]
+    async fn spill() {
+        use crate::SpillMailbox;
+
+        let dir = std::env::temp_dir().join(format!("acto-spill-{}", std::process::id()));
+        let (tx, rx) = oneshot::channel::<()>();
+        let (aref, handle) = actor!(SpillMailbox::new(4, &dir), TokioSpawner, |ctx| {
+            let _ = rx.await;
+            let mut received = Vec::new();
+            while let Ok(msg) = ctx.receive().await {
+                received.push(msg);
+            }
+            Ok(received)
+        });
+        aref.tell_all((0..50).map(|n| format!("msg {}", n)));
+        assert_eq!(aref.len(), 50);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        aref.tell_all((50..60).map(|n| format!("msg {}", n)));
+        drop(aref);
+        let received = handle.await.unwrap().unwrap();
+        let expected = (0..60).map(|n| format!("msg {}", n)).collect::<Vec<_>>();
+        assert_eq!(received, expected);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        // the file is removed when the actor stops, even if ActorRefs are left
+        let (tx, rx) = oneshot::channel::<()>();
+        let (aref, handle) = actor!(SpillMailbox::new(4, &dir), TokioSpawner, |ctx| {
+            let _ = rx.await;
+            let _: String = ctx.receive().await?;
+            Ok(())
+        });
+        aref.tell_all((0..50).map(|n| format!("msg {}", n)));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        tx.send(()).unwrap();
+        handle.await.unwrap().unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        assert!(aref.try_tell("late".to_owned()).is_err());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn become_toggle() {
+        use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+        type Out = UnboundedSender<String>;
+        type Handler = Box<
+            dyn FnMut(
+                    &'static str,
+                    &mut Context<&'static str>,
+                ) -> futures::future::Ready<Result<()>>
+                + Send,
+        >;
+
+        fn plain(out: Out) -> Handler {
+            Box::new(move |msg, ctx| {
+                match msg {
+                    "toggle" => ctx.r#become(shouting(out.clone())),
+                    "nest" => ctx.become_stacked(shouting(out.clone())),
+                    "unnest" => ctx.unbecome(),
+                    msg => out.send(msg.to_owned()).unwrap(),
+                }
+                futures::future::ready(Ok(()))
+            })
+        }
+
+        fn shouting(out: Out) -> Handler {
+            Box::new(move |msg, ctx| {
+                match msg {
+                    "toggle" => ctx.r#become(plain(out.clone())),
+                    "unnest" => ctx.unbecome(),
+                    msg => out.send(msg.to_uppercase()).unwrap(),
+                }
+                futures::future::ready(Ok(()))
+            })
+        }
+
+        let (out, mut rx) = unbounded_channel();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            ctx.r#become(plain(out));
+            ctx.run_behavior().await
+        });
+        aref.tell_all([
+            "a", "toggle", "b", "toggle", "c", "nest", "d", "unnest", "e",
+        ]);
+        for expected in ["a", "B", "c", "D", "e"] {
+            assert_eq!(rx.recv().await.unwrap(), expected);
+        }
+
+        // dropping the last behavior ends the loop
+        aref.tell("unnest");
+        handle.await.unwrap().unwrap();
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn watchable() {
+        use crate::Watchable;
+
+        let state = Watchable::new(0u32);
+        let watchers = (0..3).map(|_| state.watch()).collect::<Vec<_>>();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            while let Ok(n) = ctx.receive().await {
+                state.modify(|s| *s += n);
+            }
+            Ok(state)
+        });
+        let readers = watchers
+            .into_iter()
+            .map(|mut watcher| {
+                tokio::spawn(async move {
+                    while *watcher.borrow_and_update() < 6 {
+                        watcher.changed().await.unwrap();
+                    }
+                    *watcher.borrow()
+                })
+            })
+            .collect::<Vec<_>>();
+        aref.tell_all([1, 2, 3]);
+        for reader in readers {
+            assert_eq!(reader.await.unwrap(), 6);
+        }
+
+        let state = {
+            drop(aref);
+            handle.await.unwrap().unwrap()
+        };
+        let mut watcher = state.watch();
+        assert!(!watcher.has_changed());
+        state.set(7);
+        assert!(watcher.has_changed());
+        watcher.changed().await.unwrap();
+        assert_eq!(*watcher.borrow(), 7);
+        assert_eq!(state.watcher_count(), 1);
+        drop(state);
+        watcher
+            .changed()
+            .await
+            .unwrap_err()
+            .downcast::<NoActorRef>()
+            .unwrap();
+        assert_eq!(*watcher.borrow(), 7);
+    }
+
+    #[cfg(feature = "with_deadlock_detection")]
+    #[tokio::test]
+    async fn deadlock() {
+        use crate::Deadlock;
+
+        enum AMsg {
+            Start(ActorRef<(ActorRef<AMsg>, ActorRef<u32>)>),
+            Get(ActorRef<u32>),
+        }
+
+        let (tx, rx) = oneshot::channel();
+        let (b, _b_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let (a, reply): (ActorRef<AMsg>, ActorRef<u32>) = ctx.receive().await?;
+            let err = ctx.ask(&a, AMsg::Get).await.unwrap_err();
+            let _ = tx.send(err.downcast::<Deadlock>().unwrap());
+            reply.tell(42);
+            Ok(())
+        });
+        let (a, a_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let me = ctx.me();
+            match ctx.receive().await? {
+                AMsg::Start(b) => ctx.ask(&b, |reply| (me, reply)).await,
+                AMsg::Get(reply) => {
+                    reply.tell(0);
+                    Ok(0)
+                }
+            }
+        });
+        let (a_id, b_id) = (a.id(), b.id());
+        a.tell(AMsg::Start(b));
+        let deadlock = rx.await.unwrap();
+        assert_eq!(deadlock.cycle, vec![b_id, a_id, b_id]);
+        assert_eq!(
+            deadlock.to_string(),
+            format!("ask would deadlock: {} -> {} -> {}", b_id, a_id, b_id)
+        );
+        // the cycle was broken, so A got its reply
+        assert_eq!(a_handle.await.unwrap().unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn watchdog() {
+        let stuck = Arc::new(Mutex::new(Vec::new()));
+        let reports = stuck.clone();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            ctx.set_watchdog(Duration::from_millis(50), move |id, elapsed| {
+                reports.lock().push((id, elapsed))
+            });
+            while let Ok(millis) = ctx.receive().await {
+                tokio::time::sleep(Duration::from_millis(millis)).await;
+                if millis == 0 {
+                    // not waiting for the next message, but finished with this one
+                    ctx.done();
+                    tokio::time::sleep(Duration::from_millis(150)).await;
+                }
+            }
+            Ok(())
+        });
+        let id = aref.id();
+        aref.tell_all([10, 200, 10, 0]);
+        drop(aref);
+        handle.await.unwrap().unwrap();
+
+        let stuck = stuck.lock();
+        assert_eq!(stuck.len(), 1, "{:?}", stuck);
+        assert_eq!(stuck[0].0, id);
+        assert!(stuck[0].1 >= Duration::from_millis(50), "{:?}", stuck);
+    }
+
+    #[tokio::test]
+    async fn spawn_task() {
+        use crate::{SpawnError, TaskHandle};
+        use std::sync::atomic::AtomicBool;
+
+        let (tx, rx) = oneshot::channel::<TaskHandle<()>>();
+        let completed = Arc::new(AtomicBool::new(false));
+        let done = completed.clone();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            // feeding the result back to the actor
+            let me = ctx.me();
+            ctx.spawn_task(async move { me.tell(21) });
+            let n: u32 = ctx.receive().await?;
+
+            let task = ctx.spawn_task(async move {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                done.store(true, Ordering::SeqCst);
+            });
+            let _ = tx.send(task);
+            let _: u32 = ctx.receive().await?;
+            Ok(n * 2)
+        });
+        let task = rx.await.unwrap();
+        assert!(!task.is_finished());
+
+        aref.tell(0);
+        assert_eq!(handle.await.unwrap().unwrap(), 42);
+        let err = task.await.unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&SpawnError::Cancelled));
+        assert!(!completed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn tokio_sender() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            while let Ok(msg) = ctx.receive().await {
+                received.push(msg);
+            }
+            Ok(received)
+        });
+        let raw = aref.clone().labeled("raw").tokio_sender().unwrap();
+        aref.tell(1);
+        raw.send(2).unwrap();
+        aref.tell(3);
+        raw.send(4).unwrap();
+        assert_eq!(aref.len(), 0);
+        drop(aref);
+        assert_eq!(handle.await.unwrap().unwrap(), vec![1, 2, 3, 4]);
+        assert!(raw.is_closed());
+
+        let (bounded, _handle) = actor!(
+            TokioBoundedMailbox::new(1, OnFull::DropNewest),
+            TokioSpawner,
+            |ctx| {
+                let _: u32 = ctx.receive().await?;
+                Ok(())
+            }
+        );
+        assert!(bounded.tokio_sender().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn no_loss_on_last_drop() {
+        const SENDERS: usize = 4;
+        const MESSAGES: usize = 100;
+        for _ in 0..200 {
+            let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+                let mut count = 0;
+                loop {
+                    match ctx.receive().await {
+                        Ok(()) => count += 1,
+                        Err(err) if err.is::<NoActorRef>() => break,
+                        Err(err) => return Err(err),
+                    }
+                }
+                Ok(count)
+            });
+            let senders = (0..SENDERS)
+                .map(|_| {
+                    let aref = aref.clone();
+                    std::thread::spawn(move || {
+                        for _ in 0..MESSAGES {
+                            aref.tell(());
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            drop(aref);
+            for sender in senders {
+                sender.join().unwrap();
+            }
+            assert_eq!(handle.await.unwrap().unwrap(), SENDERS * MESSAGES);
+        }
+    }
+
+    #[tokio::test]
+    async fn registry() {
+        use crate::Registry;
+
+        let registry = Registry::new();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: String = ctx.receive().await?;
+            Ok(msg)
+        });
+        registry.register("greeter", aref);
+        assert!(registry.lookup::<i32>("greeter").is_none());
+        assert!(registry.lookup::<String>("nobody").is_none());
+        registry
+            .clone()
+            .lookup::<String>("greeter")
+            .unwrap()
+            .tell("hello".to_owned());
+        assert_eq!(handle.await.unwrap().unwrap(), "hello");
+        // the actor has stopped, so the entry is pruned
+        assert!(registry.lookup::<String>("greeter").is_none());
+        assert!(!registry.remove("greeter"));
+    }
+
+    async fn counter(mut ctx: Context<()>) -> Result<usize> {
+        let mut count = 0;
+        while ctx.receive().await.is_ok() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    #[tokio::test]
+    async fn router() {
+        use crate::Router;
+
+        for random in [false, true] {
+            let mut refs = Vec::new();
+            let mut handles = Vec::new();
+            for _ in 0..3 {
+                let (aref, handle) = actor!(TokioMailbox, TokioSpawner, fn counter(ctx));
+                refs.push(aref);
+                handles.push(handle);
+            }
+            let router = if random {
+                Router::random(refs)
+            } else {
+                Router::round_robin(refs)
+            };
+            let aref = router.as_actor_ref();
+            for _ in 0..300 {
+                aref.tell(());
+            }
+            drop(aref);
+            for handle in handles {
+                let count = handle.await.unwrap().unwrap();
+                if random {
+                    assert!((50..=150).contains(&count), "{}", count);
+                } else {
+                    assert_eq!(count, 100);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn least_loaded() {
+        use crate::{Mailbox, Router};
+
+        let (refs, _recvs): (Vec<_>, Vec<_>) =
+            (0..3).map(|_| TokioMailbox.make_mailbox::<()>()).unzip();
+        for _ in 0..4 {
+            refs[0].tell(());
+        }
+        let lens = refs.clone();
+        let router = Router::least_loaded(refs);
+        for _ in 0..8 {
+            router.tell(());
+        }
+        assert_eq!(lens.iter().map(|r| r.len()).collect::<Vec<_>>(), [4, 4, 4]);
+    }
+
+    #[tokio::test]
+    async fn broadcast() {
+        use crate::{Broadcast, Mailbox};
+
+        let (a, mut rx_a) = TokioMailbox.make_mailbox::<i32>();
+        let (b, rx_b) = TokioMailbox.make_mailbox::<i32>();
+        let (c, _rx_c) = TokioMailbox.make_mailbox::<i32>();
+        let mut broadcast = Broadcast::new();
+        broadcast.subscribe(a);
+        broadcast.subscribe(b);
+        broadcast.subscribe(c.clone());
+        broadcast.unsubscribe(&c);
+        assert_eq!(broadcast.len(), 2);
+
+        broadcast.tell(1);
+        drop(rx_b);
+        broadcast.tell(2);
+        assert_eq!(broadcast.len(), 1);
+        assert_eq!(rx_a.receive().await.unwrap(), 1);
+        assert_eq!(rx_a.receive().await.unwrap(), 2);
+        assert_eq!(c.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn pubsub() {
+        use crate::{pubsub_actor, PubSub};
+
+        async fn subscriber(mut ctx: Context<i32>, max: usize) -> Result<Vec<i32>> {
+            let mut received = Vec::new();
+            while received.len() < max {
+                match ctx.receive().await {
+                    Ok(msg) => received.push(msg),
+                    Err(_) => break,
+                }
+            }
+            Ok(received)
+        }
+
+        let topic = pubsub_actor(TokioMailbox, TokioSpawner);
+        let (a, ha) = actor!(TokioMailbox, TokioSpawner, fn subscriber(ctx, usize::MAX));
+        let (b, hb) = actor!(TokioMailbox, TokioSpawner, fn subscriber(ctx, 2));
+        topic.tell(PubSub::Subscribe(a));
+        topic.tell(PubSub::Subscribe(b));
+        topic.tell(PubSub::Publish(1));
+        topic.tell(PubSub::Publish(2));
+        assert_eq!(hb.await.unwrap().unwrap(), vec![1, 2]);
+        topic.tell(PubSub::Publish(3));
+        topic.tell(PubSub::Publish(4));
+        drop(topic);
+        assert_eq!(ha.await.unwrap().unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn select_fairness() {
+        use crate::{select, Either, Mailbox, Receiver};
+
+        let (a, rx_a) = TokioMailbox.make_mailbox::<u32>();
+        let (b, rx_b) = TokioMailbox.make_mailbox::<u32>();
+        let mut rx = select(rx_a, rx_b);
+        // the left side has a head start, then both are fed as fast as possible
+        a.tell_all(0..1000);
+        let feeders = [a, b].map(|aref| {
+            std::thread::spawn(move || {
+                for n in 0..100_000 {
+                    aref.tell(n);
+                }
+            })
+        });
+        for feeder in feeders {
+            feeder.join().unwrap();
+        }
+        let (mut left, mut right) = (0, 0);
+        for _ in 0..20_000 {
+            match rx.receive().await.unwrap() {
+                Either::Left(_) => left += 1,
+                Either::Right(_) => right += 1,
+            }
+        }
+        // strict alternation while both sides have messages
+        assert_eq!((left, right), (10_000, 10_000));
+
+        // right delivered last because left was empty, so left goes first now
+        let (a, rx_a) = TokioMailbox.make_mailbox::<u32>();
+        let (b, rx_b) = TokioMailbox.make_mailbox::<u32>();
+        let mut rx = select(rx_a, rx_b);
+        b.tell_all([1, 2]);
+        assert_eq!(rx.receive().await.unwrap(), Either::Right(1));
+        a.tell(1);
+        assert_eq!(rx.receive().await.unwrap(), Either::Left(1));
+        assert_eq!(rx.receive().await.unwrap(), Either::Right(2));
+    }
+
+    #[tokio::test]
+    async fn select() {
+        use crate::{select, BothClosed, Either, Mailbox, Receiver};
+
+        let (a, rx_a) = TokioMailbox.make_mailbox::<i32>();
+        let (b, rx_b) = TokioMailbox.make_mailbox::<String>();
+        let mut rx = select(rx_a, rx_b);
+        for i in 0..3 {
+            a.tell(i);
+            b.tell(i.to_string());
+        }
+        // both sides take turns
+        let mut received = Vec::new();
+        for _ in 0..4 {
+            received.push(rx.receive().await.unwrap());
+        }
+        assert_eq!(
+            received,
+            vec![
+                Either::Left(0),
+                Either::Right("0".to_owned()),
+                Either::Left(1),
+                Either::Right("1".to_owned()),
+            ]
+        );
+
+        drop(a);
+        assert_eq!(rx.receive().await.unwrap(), Either::Left(2));
+        assert_eq!(rx.receive().await.unwrap(), Either::Right("2".to_owned()));
+        assert!(!rx.left_closed());
+        b.tell("3".to_owned());
+        assert_eq!(rx.receive().await.unwrap(), Either::Right("3".to_owned()));
+        assert!(rx.left_closed());
+        assert!(!rx.right_closed());
+        drop(b);
+        assert!(rx.receive().await.unwrap_err().is::<BothClosed>());
+        assert!(rx.right_closed());
+    }
+
+    #[tokio::test]
+    // the interior mutability of ActorRef does not affect its hash
+    #[allow(clippy::mutable_key_type)]
+    async fn identity() {
+        use crate::Mailbox;
+        use std::collections::HashSet;
+
+        let (a, _rx_a) = TokioMailbox.make_mailbox::<i32>();
+        let (b, _rx_b) = TokioMailbox.make_mailbox::<i32>();
+        assert_eq!(a, a.clone());
+        assert_ne!(a, b);
+        let set = [a.clone(), b.clone(), a.clone()]
+            .iter()
+            .cloned()
+            .collect::<HashSet<_>>();
+        assert_eq!(set.len(), 2);
+        assert_eq!(a.clone().contramap(|s: String| s.len() as i32).id(), a.id());
+    }
+
+    #[tokio::test]
+    async fn weak() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let first: i32 = ctx.receive().await?;
+            let err = ctx.receive().await.unwrap_err();
+            Ok((first, err.is::<NoActorRef>()))
+        });
+        let weak = aref.downgrade();
+        weak.upgrade().unwrap().tell(1);
+        drop(aref);
+        // the weak reference does not keep the actor alive
+        assert_eq!(handle.await.unwrap().unwrap(), (1, true));
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[tokio::test]
+    async fn clones() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            let stopped = loop {
+                match ctx.receive().await {
+                    Ok(msg) => received.push(msg),
+                    Err(e) => break e.is::<NoActorRef>(),
+                }
+            };
+            Ok((received, stopped))
+        });
+        let other = aref.clone();
+        aref.tell(1);
+        drop(aref);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // still running since the other clone keeps it alive
+        other.tell(2);
+        drop(other);
+        assert_eq!(handle.await.unwrap().unwrap(), (vec![1, 2], true));
+    }
+
+    #[tokio::test]
+    async fn with_timeout() {
+        use crate::{Timeout, WithTimeout};
+        use std::sync::atomic::AtomicBool;
+
+        struct Guard(Arc<AtomicBool>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = Guard(dropped.clone());
+        let spawner = WithTimeout(TokioSpawner, Duration::from_millis(50));
+        let (_aref, handle) = actor!(TokioMailbox, spawner, |ctx| {
+            let _guard = guard;
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            let () = ctx.receive().await?;
+            Ok(())
+        });
+        let err = handle.await.unwrap_err();
+        assert!(err.is::<Timeout>(), "{}", err);
+        assert!(dropped.load(Ordering::SeqCst));
+
+        let spawner = WithTimeout(TokioSpawner, Duration::from_secs(10));
+        let (aref, handle) = actor!(TokioMailbox, spawner, |ctx| {
+            let msg: i32 = ctx.receive().await?;
+            Ok(msg)
+        });
+        aref.tell(42);
+        assert_eq!(handle.await.unwrap().unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn ask() {
+        let (aref, _join_handle) = actor!(TokioMailbox, TokioSpawner, fn actor(ctx));
+        let reply = aref
+            .ask_with(TokioMailbox, |r| ("Fred".to_owned(), r))
+            .await
+            .unwrap();
+        assert_eq!(reply, "Hello Fred!");
+
+        let (aref, _join_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: ActorRef<String> = ctx.receive().await?;
+            drop(msg);
+            Ok(())
+        });
+        aref.ask_with(TokioMailbox, |r| r)
+            .await
+            .unwrap_err()
+            .downcast::<NoActorRef>()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn ask_timeout() {
+        use crate::AskTimeout;
+
+        let (aref, _join_handle) = actor!(TokioMailbox, TokioSpawner, fn actor(ctx));
+        let reply = aref
+            .ask_timeout_with(
+                TokioMailbox,
+                &TokioSpawner,
+                |r| ("Fred".to_owned(), r),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply, "Hello Fred!");
+
+        let (aref, _join_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            // hold on to the reply ActorRef without answering
+            let _msg: ActorRef<String> = ctx.receive().await?;
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        });
+        aref.ask_timeout_with(
+            TokioMailbox,
+            &TokioSpawner,
+            |r| r,
+            Duration::from_millis(50),
+        )
+        .await
+        .unwrap_err()
+        .downcast::<AskTimeout>()
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn retry() {
+        use crate::{retry, Backoff, RetryPolicy};
+
+        let attempts = AtomicUsize::new(0);
+        let op = || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(anyhow::anyhow!("not yet")),
+                n => Ok(n),
+            }
+        };
+        let policy = RetryPolicy::new(5, Duration::from_millis(10), Backoff::Exponential);
+        assert_eq!(retry(&TokioSpawner, policy, op).await.unwrap(), 2);
+
+        attempts.store(0, Ordering::SeqCst);
+        let policy = RetryPolicy {
+            retry_if: |err| !err.to_string().contains("not"),
+            ..RetryPolicy::new(5, Duration::from_millis(10), Backoff::Fixed)
+        };
+        assert!(retry(&TokioSpawner, policy, op).await.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn priority() {
+        use crate::{MailboxFor, Priority, PriorityMailbox};
+
+        let mailbox = PriorityMailbox::new(|msg: &(Priority, u32)| msg.0);
+        let (aref, handle) = actor!(mailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            for _ in 0..3 {
+                received.push(ctx.receive().await?.1);
+            }
+            Ok(received)
+        });
+        aref.tell((Priority::Low, 1));
+        aref.tell((Priority::Normal, 2));
+        aref.tell((Priority::High, 3));
+        assert_eq!(handle.await.unwrap().unwrap(), vec![3, 2, 1]);
+
+        let mailbox = PriorityMailbox::new(|msg: &(Priority, u32)| msg.0).with_fairness(2);
+        let (aref, mut recv) = mailbox.make_mailbox_for();
+        for i in 1..=4 {
+            aref.tell((Priority::High, i));
+        }
+        aref.tell((Priority::Low, 5));
+        let mut received = Vec::new();
+        for _ in 0..5 {
+            received.push(recv.receive().await.unwrap().1);
+        }
+        assert_eq!(received, vec![1, 2, 5, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn send() {
+        let mailbox = TokioBoundedMailbox::new(1, OnFull::DropNewest);
+        let (aref, handle) = actor!(mailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            for _ in 0..3 {
+                received.push(ctx.receive().await?);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(received)
+        });
+        for i in 1..=3 {
+            aref.send(i).await.unwrap();
+        }
+        assert_eq!(handle.await.unwrap().unwrap(), vec![1, 2, 3]);
+        assert_eq!(aref.send(4).await.unwrap_err().into_inner(), 4);
+        let err = aref.labeled("sink").send(4).await.unwrap_err();
+        assert_eq!(err.label(), Some("sink"));
+
+        // a contramapped ActorRef waits for room just the same
+        let mailbox = TokioBoundedMailbox::new(1, OnFull::DropNewest);
+        let (aref, handle) = actor!(mailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            for _ in 0..3 {
+                received.push(ctx.receive().await?);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(received)
+        });
+        let mapped = aref.contramap(|s: &str| s.len());
+        for s in ["a", "bb", "ccc"] {
+            mapped.send(s).await.unwrap();
+        }
+        assert_eq!(handle.await.unwrap().unwrap(), vec![1, 2, 3]);
+
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: i32 = ctx.receive().await?;
+            Ok(msg)
+        });
+        aref.send(5).await.unwrap();
+        assert_eq!(handle.await.unwrap().unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn panicked() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: u32 = ctx.receive().await?;
+            if msg == 0 {
+                panic!("boom {}", msg);
+            }
+            Ok(msg)
+        });
+        aref.tell(0);
+        let err = handle.await.unwrap_err();
+        assert_eq!(err.to_string(), "actor task panicked: boom 0");
+        assert_eq!(
+            err.downcast::<SpawnError>().unwrap(),
+            SpawnError::Panicked("boom 0".to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled() {
+        let spawner = TokioRuntimeSpawner(Runtime::new().unwrap());
+        let handle = crate::spawn(&spawner, std::future::pending::<()>());
+        spawner.0.shutdown_background();
+        let err = handle.await.unwrap_err();
+        assert_eq!(err.downcast::<SpawnError>().unwrap(), SpawnError::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn traced() {
+        use crate::Traced;
+
+        let told = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(AtomicUsize::new(0));
+        let (t, r) = (told.clone(), received.clone());
+        let mailbox = Traced::new(TokioMailbox)
+            .on_tell(move |msg: &usize| {
+                t.fetch_add(*msg, Ordering::SeqCst);
+            })
+            .on_receive(move |msg: &usize| {
+                r.fetch_add(*msg, Ordering::SeqCst);
+            });
+        let (aref, handle) = actor!(mailbox, TokioSpawner, |ctx| {
+            let msg = ctx.receive().await?;
+            Ok(msg)
+        });
+        aref.tell(1);
+        aref.tell(2);
+        assert_eq!(told.load(Ordering::SeqCst), 3);
+        assert_eq!(handle.await.unwrap().unwrap(), 1);
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "with_tracing")]
+    #[tokio::test]
+    async fn with_span() {
+        use crate::WithSpan;
+        use tracing::{info_span, Span};
+        use tracing_subscriber::prelude::*;
+
+        let _guard = tracing_subscriber::registry().set_default();
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: WithSpan<i32> = ctx.receive().await?;
+            Ok(msg.in_span(|msg| (msg, Span::current().metadata().map(|m| m.name()))))
+        });
+        let aref = aref.with_current_span();
+        info_span!("request").in_scope(|| aref.tell(42));
+        assert_eq!(handle.await.unwrap().unwrap(), (42, Some("request")));
+    }
+
+    #[cfg(feature = "with_tracing")]
+    #[tokio::test]
+    async fn log_errors() {
+        use crate::ActorConfig;
+        use tracing::{field::Field, Event, Level, Subscriber};
+        use tracing_subscriber::{field::Visit, layer::Context as LayerContext, prelude::*, Layer};
+
+        #[derive(Clone, Default)]
+        struct Records(Arc<Mutex<Vec<(Level, String)>>>);
+
+        struct Message<'a>(&'a mut String);
+        impl Visit for Message<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    *self.0 = format!("{:?}", value);
+                }
+            }
+        }
+
+        impl<S: Subscriber> Layer<S> for Records {
+            fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+                let mut msg = String::new();
+                event.record(&mut Message(&mut msg));
+                self.0.lock().push((*event.metadata().level(), msg));
+            }
+        }
+
+        let records = Records::default();
+        let _guard = tracing_subscriber::registry()
+            .with(records.clone())
+            .set_default();
+        let config = ActorConfig {
+            log_errors: true,
+            ..ActorConfig::default()
+        };
+
+        let (aref, handle) = actor!(config = config.clone(), TokioMailbox, TokioSpawner, |ctx| {
+            let n: u32 = ctx.receive().await?;
+            anyhow::ensure!(n > 0, "zero");
+            Ok(n)
+        });
+        aref.tell(0);
+        handle.await.unwrap().unwrap_err();
+        assert_eq!(
+            records.0.lock().drain(..).collect::<Vec<_>>(),
+            vec![(Level::ERROR, "actor failed: zero".to_owned())]
+        );
+
+        let (aref, handle) = actor!(config = config.clone(), TokioMailbox, TokioSpawner, |ctx| {
+            let n: u32 = ctx.receive().await?;
+            if n == 0 {
+                panic!("boom");
+            }
+            Ok(n)
+        });
+        aref.tell(0);
+        handle.await.unwrap_err();
+        assert_eq!(
+            records.0.lock().drain(..).collect::<Vec<_>>(),
+            vec![(Level::ERROR, "actor panicked: boom".to_owned())]
+        );
+
+        // succeeding is not logged
+        let (aref, handle) = actor!(config = config.clone(), TokioMailbox, TokioSpawner, |ctx| {
+            let n: u32 = ctx.receive().await?;
+            Ok(n)
+        });
+        aref.tell(1);
+        assert_eq!(handle.await.unwrap().unwrap(), 1);
+        assert_eq!(records.0.lock().len(), 0);
+
+        // stopping normally is no error
+        let (aref, handle) = actor!(config = config.clone(), TokioMailbox, TokioSpawner, |ctx| {
+            let _: u32 = ctx.receive().await?;
+            Ok(())
+        });
+        drop(aref);
+        handle.await.unwrap().unwrap_err();
+        assert_eq!(
+            records.0.lock().drain(..).collect::<Vec<_>>(),
+            vec![(
+                Level::DEBUG,
+                "actor stopped: cannot receive: no external ActorRef for this actor".to_owned()
+            )]
+        );
+
+        // also for actors spawned with the builder
+        let (aref, handle) = crate::ActorBuilder::new()
+            .mailbox(TokioMailbox)
+            .spawner(TokioSpawner)
+            .config(config)
+            .run(|mut ctx| async move {
+                let n: u32 = ctx.receive().await?;
+                anyhow::ensure!(n > 0, "zero");
+                Ok(n)
+            });
+        aref.tell(0);
+        handle.await.unwrap().unwrap_err();
+        assert_eq!(
+            records.0.lock().drain(..).collect::<Vec<_>>(),
+            vec![(Level::ERROR, "actor failed: zero".to_owned())]
+        );
+
+        // without the flag nothing is logged
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let n: u32 = ctx.receive().await?;
+            anyhow::ensure!(n > 0, "zero");
+            Ok(n)
+        });
+        aref.tell(0);
+        handle.await.unwrap().unwrap_err();
+        assert_eq!(records.0.lock().len(), 0);
+    }
+
+    #[cfg(feature = "with_metrics")]
+    #[tokio::test]
+    async fn metrics() {
+        use crate::{ActorMetrics, Metered, MetricsHandle, MetricsSink};
+
+        struct Timings(Arc<AtomicUsize>);
+        impl MetricsSink for Timings {
+            fn processing_time(&self, dur: Duration) {
+                assert!(dur >= Duration::from_millis(10));
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let timings = Arc::new(AtomicUsize::new(0));
+        let metrics = MetricsHandle::with_sink(Timings(timings.clone()));
+        let mailbox = Metered::new(TokioMailbox, metrics.clone());
+        let (aref, handle) = actor!(name = "metered", mailbox, TokioSpawner, |ctx| {
+            let _: u32 = ctx.receive().await?;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            let _: u32 = ctx.receive().await?;
+            Ok(())
+        });
+        for i in 0..3 {
+            aref.tell(i);
+        }
+        assert_eq!(
+            metrics.snapshot(),
+            ActorMetrics {
+                name: Some("metered".to_owned()),
+                received: 0,
+                sent: 3,
+                depth: 3
+            }
+        );
+        handle.await.unwrap().unwrap();
+        assert_eq!(
+            metrics.snapshot(),
+            ActorMetrics {
+                name: Some("metered".to_owned()),
+                received: 2,
+                sent: 3,
+                depth: 1
+            }
+        );
+        assert_eq!(timings.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn persistent() {
+        use crate::{
+            persistent, Cadence, FileStore, Persistent, RestartStrategy, SnapshotConfig, Supervised,
+        };
+
+        struct Counter(u32);
+
+        impl Persistent for Counter {
+            fn snapshot(&self) -> Vec<u8> {
+                self.0.to_le_bytes().to_vec()
+            }
+            fn restore(bytes: &[u8]) -> Self {
+                use std::convert::TryInto;
+                Counter(u32::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        }
+
+        fn count(counter: &mut Counter, msg: u32) -> Result<()> {
+            anyhow::ensure!(msg > 0, "crash");
+            counter.0 += msg;
+            Ok(())
+        }
+
+        let dir = std::env::temp_dir().join(format!("acto-persistent-{}", std::process::id()));
+        let config = SnapshotConfig::new(Arc::new(FileStore::new(&dir)), "counter")
+            .with_cadence(Cadence::EveryMessages(1));
+
+        // the restarted incarnation resumes from the snapshot taken before the crash
+        let supervisor = Supervised::new(TokioSpawner, RestartStrategy::Always);
+        let cfg = config.clone();
+        let (aref, handle) = supervisor.spawn(TokioMailbox, move |ctx: Context<u32>| {
+            persistent(ctx, cfg.clone(), || Counter(0), count)
+        });
+        for msg in [1, 2, 0, 3] {
+            aref.tell(msg);
+        }
+        drop(aref);
+        assert_eq!(handle.await.unwrap().unwrap().0, 6);
+
+        // and so does a new actor with the same name
+        let config = config.with_cadence(Cadence::OnStop);
+        let (aref, handle) =
+            actor!(TokioMailbox, TokioSpawner, fn persistent(ctx, config, || Counter(0), count));
+        aref.tell(4);
+        drop(aref);
+        assert_eq!(handle.await.unwrap().unwrap().0, 10);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn file_store_ids() {
+        use crate::{FileStore, SnapshotStore};
+
+        let dir = std::env::temp_dir().join(format!("acto-file-store-{}", std::process::id()));
+        let store = FileStore::new(dir.join("store"));
+        let ids = [
+            "../escaped",
+            "..",
+            "a/b",
+            "a\\b",
+            "%2F",
+            ".",
+            ".hidden",
+            "c:d",
+        ];
+        for (n, id) in ids.iter().enumerate() {
+            store.save(id, &[n as u8]).unwrap();
+        }
+        for (n, id) in ids.iter().enumerate() {
+            assert_eq!(store.load(id).unwrap(), Some(vec![n as u8]), "{}", id);
+        }
+        // all snapshots ended up as distinct files in the store’s directory
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        assert_eq!(
+            std::fs::read_dir(dir.join("store")).unwrap().count(),
+            ids.len()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn event_sourced() {
+        use crate::{event_sourced, EventSourced, EventStore, MemoryEventStore};
+
+        #[derive(Default)]
+        struct Account {
+            balance: u32,
+        }
+
+        enum Command {
+            Deposit(u32),
+            Withdraw(u32),
+        }
+
+        #[derive(Debug, Clone, PartialEq)]
+        enum Booked {
+            Credit(u32),
+            Debit(u32),
+        }
+
+        impl EventSourced for Account {
+            type Command = Command;
+            type Event = Booked;
+
+            fn handle_command(&self, cmd: Command) -> Vec<Booked> {
+                match cmd {
+                    Command::Deposit(n) => vec![Booked::Credit(n)],
+                    Command::Withdraw(n) if n <= self.balance => vec![Booked::Debit(n)],
+                    Command::Withdraw(_) => vec![],
+                }
+            }
+
+            fn apply_event(&mut self, event: &Booked) {
+                match event {
+                    Booked::Credit(n) => self.balance += n,
+                    Booked::Debit(n) => self.balance -= n,
+                }
+            }
+        }
+
+        let store = MemoryEventStore::new();
+        let (aref, handle) = actor!(
+            TokioMailbox,
+            TokioSpawner,
+            fn event_sourced(ctx, Arc::new(store.clone()), "account".to_owned(), Account::default())
+        );
+        aref.tell(Command::Deposit(5));
+        aref.tell(Command::Withdraw(7));
+        aref.tell(Command::Withdraw(3));
+        drop(aref);
+        assert_eq!(handle.await.unwrap().unwrap().balance, 2);
+        assert_eq!(
+            store.replay("account").unwrap(),
+            vec![Booked::Credit(5), Booked::Debit(3)]
+        );
+
+        // a new incarnation replays the log before handling commands
+        let (aref, handle) = actor!(
+            TokioMailbox,
+            TokioSpawner,
+            fn event_sourced(ctx, Arc::new(store.clone()), "account".to_owned(), Account::default())
+        );
+        aref.tell(Command::Withdraw(2));
+        drop(aref);
+        assert_eq!(handle.await.unwrap().unwrap().balance, 0);
+        assert_eq!(store.replay("account").unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn pipeline() {
+        use crate::Pipeline;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let (open, gate) = oneshot::channel::<()>();
+        let (sink, received) = actor!(
+            TokioBoundedMailbox::new(1, OnFull::Block),
+            TokioSpawner,
+            |ctx| {
+                gate.await?;
+                let mut received = Vec::new();
+                while received.len() < 100 {
+                    let msg: u32 = ctx.receive().await?;
+                    received.push(msg);
+                }
+                Ok(received)
+            }
+        );
+        let source = Pipeline::new(TokioBoundedMailbox::new(2, OnFull::Block), TokioSpawner)
+            .stage(|x: u32| async move { x + 1 })
+            .stage(|x| async move { x * 2 })
+            .into_ref(sink);
+
+        let sent = Arc::new(AtomicUsize::new(0));
+        let count = sent.clone();
+        let producer = tokio::spawn(async move {
+            for i in 0..100 {
+                source.send(i).await.ok().unwrap();
+                count.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        // two stages with queues of 2 and one message in hand each, plus the sink’s queue of 1
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(sent.load(Ordering::SeqCst) <= 7);
+        assert!(!producer.is_finished());
+
+        open.send(()).unwrap();
+        producer.await.unwrap();
+        let expected = (0..100).map(|i| (i + 1) * 2).collect::<Vec<_>>();
+        assert_eq!(received.await.unwrap().unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn drain_after_drop() {
+        async fn collect(mut ctx: Context<u32>) -> Result<(Vec<u32>, anyhow::Error)> {
+            let mut received = Vec::new();
+            loop {
+                match ctx.receive().await {
+                    Ok(msg) => received.push(msg),
+                    Err(err) => return Ok((received, err)),
+                }
+            }
+        }
+
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, fn collect(ctx));
+        let (bounded, bounded_handle) = actor!(
+            TokioBoundedMailbox::new(5, OnFull::DropNewest),
+            TokioSpawner,
+            fn collect(ctx)
+        );
+        for i in 0..5 {
+            aref.tell(i);
+            bounded.tell(i);
+        }
+        drop(aref);
+        drop(bounded);
+
+        for handle in [Box::pin(handle), Box::pin(bounded_handle)] {
+            let (received, err) = handle.await.unwrap().unwrap();
+            assert_eq!(received, vec![0, 1, 2, 3, 4]);
+            assert!(err.is::<NoActorRef>());
+        }
+    }
+
+    #[tokio::test]
+    async fn closed_receiver() {
+        let (aref, mut recv) = TokioMailbox.make_mailbox::<u32>();
+        drop(aref);
+        recv.receive()
+            .await
+            .unwrap_err()
+            .downcast::<NoActorRef>()
+            .unwrap();
+
+        let (aref, mut recv) = TokioBoundedMailbox::new(1, OnFull::Block).make_mailbox::<u32>();
+        aref.tell(1);
+        drop(aref);
+        assert_eq!(recv.receive().await.unwrap(), 1);
+        recv.receive()
+            .await
+            .unwrap_err()
+            .downcast::<NoActorRef>()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn named() {
+        struct Recording(Arc<Mutex<Vec<String>>>);
+
+        impl Spawner for Recording {
+            fn spawn(&self, fut: FutureBox) -> FutureResultBox {
+                self.0.lock().push("<unnamed>".to_owned());
+                TokioSpawner.spawn(fut)
+            }
+
+            fn spawn_named(&self, name: &str, fut: FutureBox) -> FutureResultBox {
+                self.0.lock().push(name.to_owned());
+                TokioSpawner.spawn_named(name, fut)
+            }
+        }
+
+        async fn echo(mut ctx: Context<u32>) -> Result<u32> {
+            ctx.receive().await
+        }
+
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let (aref, handle) = actor!(
+            name = "parent",
+            TokioMailbox,
+            Recording(names.clone()),
+            |ctx| {
+                let (child, child_handle) = actor!(name = "child", TokioMailbox, |ctx| {
+                    let msg: u32 = ctx.receive().await?;
+                    Ok(msg)
+                });
+                let (anonymous, anonymous_handle) = actor!(TokioMailbox, |ctx| {
+                    let msg: u32 = ctx.receive().await?;
+                    Ok(msg)
+                });
+                let msg: u32 = ctx.receive().await?;
+                child.tell(msg);
+                anonymous.tell(msg);
+                Ok(child_handle.await?? + anonymous_handle.await??)
+            }
+        );
+        let (other, other_handle) = actor!(
+            name = &format!("echo-{}", 1),
+            TokioMailbox,
+            Recording(names.clone()),
+            fn echo(ctx)
+        );
+        aref.tell(21);
+        other.tell(1);
+        assert_eq!(handle.await.unwrap().unwrap(), 42);
+        assert_eq!(other_handle.await.unwrap().unwrap(), 1);
+        let mut names = names.lock().clone();
+        names.sort();
+        assert_eq!(names, vec!["<unnamed>", "child", "echo-1", "parent"]);
+    }
+
+    #[tokio::test]
+    async fn initial_state() {
+        fn adder(
+            offset: u32,
+        ) -> (
+            ActorRef<u32>,
+            impl Future<Output = Result<Result<Vec<u32>>>>,
+        ) {
+            actor!(
+                TokioMailbox,
+                TokioSpawner,
+                state = (offset, Vec::new()),
+                |ctx, state| {
+                    while state.1.len() < 2 {
+                        let msg: u32 = ctx.receive().await?;
+                        state.1.push(msg + state.0);
+                    }
+                    Ok(state.1)
+                }
+            )
+        }
+
+        async fn multiplier(mut ctx: Context<u32>, factor: u32) -> Result<u32> {
+            Ok(ctx.receive().await? * factor)
+        }
+
+        let (one, one_handle) = adder(1);
+        let (ten, ten_handle) = adder(10);
+        let (times, times_handle) =
+            actor!(TokioMailbox, TokioSpawner, state = 3, fn multiplier(ctx));
+        for msg in [1, 2] {
+            one.tell(msg);
+            ten.tell(msg);
+        }
+        times.tell(5);
+        assert_eq!(one_handle.await.unwrap().unwrap(), vec![2, 3]);
+        assert_eq!(ten_handle.await.unwrap().unwrap(), vec![11, 12]);
+        assert_eq!(times_handle.await.unwrap().unwrap(), 15);
+    }
+
+    #[tokio::test]
+    async fn close() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            let err = loop {
+                match ctx.receive().await {
+                    Ok(msg) => received.push(msg),
+                    Err(err) => break err,
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            };
+            Ok((received, err.is::<NoActorRef>()))
+        });
+        let mapped = aref.clone().contramap(|x: u32| x * 2);
+        for i in 0..5 {
+            aref.tell(i);
+        }
+        mapped.close();
+        assert!(aref.try_tell(5).is_err());
+        assert!(mapped.try_tell(6).is_err());
+
+        // the ActorRefs are still around, yet the actor stops after processing the queue
+        let (received, closed) = handle.await.unwrap().unwrap();
+        assert_eq!(received, vec![0, 1, 2, 3, 4]);
+        assert!(closed);
+        drop(aref);
+    }
+
+    #[tokio::test]
+    async fn is_alive() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: u32 = ctx.receive().await?;
+            Ok(msg)
+        });
+        assert!(aref.is_alive());
+        aref.tell(1);
+        assert_eq!(handle.await.unwrap().unwrap(), 1);
+        assert!(!aref.is_alive());
+
+        let (aref, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let msg: u32 = ctx.receive().await?;
+            Ok(msg)
+        });
+        aref.close();
+        assert!(!aref.is_alive());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn tell_all() {
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            while received.len() < 200 {
+                let msg: (u32, u32) = ctx.receive().await?;
+                received.push(msg);
+            }
+            Ok(received)
+        });
+        let producers = (0..2)
+            .map(|producer| {
+                let aref = aref.clone();
+                tokio::spawn(async move {
+                    for batch in 0..10 {
+                        aref.tell_all((0..10).map(|i| (producer, batch * 10 + i)));
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for producer in producers {
+            producer.await.unwrap();
+        }
+
+        let received = handle.await.unwrap().unwrap();
+        for producer in 0..2 {
+            let seq = received
+                .iter()
+                .filter(|(p, _)| *p == producer)
+                .map(|(_, seq)| *seq)
+                .collect::<Vec<_>>();
+            assert_eq!(seq, (0..100).collect::<Vec<_>>());
+        }
+    }
+
+    #[tokio::test]
+    async fn context_ask() {
+        let (b, b_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let (x, reply): (u32, ActorRef<u32>) = ctx.receive().await?;
+            reply.tell(x * 10);
+            // the second request is not answered
+            let (_, reply) = ctx.receive().await?;
+            drop(reply);
+            Ok(())
+        });
+        let (a, a_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut results = Vec::new();
+            while results.len() < 3 {
+                let x: u32 = ctx.receive().await?;
+                let answer = ctx.ask(&b, |reply| (x, reply)).await;
+                results.push(answer.map_err(|e| e.is::<NoActorRef>()));
+            }
+            Ok(results)
+        });
+        // the later messages are buffered while the first is being handled
+        a.tell(1);
+        a.tell(2);
+        a.tell(3);
+        b_handle.await.unwrap().unwrap();
+        assert_eq!(
+            a_handle.await.unwrap().unwrap(),
+            vec![Ok(10), Err(true), Err(true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn reply() {
+        use crate::Reply;
+
+        let (aref, join_handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            loop {
+                let (name, reply): (String, Reply<String>) = match ctx.receive().await {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                };
+                if name.is_empty() {
+                    // dropping the Reply without answering
+                    continue;
+                }
+                let answer = format!("Hello {}!", name);
+                if let Err(e) = reply.send(answer) {
+                    // nobody listens to this one
+                    assert_eq!(e.0, "Hello Dino!");
+                }
+            }
+            Ok(())
+        });
+
+        let (reply, answer) = Reply::new();
+        aref.tell(("Fred".to_owned(), reply));
+        assert_eq!(answer.await.unwrap(), "Hello Fred!");
+
+        let (reply, answer) = Reply::new();
+        aref.tell((String::new(), reply));
+        answer.await.unwrap_err().downcast::<NoActorRef>().unwrap();
+
+        let (reply, answer) = Reply::new();
+        drop(answer);
+        aref.tell(("Dino".to_owned(), reply));
+
+        drop(aref);
+        join_handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn scatter_gather() {
+        use crate::{scatter_gather, GatherPolicy, Reply};
+
+        async fn worker(mut ctx: Context<(u32, Reply<u32>)>, factor: u32) -> Result<()> {
+            let mut stuck = Vec::new();
+            loop {
+                let (x, reply) = ctx.receive().await?;
+                if factor == 0 {
+                    // never replies, but keeps the Reply around
+                    stuck.push(reply);
+                } else {
+                    let _ = reply.send(x * factor);
+                }
+            }
+        }
+
+        let workers = [1, 0, 3]
+            .iter()
+            .map(|factor| actor!(TokioMailbox, TokioSpawner, fn worker(ctx, *factor)).0)
+            .collect::<Vec<_>>();
+
+        let mut results =
+            scatter_gather(&TokioSpawner, &workers, |r| (2, r), GatherPolicy::FirstN(2))
+                .await
+                .into_iter()
+                .map(|r| r.unwrap())
+                .collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(results, vec![2, 6]);
+
+        let results = scatter_gather(
+            &TokioSpawner,
+            &workers,
+            |r| (1, r),
+            GatherPolicy::WithinTimeout(Duration::from_millis(100)),
+        )
+        .await;
+        assert_eq!(results.len(), 2);
+
+        // a stopped target yields an error, so All completes
+        let workers = vec![
+            workers[0].clone(),
+            workers[2].clone(),
+            ActorRef::new(Box::new(Err)),
+        ];
+        let results = scatter_gather(&TokioSpawner, &workers, |r| (1, r), GatherPolicy::All).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn schedule() {
+        enum Debounce {
+            Input(u32),
+            Flush,
+        }
+
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let mut latest = 0;
+            let mut flushed = Vec::new();
+            let mut pending = None;
+            while flushed.len() < 2 {
+                match ctx.receive().await? {
+                    Debounce::Input(x) => {
+                        latest = x;
+                        // replacing the handle cancels the previously scheduled flush
+                        pending = Some(ctx.schedule(Debounce::Flush, Duration::from_millis(50)));
+                    }
+                    Debounce::Flush => {
+                        flushed.push(latest);
+                        pending = None;
+                    }
+                }
+            }
+            drop(pending);
+            Ok(flushed)
+        });
+        for x in 1..=3 {
+            aref.tell(Debounce::Input(x));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        aref.tell(Debounce::Input(4));
+        assert_eq!(handle.await.unwrap().unwrap(), vec![3, 4]);
+
+        // a scheduled message does not keep the actor alive
+        let (aref, handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+            let _flush = ctx.schedule(1, Duration::from_millis(10));
+            let msg: Result<u32> = ctx.receive().await;
+            Ok(msg.is_err())
+        });
+        drop(aref);
+        assert!(handle.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn ring() {
+        use crate::RingMailbox;
+
+        let (aref, recv) = RingMailbox::new(3).make_mailbox::<u32>();
+        for i in 0..10 {
+            aref.try_tell(i).unwrap();
+        }
+        assert_eq!(aref.len(), 3);
+        assert_eq!(drain(recv, 3).await, vec![7, 8, 9]);
+        aref.try_tell(10).unwrap_err();
+
+        let (aref, handle) = actor!(RingMailbox::new(2), TokioSpawner, |ctx| {
+            let mut received = Vec::new();
+            while received.len() < 3 {
+                let msg: u32 = ctx.receive().await?;
+                received.push(msg);
+            }
+            Ok(received)
+        });
+        aref.tell(1);
+        aref.tell(2);
+        aref.tell(3);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        aref.tell(4);
+        assert_eq!(handle.await.unwrap().unwrap(), vec![2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn coalescing() {
+        use crate::{CoalesceOrder, CoalescingMailbox, MailboxFor};
+
+        fn key(msg: &(&'static str, u32)) -> &'static str {
+            msg.0
+        }
+
+        let (aref, mut recv) = CoalescingMailbox::new(key).make_mailbox_for();
+        for msg in [("x", 1), ("y", 1), ("x", 2), ("x", 3)] {
+            aref.tell(msg);
+        }
+        assert_eq!(aref.len(), 2);
+        assert_eq!(recv.receive().await.unwrap(), ("x", 3));
+        aref.tell(("x", 4));
+        assert_eq!(recv.receive().await.unwrap(), ("y", 1));
+        assert_eq!(recv.receive().await.unwrap(), ("x", 4));
+
+        let mailbox = CoalescingMailbox::new(key).with_order(CoalesceOrder::MoveToBack);
+        let (aref, mut recv) = mailbox.make_mailbox_for();
+        for msg in [("x", 1), ("y", 1), ("x", 2), ("x", 3)] {
+            aref.tell(msg);
+        }
+        assert_eq!(aref.len(), 2);
+        assert_eq!(recv.receive().await.unwrap(), ("y", 1));
+        assert_eq!(recv.receive().await.unwrap(), ("x", 3));
+    }
+
+    async fn drain(mut recv: Box<dyn Receiver<u32>>, n: usize) -> Vec<u32> {
+        let mut v = Vec::new();
+        for _ in 0..n {
+            v.push(recv.receive().await.unwrap());
+        }
+        v
+    }
+
+    #[tokio::test]
+    async fn bounded() {
+        let (aref, recv) = TokioBoundedMailbox::new(2, OnFull::DropNewest).make_mailbox();
+        (1..=3).for_each(|n| aref.tell(n));
+        assert_eq!(drain(recv, 2).await, vec![1, 2]);
+
+        let (aref, recv) = TokioBoundedMailbox::new(2, OnFull::DropOldest).make_mailbox();
+        (1..=3).for_each(|n| aref.tell(n));
+        assert_eq!(drain(recv, 2).await, vec![2, 3]);
+
+        let (aref, recv) = TokioBoundedMailbox::new(2, OnFull::Block).make_mailbox();
+        let sender = std::thread::spawn(move || (1..=3).for_each(|n| aref.tell(n)));
+        assert_eq!(drain(recv, 3).await, vec![1, 2, 3]);
+        sender.join().unwrap();
+    }
 }
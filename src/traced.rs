@@ -0,0 +1,86 @@
+use crate::{ActorRef, MailboxFor, Receiver};
+use anyhow::Result;
+use std::{future::Future, pin::Pin, sync::Arc, task::Poll};
+
+type Callback<M> = Arc<dyn Fn(&M) + Send + Sync>;
+
+/// Mailbox decorator that calls back for every message sent to or received by the actor
+///
+/// Typical uses are counting throughput or logging message types, e.g. with the `tracing`
+/// crate. Without any callbacks the inner mailbox is used as is, so there is no overhead.
+pub struct Traced<MB, M> {
+    inner: MB,
+    on_tell: Option<Callback<M>>,
+    on_receive: Option<Callback<M>>,
+}
+
+impl<MB, M> Traced<MB, M> {
+    pub fn new(inner: MB) -> Self {
+        Self {
+            inner,
+            on_tell: None,
+            on_receive: None,
+        }
+    }
+
+    /// Call `f` with each message sent to the actor, before it is enqueued
+    pub fn on_tell(self, f: impl Fn(&M) + Send + Sync + 'static) -> Self {
+        Self {
+            on_tell: Some(Arc::new(f)),
+            ..self
+        }
+    }
+
+    /// Call `f` with each message received by the actor, before `receive()` returns it
+    pub fn on_receive(self, f: impl Fn(&M) + Send + Sync + 'static) -> Self {
+        Self {
+            on_receive: Some(Arc::new(f)),
+            ..self
+        }
+    }
+}
+
+impl<M: Send + 'static, MB: MailboxFor<M>> MailboxFor<M> for Traced<MB, M> {
+    fn make_mailbox_for(&self) -> (ActorRef<M>, Box<dyn Receiver<M>>) {
+        let (mut aref, mut recv) = self.inner.make_mailbox_for();
+        if let Some(f) = &self.on_tell {
+            let f = f.clone();
+            aref = aref.inspect(move |msg| f(msg));
+        }
+        if let Some(f) = &self.on_receive {
+            recv = Box::new(TracedReceiver {
+                inner: recv,
+                f: f.clone(),
+            });
+        }
+        (aref, recv)
+    }
+}
+
+struct TracedReceiver<M> {
+    inner: Box<dyn Receiver<M>>,
+    f: Callback<M>,
+}
+
+impl<M: Send + 'static> Receiver<M> for TracedReceiver<M> {
+    fn receive(&mut self) -> &mut (dyn Future<Output = Result<M>> + Send + Unpin + '_) {
+        self
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.inner.set_name(name)
+    }
+}
+
+impl<M: Send + 'static> Future for TracedReceiver<M> {
+    type Output = Result<M>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let result = Pin::new(this.inner.receive()).poll(cx);
+        if let Poll::Ready(Ok(msg)) = &result {
+            (this.f)(msg);
+        }
+        result
+    }
+}
@@ -0,0 +1,189 @@
+use crate::NoActorRef;
+use anyhow::Result;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+use std::{
+    collections::HashMap,
+    future::poll_fn,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Poll, Waker},
+};
+
+/// The latest value of some actor state, readable without going through the actor’s mailbox
+///
+/// The actor keeps the `Watchable` and [`set`](#method.set)s it whenever its state changes, while
+/// any number of readers hold a [`Watcher`](struct.Watcher.html) for looking at the current value
+/// or waiting for the next change. Readers only see the latest value: when it is set several
+/// times in quick succession, the intermediate values may be missed.
+///
+/// ```
+/// use acto::{actor, Result, Watchable};
+/// use acto::tokio::{TokioMailbox, TokioSpawner};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<()> {
+///     let total = Watchable::new(0u64);
+///     let mut watcher = total.watch();
+///     let (adder, _handle) = actor!(TokioMailbox, TokioSpawner, |ctx| {
+///         while let Ok(n) = ctx.receive().await {
+///             total.modify(|t| *t += n);
+///         }
+///         Ok(())
+///     });
+///     adder.tell(5u64);
+///     watcher.changed().await?;
+///     assert_eq!(*watcher.borrow(), 5);
+///     Ok(())
+/// }
+/// ```
+pub struct Watchable<S> {
+    shared: Arc<Shared<S>>,
+}
+
+/// Read access to a [`Watchable`](struct.Watchable.html)
+///
+/// Each `Watcher` tracks which version of the value it has seen, clones start out with the
+/// original’s version.
+pub struct Watcher<S> {
+    shared: Arc<Shared<S>>,
+    seen: u64,
+    id: u64,
+}
+
+struct Shared<S> {
+    value: RwLock<S>,
+    version: AtomicU64,
+    closed: AtomicBool,
+    wakers: Mutex<HashMap<u64, Waker>>,
+    next_id: AtomicU64,
+}
+
+impl<S> Shared<S> {
+    fn wake_all(&self) {
+        for (_, waker) in self.wakers.lock().drain() {
+            waker.wake();
+        }
+    }
+}
+
+impl<S> Watchable<S> {
+    pub fn new(initial: S) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                value: RwLock::new(initial),
+                version: AtomicU64::new(0),
+                closed: AtomicBool::new(false),
+                wakers: Mutex::new(HashMap::new()),
+                next_id: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Replace the value and notify all watchers
+    pub fn set(&self, value: S) {
+        self.modify(|v| *v = value);
+    }
+
+    /// Change the value in place and notify all watchers
+    pub fn modify(&self, f: impl FnOnce(&mut S)) {
+        let mut value = self.shared.value.write();
+        f(&mut value);
+        self.shared.version.fetch_add(1, Ordering::AcqRel);
+        drop(value);
+        self.shared.wake_all();
+    }
+
+    /// The current value, which cannot be set while the guard is held
+    pub fn borrow(&self) -> RwLockReadGuard<'_, S> {
+        self.shared.value.read()
+    }
+
+    /// A new watcher that has seen the current value
+    pub fn watch(&self) -> Watcher<S> {
+        Watcher {
+            shared: self.shared.clone(),
+            seen: self.shared.version.load(Ordering::Acquire),
+            id: self.shared.next_id.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// The number of watchers that currently exist
+    pub fn watcher_count(&self) -> usize {
+        Arc::strong_count(&self.shared) - 1
+    }
+}
+
+impl<S> Drop for Watchable<S> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.wake_all();
+    }
+}
+
+impl<S> Watcher<S> {
+    /// The current value, which cannot be set while the guard is held
+    ///
+    /// This does not mark the value as seen, see [`borrow_and_update`](#method.borrow_and_update).
+    pub fn borrow(&self) -> RwLockReadGuard<'_, S> {
+        self.shared.value.read()
+    }
+
+    /// The current value, marking it as seen
+    pub fn borrow_and_update(&mut self) -> RwLockReadGuard<'_, S> {
+        let value = self.shared.value.read();
+        // read under the lock, so that the version matches the value
+        self.seen = self.shared.version.load(Ordering::Acquire);
+        value
+    }
+
+    /// Whether the value has been set since it was last seen
+    pub fn has_changed(&self) -> bool {
+        self.shared.version.load(Ordering::Acquire) != self.seen
+    }
+
+    /// Wait until the value has been set since it was last seen, then mark it as seen
+    ///
+    /// Fails with [`NoActorRef`](struct.NoActorRef.html) once the `Watchable` has been dropped
+    /// and there is no unseen value; the last value can still be borrowed after that.
+    pub async fn changed(&mut self) -> Result<()> {
+        poll_fn(|cx| {
+            for attempt in 0..2 {
+                let version = self.shared.version.load(Ordering::Acquire);
+                if version != self.seen {
+                    self.seen = version;
+                    return Poll::Ready(Ok(()));
+                }
+                if self.shared.closed.load(Ordering::Acquire) {
+                    return Poll::Ready(Err(NoActorRef.into()));
+                }
+                // check again after installing the waker, in case of a concurrent update
+                if attempt == 0 {
+                    self.shared
+                        .wakers
+                        .lock()
+                        .insert(self.id, cx.waker().clone());
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+impl<S> Clone for Watcher<S> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            seen: self.seen,
+            id: self.shared.next_id.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl<S> Drop for Watcher<S> {
+    fn drop(&mut self) {
+        self.shared.wakers.lock().remove(&self.id);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{ActorId, Spawner};
+use parking_lot::Mutex;
+use std::{
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+// tracks whether the actor is processing a message, see Context::set_watchdog
+pub(crate) struct Watchdog {
+    state: Arc<Mutex<State>>,
+    spawner: Arc<dyn Spawner>,
+}
+
+#[derive(Default)]
+struct State {
+    // when the message being processed was received, numbered to report each one at most once
+    busy: Option<(Instant, u64)>,
+    received: u64,
+    reported: u64,
+}
+
+impl Watchdog {
+    pub(crate) fn start(
+        id: ActorId,
+        spawner: Arc<dyn Spawner>,
+        limit: Duration,
+        mut on_stuck: impl FnMut(ActorId, Duration) + Send + 'static,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let weak = Arc::downgrade(&state);
+        let timer = spawner.clone();
+        drop(crate::spawn(&*spawner, async move {
+            while let Some(wait) = check(&weak, &*timer, limit, id, &mut on_stuck) {
+                timer.delay(wait).await;
+            }
+        }));
+        Self { state, spawner }
+    }
+
+    pub(crate) fn received(&self) {
+        let mut state = self.state.lock();
+        state.received += 1;
+        state.busy = Some((self.spawner.now(), state.received));
+    }
+
+    pub(crate) fn done(&self) {
+        self.state.lock().busy = None;
+    }
+}
+
+// reports a stuck message if needed, returning how long to wait before checking again
+fn check(
+    state: &Weak<Mutex<State>>,
+    spawner: &dyn Spawner,
+    limit: Duration,
+    id: ActorId,
+    on_stuck: &mut dyn FnMut(ActorId, Duration),
+) -> Option<Duration> {
+    let state = state.upgrade()?;
+    let mut state = state.lock();
+    match state.busy {
+        Some((since, seq)) if seq != state.reported => {
+            let elapsed = spawner.now().saturating_duration_since(since);
+            if elapsed < limit {
+                return Some(limit - elapsed);
+            }
+            state.reported = seq;
+            drop(state);
+            on_stuck(id, elapsed);
+            Some(limit)
+        }
+        _ => Some(limit),
+    }
+}